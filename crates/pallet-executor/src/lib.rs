@@ -25,7 +25,7 @@ use frame_support::traits::Get;
 use frame_system::offchain::SubmitTransaction;
 pub use pallet::*;
 use sp_executor::{
-    BundleEquivocationProof, FraudProof, InvalidTransactionProof, SignedExecutionReceipt,
+    BundleEquivocationProof, DomainId, FraudProof, InvalidTransactionProof, SignedExecutionReceipt,
     SignedOpaqueBundle,
 };
 use sp_runtime::traits::{BlockNumberProvider, One};
@@ -90,6 +90,8 @@ mod pallet {
         UnexpectedSigner,
         /// Invalid transaction bundle signature.
         BadSignature,
+        /// The bundle is produced for a domain unknown to the primary chain.
+        UnknownDomain,
     }
 
     impl<T> From<BundleError> for Error<T> {
@@ -620,6 +622,11 @@ impl<T: Config> Pallet<T> {
             return Err(BundleError::BadSignature);
         }
 
+        // Only the system domain is supported by the primary chain for now.
+        if opaque_bundle.header.domain_id != DomainId::SYSTEM {
+            return Err(BundleError::UnknownDomain);
+        }
+
         // TODO: upgrade once the trusted executor system is upgraded.
         let expected_executor = Self::executor()
             .map(|(_, authority_id)| authority_id)
@@ -7,10 +7,12 @@ use frame_support::{assert_noop, assert_ok, parameter_types};
 use sp_core::crypto::Pair;
 use sp_core::{H256, U256};
 use sp_executor::{
-    ExecutionPhase, ExecutionReceipt, ExecutorPair, FraudProof, SignedExecutionReceipt,
+    BundleHeader, DomainId, ExecutionPhase, ExecutionReceipt, ExecutorPair, FraudProof,
+    OpaqueBundle, SignedExecutionReceipt, SignedOpaqueBundle,
 };
 use sp_runtime::testing::Header;
-use sp_runtime::traits::{BlakeTwo256, IdentityLookup};
+use sp_runtime::traits::{BlakeTwo256, IdentityLookup, ValidateUnsigned};
+use sp_runtime::transaction_validity::{InvalidTransaction, TransactionSource};
 use sp_trie::StorageProof;
 
 type UncheckedExtrinsic = frame_system::mocking::MockUncheckedExtrinsic<Test>;
@@ -246,3 +248,44 @@ fn submit_fraud_proof_should_work() {
         });
     });
 }
+
+#[test]
+fn bundle_of_unknown_domain_should_be_rejected() {
+    let pair = ExecutorPair::from_seed(&U256::from(100u32).into());
+    let signed_opaque_bundle = |domain_id| {
+        let opaque_bundle = OpaqueBundle {
+            header: BundleHeader {
+                domain_id,
+                primary_hash: Hash::random(),
+                slot_number: 1,
+                extrinsics_root: Default::default(),
+            },
+            opaque_extrinsics: Vec::new(),
+        };
+        SignedOpaqueBundle {
+            signature: pair.sign(opaque_bundle.hash().as_ref()),
+            signer: pair.public(),
+            opaque_bundle,
+        }
+    };
+
+    new_test_ext().execute_with(|| {
+        assert!(<Executor as ValidateUnsigned>::validate_unsigned(
+            TransactionSource::External,
+            &crate::Call::submit_transaction_bundle {
+                signed_opaque_bundle: signed_opaque_bundle(DomainId::SYSTEM),
+            },
+        )
+        .is_ok());
+
+        assert_eq!(
+            <Executor as ValidateUnsigned>::validate_unsigned(
+                TransactionSource::External,
+                &crate::Call::submit_transaction_bundle {
+                    signed_opaque_bundle: signed_opaque_bundle(DomainId::new(1)),
+                },
+            ),
+            Err(InvalidTransaction::Custom(crate::InvalidTransactionCode::Bundle as u8).into())
+        );
+    });
+}
@@ -0,0 +1,163 @@
+//! Versioned envelope of [`FraudProof`] for the external verifiers.
+//!
+//! The envelope has a fixed layout independent of SCALE so that it can be parsed without the
+//! Substrate type stack. All integers are little-endian.
+//!
+//! Version 1:
+//!
+//! | Field             | Size                 | Description                                      |
+//! |-------------------|----------------------|--------------------------------------------------|
+//! | magic             | 4                    | `b"SFPE"`                                        |
+//! | version           | 1                    | `1`                                              |
+//! | parent_number     | 4                    | `u32`                                            |
+//! | parent_hash       | 32                   |                                                  |
+//! | pre_state_root    | 32                   |                                                  |
+//! | post_state_root   | 32                   |                                                  |
+//! | execution_phase   | 1                    | `0`: initialize_block, `1`: apply_extrinsic, `2`: finalize_block |
+//! | call_data_len     | 4                    | `u32`, `0` for finalize_block                    |
+//! | call_data         | call_data_len        |                                                  |
+//! | node_count        | 4                    | `u32`, number of the storage proof trie nodes    |
+//! | nodes             | node_count × (4 + n) | `u32` length `n` followed by the node            |
+
+use crate::{ExecutionPhase, FraudProof};
+use sp_core::H256;
+use sp_std::vec::Vec;
+use sp_trie::StorageProof;
+
+/// Magic bytes the envelope starts with.
+pub const FRAUD_PROOF_ENVELOPE_MAGIC: [u8; 4] = *b"SFPE";
+
+/// Latest version of the envelope layout.
+pub const FRAUD_PROOF_ENVELOPE_VERSION: u8 = 1;
+
+/// Error of decoding a fraud proof envelope.
+#[derive(Debug, PartialEq, Eq)]
+pub enum EnvelopeError {
+    /// The bytes don't start with [`FRAUD_PROOF_ENVELOPE_MAGIC`].
+    BadMagic,
+    /// The envelope version is not supported.
+    UnsupportedVersion(u8),
+    /// The envelope ends before all the fields are read.
+    Truncated,
+    /// Unknown execution phase tag.
+    UnknownExecutionPhase(u8),
+    /// The call data of an execution phase that takes none, i.e., finalize_block, is not empty.
+    UnexpectedCallData,
+    /// There are bytes left after all the fields are read.
+    TrailingBytes,
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], EnvelopeError> {
+        if self.bytes.len() < len {
+            return Err(EnvelopeError::Truncated);
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn u8(&mut self) -> Result<u8, EnvelopeError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, EnvelopeError> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes(
+            bytes.try_into().expect("Slice has the length of u32; qed"),
+        ))
+    }
+
+    fn h256(&mut self) -> Result<H256, EnvelopeError> {
+        Ok(H256::from_slice(self.take(32)?))
+    }
+
+    fn bytes(&mut self) -> Result<Vec<u8>, EnvelopeError> {
+        let len = self.u32()? as usize;
+        Ok(self.take(len)?.to_vec())
+    }
+}
+
+fn push_bytes(envelope: &mut Vec<u8>, bytes: &[u8]) {
+    envelope.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+    envelope.extend_from_slice(bytes);
+}
+
+impl FraudProof {
+    /// Encodes the fraud proof into the latest version of the envelope.
+    pub fn to_envelope(&self) -> Vec<u8> {
+        let mut envelope = Vec::new();
+        envelope.extend_from_slice(&FRAUD_PROOF_ENVELOPE_MAGIC);
+        envelope.push(FRAUD_PROOF_ENVELOPE_VERSION);
+        envelope.extend_from_slice(&self.parent_number.to_le_bytes());
+        envelope.extend_from_slice(self.parent_hash.as_bytes());
+        envelope.extend_from_slice(self.pre_state_root.as_bytes());
+        envelope.extend_from_slice(self.post_state_root.as_bytes());
+
+        let phase_tag = match self.execution_phase {
+            ExecutionPhase::InitializeBlock { .. } => 0u8,
+            ExecutionPhase::ApplyExtrinsic { .. } => 1,
+            ExecutionPhase::FinalizeBlock => 2,
+        };
+        envelope.push(phase_tag);
+        push_bytes(&mut envelope, self.execution_phase.call_data());
+
+        let nodes = self.proof.clone().into_nodes();
+        envelope.extend_from_slice(&(nodes.len() as u32).to_le_bytes());
+        for node in nodes.iter() {
+            push_bytes(&mut envelope, node);
+        }
+
+        envelope
+    }
+
+    /// Decodes the fraud proof from the envelope.
+    pub fn from_envelope(envelope: &[u8]) -> Result<Self, EnvelopeError> {
+        let mut reader = Reader { bytes: envelope };
+
+        if reader.take(FRAUD_PROOF_ENVELOPE_MAGIC.len())? != FRAUD_PROOF_ENVELOPE_MAGIC {
+            return Err(EnvelopeError::BadMagic);
+        }
+        let version = reader.u8()?;
+        if version != FRAUD_PROOF_ENVELOPE_VERSION {
+            return Err(EnvelopeError::UnsupportedVersion(version));
+        }
+
+        let parent_number = reader.u32()?;
+        let parent_hash = reader.h256()?;
+        let pre_state_root = reader.h256()?;
+        let post_state_root = reader.h256()?;
+
+        let phase_tag = reader.u8()?;
+        let call_data = reader.bytes()?;
+        let execution_phase = match phase_tag {
+            0 => ExecutionPhase::InitializeBlock { call_data },
+            1 => ExecutionPhase::ApplyExtrinsic { call_data },
+            2 if call_data.is_empty() => ExecutionPhase::FinalizeBlock,
+            2 => return Err(EnvelopeError::UnexpectedCallData),
+            tag => return Err(EnvelopeError::UnknownExecutionPhase(tag)),
+        };
+
+        let node_count = reader.u32()?;
+        let nodes = (0..node_count)
+            .map(|_| reader.bytes())
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if !reader.bytes.is_empty() {
+            return Err(EnvelopeError::TrailingBytes);
+        }
+
+        Ok(Self {
+            parent_number,
+            parent_hash,
+            pre_state_root,
+            post_state_root,
+            proof: StorageProof::new(nodes),
+            execution_phase,
+        })
+    }
+}
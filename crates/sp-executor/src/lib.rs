@@ -17,6 +17,12 @@
 
 #![cfg_attr(not(feature = "std"), no_std)]
 
+mod fraud_proof_envelope;
+
+pub use fraud_proof_envelope::{
+    EnvelopeError, FRAUD_PROOF_ENVELOPE_MAGIC, FRAUD_PROOF_ENVELOPE_VERSION,
+};
+
 use parity_scale_codec::{Decode, Encode};
 use scale_info::TypeInfo;
 use sp_consensus_slots::Slot;
@@ -59,9 +65,35 @@ impl sp_runtime::BoundToRuntimeAppPublic for ExecutorKey {
     type Public = ExecutorId;
 }
 
+/// Identifier of an execution domain.
+#[derive(
+    Debug, Decode, Encode, TypeInfo, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy, Default,
+)]
+pub struct DomainId(u32);
+
+impl DomainId {
+    /// The system domain.
+    pub const SYSTEM: Self = Self(0);
+
+    /// Creates a new domain id.
+    pub const fn new(id: u32) -> Self {
+        Self(id)
+    }
+}
+
+impl From<u32> for DomainId {
+    fn from(id: u32) -> Self {
+        Self(id)
+    }
+}
+
 /// Header of transaction bundle.
 #[derive(Debug, Decode, Encode, TypeInfo, PartialEq, Eq, Clone)]
 pub struct BundleHeader {
+    /// The domain the bundle is produced for.
+    ///
+    /// Introduced in `ExecutorApi` version 2 and the executor gossip protocol version 2.
+    pub domain_id: DomainId,
     /// The hash of primary block at which the bundle was created.
     pub primary_hash: PHash,
     /// The slot number.
@@ -347,6 +379,7 @@ impl BundleEquivocationProof {
     /// Constructs a dummy bundle equivocation proof.
     pub fn dummy_at(slot_number: u64) -> Self {
         let dummy_header = BundleHeader {
+            domain_id: DomainId::SYSTEM,
             primary_hash: PHash::default(),
             slot_number,
             extrinsics_root: H256::default(),
@@ -362,11 +395,26 @@ impl BundleEquivocationProof {
 }
 
 /// Represents an invalid transaction proof.
+///
+/// The fields are introduced in `ExecutorApi` version 2, the proof used to be encoded as an empty
+/// struct. It's only passed to the `submit_invalid_transaction_proof` call and never stored, so
+/// there is no storage to migrate.
 #[derive(Clone, Debug, Decode, Encode, PartialEq, TypeInfo)]
-pub struct InvalidTransactionProof;
+pub struct InvalidTransactionProof {
+    /// Hash of the bundle in which the invalid transaction was included.
+    pub bundle_hash: H256,
+    /// Index of the invalid transaction in the bundle.
+    pub extrinsic_index: u32,
+    /// Encoded invalid transaction.
+    pub extrinsic: Vec<u8>,
+}
 
 sp_api::decl_runtime_apis! {
     /// API necessary for executor pallet.
+    ///
+    /// Version 2 changes the encoding of the bundles, [`BundleHeader`] carries the domain, and
+    /// of the [`InvalidTransactionProof`], which carries the invalid extrinsic.
+    #[api_version(2)]
     pub trait ExecutorApi<SecondaryHash: Encode + Decode> {
         /// Submits the execution receipt via an unsigned extrinsic.
         fn submit_execution_receipt_unsigned(
@@ -376,6 +424,11 @@ sp_api::decl_runtime_apis! {
         /// Submits the transaction bundle via an unsigned extrinsic.
         fn submit_transaction_bundle_unsigned(opaque_bundle: SignedOpaqueBundle);
 
+        /// Returns the unsigned extrinsic submitting the transaction bundle.
+        ///
+        /// Introduced in version 2.
+        fn transaction_bundle_extrinsic(opaque_bundle: SignedOpaqueBundle) -> OpaqueExtrinsic;
+
         /// Submits the fraud proof via an unsigned extrinsic.
         fn submit_fraud_proof_unsigned(fraud_proof: FraudProof);
 
@@ -392,6 +445,11 @@ sp_api::decl_runtime_apis! {
         /// Extract the bundles from extrinsics in a block.
         fn extract_bundles(extrinsics: Vec<OpaqueExtrinsic>) -> Vec<OpaqueBundle>;
 
+        /// Extract the fraud proofs from extrinsics in a block.
+        ///
+        /// Introduced in version 2.
+        fn extract_fraud_proofs(extrinsics: Vec<OpaqueExtrinsic>) -> Vec<FraudProof>;
+
         /// Generates a randomness seed for extrinsics shuffling.
         fn extrinsics_shuffling_seed(header: Block::Header) -> Randomness;
 
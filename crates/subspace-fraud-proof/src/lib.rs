@@ -12,14 +12,16 @@ use hash_db::{HashDB, Hasher, Prefix};
 use sc_client_api::backend;
 use sc_client_api::execution_extensions::ExtensionsFactory;
 use sp_api::{ProvideRuntimeApi, StateBackend, StorageProof};
-use sp_core::traits::{CodeExecutor, FetchRuntimeCode, RuntimeCode, SpawnNamed};
-use sp_core::H256;
+use sp_core::traits::{CodeExecutor, FetchRuntimeCode, RuntimeCode, SpawnNamed, TaskExecutorExt};
+use sp_core::{NeverNativeValue, H256};
 use sp_executor::fraud_proof_ext::FraudProofExt;
 use sp_executor::{ExecutionPhase, ExecutorApi, FraudProof, VerificationError};
 use sp_externalities::Extensions;
 use sp_runtime::generic::BlockId;
 use sp_runtime::traits::{BlakeTwo256, Block as BlockT, HashFor};
-use sp_state_machine::{TrieBackend, TrieBackendStorage};
+use sp_state_machine::{
+    Ext, OverlayedChanges, ProvingBackend, StorageTransactionCache, TrieBackend, TrieBackendStorage,
+};
 use sp_trie::DBValue;
 use std::marker::PhantomData;
 use std::sync::Arc;
@@ -98,6 +100,78 @@ where
         }
     }
 
+    /// Executes `execution_phase` like [`Self::prove_execution`], but returns the error of the
+    /// code executor as is instead of the one type-erased by the state machine.
+    ///
+    /// Returns the result of the execution, e.g., the runtime traps, along with the storage
+    /// proof recorded. An error is only returned if the execution can't be started.
+    pub fn prove_execution_recording<DB: HashDB<HashFor<Block>, DBValue>>(
+        &self,
+        at: BlockId<Block>,
+        execution_phase: &ExecutionPhase,
+        delta_changes: Option<(DB, Block::Hash)>,
+    ) -> sp_blockchain::Result<(Result<(), Exec::Error>, StorageProof)> {
+        let state = self.backend.state_at(at)?;
+
+        let trie_backend = state.as_trie_backend().ok_or_else(|| {
+            Box::new(sp_state_machine::ExecutionError::UnableToGenerateProof)
+                as Box<dyn sp_state_machine::Error>
+        })?;
+
+        let state_runtime_code = sp_state_machine::backend::BackendRuntimeCode::new(trie_backend);
+        let runtime_code = state_runtime_code
+            .runtime_code()
+            .map_err(sp_blockchain::Error::RuntimeCode)?;
+
+        if let Some((delta, post_delta_root)) = delta_changes {
+            let delta_backend = create_delta_backend(trie_backend, delta, post_delta_root);
+            Ok(self.execute_recording(&delta_backend, execution_phase, &runtime_code))
+        } else {
+            Ok(self.execute_recording(trie_backend, execution_phase, &runtime_code))
+        }
+    }
+
+    /// Executes `execution_phase` on top of `trie_backend` with the code executor directly, the
+    /// state machine would type-erase the executor error.
+    fn execute_recording<S: TrieBackendStorage<HashFor<Block>>>(
+        &self,
+        trie_backend: &TrieBackend<S, HashFor<Block>>,
+        execution_phase: &ExecutionPhase,
+        runtime_code: &RuntimeCode,
+    ) -> (Result<(), Exec::Error>, StorageProof) {
+        let proving_backend = ProvingBackend::new(trie_backend);
+
+        let mut overlay = OverlayedChanges::default();
+        let mut storage_transaction_cache = StorageTransactionCache::default();
+        let mut extensions = Extensions::default();
+        extensions.register(TaskExecutorExt::new(self.spawn_handle.clone()));
+
+        overlay
+            .enter_runtime()
+            .expect("A fresh overlay is never in the runtime; qed");
+        let (result, _was_native) = {
+            let mut ext = Ext::new(
+                &mut overlay,
+                &mut storage_transaction_cache,
+                &proving_backend,
+                Some(&mut extensions),
+            );
+            self.executor.call::<NeverNativeValue, fn() -> _>(
+                &mut ext,
+                runtime_code,
+                execution_phase.proving_method(),
+                execution_phase.call_data(),
+                false,
+                None,
+            )
+        };
+        overlay
+            .exit_runtime()
+            .expect("The overlay entered the runtime above; qed");
+
+        (result.map(|_| ()), proving_backend.extract_proof())
+    }
+
     /// Runs the execution using the partial state constructed from the given storage proof and
     /// returns the execution result.
     ///
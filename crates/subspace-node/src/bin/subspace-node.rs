@@ -407,10 +407,21 @@ fn main() -> Result<(), Error> {
                         _,
                         _,
                         _,
+                        _,
                         cirrus_runtime::RuntimeApi,
                         SecondaryExecutorDispatch,
                     >(
-                        secondary_chain_config,
+                        cirrus_node::service::SecondaryChainConfiguration {
+                            base: secondary_chain_config,
+                            transaction_pool_revalidation: if secondary_chain_cli
+                                .light_pool_revalidation
+                            {
+                                cirrus_node::service::RevalidationType::Light
+                            } else {
+                                cirrus_node::service::RevalidationType::Full
+                            },
+                            executor_config: Default::default(),
+                        },
                         primary_chain_node.client.clone(),
                         primary_chain_node.network.clone(),
                         &primary_chain_node.select_chain,
@@ -420,15 +431,20 @@ fn main() -> Result<(), Error> {
                             .then(|imported_block_notification| async move {
                                 imported_block_notification.block_number
                             }),
-                        primary_chain_node
-                            .new_slot_notification_stream
-                            .subscribe()
-                            .then(|slot_notification| async move {
-                                (
-                                    slot_notification.new_slot_info.slot,
-                                    slot_notification.new_slot_info.global_challenge,
+                        {
+                            let new_slot_notification_stream =
+                                primary_chain_node.new_slot_notification_stream.clone();
+                            move || {
+                                new_slot_notification_stream.subscribe().then(
+                                    |slot_notification| async move {
+                                        (
+                                            slot_notification.new_slot_info.slot,
+                                            slot_notification.new_slot_info.global_challenge,
+                                        )
+                                    },
                                 )
-                            }),
+                            }
+                        },
                     );
 
                     let secondary_chain_node = secondary_chain_node_fut.await?;
@@ -41,10 +41,25 @@ pub enum Subcommand {
     Benchmark(frame_benchmarking_cli::BenchmarkCmd),
 }
 
+/// Command-line arguments of the secondary chain node.
+#[derive(Debug, Parser)]
+struct SecondaryChainArgs {
+    #[clap(flatten)]
+    run: RunCmd,
+
+    /// Only revalidate a limited number of transactions in the transaction pool on each
+    /// imported block instead of revalidating the whole pool.
+    #[clap(long)]
+    light_pool_revalidation: bool,
+}
+
 pub struct SecondaryChainCli {
     /// Run a node.
     pub run: RunCmd,
 
+    /// Whether to use the light revalidation strategy for the transaction pool.
+    pub light_pool_revalidation: bool,
+
     /// The base path that should be used by the secondary chain.
     pub base_path: Option<PathBuf>,
 
@@ -61,10 +76,15 @@ impl SecondaryChainCli {
         chain_spec: ExecutionChainSpec<ExecutionGenesisConfig>,
         secondary_chain_args: impl Iterator<Item = &'a String>,
     ) -> Self {
+        let SecondaryChainArgs {
+            run,
+            light_pool_revalidation,
+        } = SecondaryChainArgs::parse_from(secondary_chain_args);
         Self {
+            run,
+            light_pool_revalidation,
             base_path,
             chain_spec,
-            run: RunCmd::parse_from(secondary_chain_args),
         }
     }
 }
@@ -53,7 +53,7 @@ use sp_consensus_subspace::{
 };
 use sp_core::crypto::{ByteArray, KeyTypeId};
 use sp_core::OpaqueMetadata;
-use sp_executor::{FraudProof, OpaqueBundle};
+use sp_executor::{FraudProof, OpaqueBundle, SignedOpaqueBundle};
 use sp_runtime::traits::{AccountIdLookup, BlakeTwo256, NumberFor, Zero};
 use sp_runtime::transaction_validity::{TransactionSource, TransactionValidity};
 use sp_runtime::{
@@ -84,10 +84,10 @@ pub const VERSION: RuntimeVersion = RuntimeVersion {
     spec_name: create_runtime_str!("subspace"),
     impl_name: create_runtime_str!("subspace"),
     authoring_version: 0,
-    spec_version: 1,
+    spec_version: 2,
     impl_version: 0,
     apis: RUNTIME_API_VERSIONS,
-    transaction_version: 0,
+    transaction_version: 1,
     state_version: 0,
 };
 
@@ -542,6 +542,37 @@ fn extract_bundles(extrinsics: Vec<OpaqueExtrinsic>) -> Vec<OpaqueBundle> {
         .collect()
 }
 
+fn transaction_bundle_extrinsic(signed_opaque_bundle: SignedOpaqueBundle) -> OpaqueExtrinsic {
+    UncheckedExtrinsic::new_unsigned(
+        pallet_executor::Call::submit_transaction_bundle {
+            signed_opaque_bundle,
+        }
+        .into(),
+    )
+    .into()
+}
+
+fn extract_fraud_proofs(extrinsics: Vec<OpaqueExtrinsic>) -> Vec<FraudProof> {
+    extrinsics
+        .into_iter()
+        .filter_map(|opaque_extrinsic| {
+            match <UncheckedExtrinsic>::decode(&mut opaque_extrinsic.encode().as_slice()) {
+                Ok(uxt) => {
+                    if let Call::Executor(pallet_executor::Call::submit_fraud_proof {
+                        fraud_proof,
+                    }) = uxt.function
+                    {
+                        Some(fraud_proof)
+                    } else {
+                        None
+                    }
+                }
+                Err(_) => None,
+            }
+        })
+        .collect()
+}
+
 fn extrinsics_shuffling_seed<Block: BlockT>(header: Block::Header) -> Randomness {
     if header.number().is_zero() {
         Randomness::default()
@@ -757,6 +788,10 @@ impl_runtime_apis! {
             Executor::submit_transaction_bundle_unsigned(opaque_bundle)
         }
 
+        fn transaction_bundle_extrinsic(opaque_bundle: sp_executor::SignedOpaqueBundle) -> OpaqueExtrinsic {
+            transaction_bundle_extrinsic(opaque_bundle)
+        }
+
         fn submit_fraud_proof_unsigned(fraud_proof: FraudProof) {
             Executor::submit_fraud_proof_unsigned(fraud_proof)
         }
@@ -777,6 +812,10 @@ impl_runtime_apis! {
             extract_bundles(extrinsics)
         }
 
+        fn extract_fraud_proofs(extrinsics: Vec<OpaqueExtrinsic>) -> Vec<FraudProof> {
+            extract_fraud_proofs(extrinsics)
+        }
+
         fn extrinsics_shuffling_seed(header: <Block as BlockT>::Header) -> Randomness {
             extrinsics_shuffling_seed::<Block>(header)
         }
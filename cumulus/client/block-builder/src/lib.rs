@@ -27,6 +27,7 @@
 #![warn(missing_docs)]
 
 use codec::Encode;
+use std::time::{Duration, Instant};
 
 use sp_api::{
 	ApiExt, ApiRef, Core, ProvideRuntimeApi, StorageChanges, StorageProof, TransactionOutcome,
@@ -93,6 +94,10 @@ pub struct BuiltBlock<Block: BlockT, StateBackend: backend::StateBackend<HashFor
 	pub storage_changes: StorageChanges<StateBackend, Block>,
 	/// An optional proof that was recorded while building the block.
 	pub proof: Option<StorageProof>,
+	/// The wall-clock time of applying each extrinsic, in the order of the extrinsics.
+	///
+	/// Only set when the timing was enabled via [`BlockBuilder::record_extrinsic_timings`].
+	pub extrinsic_timings: Option<Vec<Duration>>,
 }
 
 impl<Block: BlockT, StateBackend: backend::StateBackend<HashFor<Block>>>
@@ -140,6 +145,8 @@ pub struct BlockBuilder<'a, Block: BlockT, A: ProvideRuntimeApi<Block>, B> {
 	backend: &'a B,
 	/// The estimated size of the block header.
 	estimated_header_size: usize,
+	/// Whether to time applying each extrinsic.
+	record_extrinsic_timings: bool,
 }
 
 impl<'a, Block, A, B> BlockBuilder<'a, Block, A, B>
@@ -184,14 +191,33 @@ where
 
 		api.initialize_block_with_context(&block_id, ExecutionContext::BlockConstruction, &header)?;
 
-		Ok(Self { parent_hash, extrinsics, api, block_id, backend, estimated_header_size })
+		Ok(Self {
+			parent_hash,
+			extrinsics,
+			api,
+			block_id,
+			backend,
+			estimated_header_size,
+			record_extrinsic_timings: false,
+		})
+	}
+
+	/// Records the wall-clock time of applying each extrinsic while building the block, which is
+	/// only meant for diagnostics.
+	pub fn record_extrinsic_timings(&mut self) {
+		self.record_extrinsic_timings = true;
 	}
 
-	/// Execute the block's list of extrinsics.
-	fn execute_extrinsics(&self) -> Result<(), Error> {
+	/// Execute the block's list of extrinsics, returns the time of applying each extrinsic if
+	/// the timing is enabled.
+	fn execute_extrinsics(&self) -> Result<Option<Vec<Duration>>, Error> {
 		let block_id = &self.block_id;
 
+		let mut extrinsic_timings =
+			self.record_extrinsic_timings.then(|| Vec::with_capacity(self.extrinsics.len()));
+
 		for xt in &self.extrinsics {
+			let started_at = Instant::now();
 			// TODO: rethink what to do if an error occurs when executing the transaction.
 			self.api.execute_in_transaction(|api| {
 				match api.apply_extrinsic_with_context(
@@ -206,9 +232,13 @@ where
 					Err(e) => TransactionOutcome::Rollback(Err(Error::from(e))),
 				}
 			})?;
+
+			if let Some(extrinsic_timings) = &mut extrinsic_timings {
+				extrinsic_timings.push(started_at.elapsed());
+			}
 		}
 
-		Ok(())
+		Ok(extrinsic_timings)
 	}
 
 	fn collect_storage_changes(
@@ -268,8 +298,19 @@ where
 	/// Returns the build `Block`, the changes to the storage and an optional `StorageProof`
 	/// supplied by `self.api`, combined as [`BuiltBlock`].
 	/// The storage proof will be `Some(_)` when proof recording was enabled.
-	pub fn build(mut self) -> Result<BuiltBlock<Block, backend::StateBackendFor<B, Block>>, Error> {
-		self.execute_extrinsics()?;
+	pub fn build(self) -> Result<BuiltBlock<Block, backend::StateBackendFor<B, Block>>, Error> {
+		self.build_and_inspect(|_api, _at| Ok(())).map(|(built_block, ())| built_block)
+	}
+
+	/// Consume the builder to build a valid `Block` like [`Self::build`], additionally calling
+	/// `inspect` with the runtime api on top of the state right after the block is finalized.
+	///
+	/// Returns the built block along with the output of `inspect`.
+	pub fn build_and_inspect<R>(
+		mut self,
+		inspect: impl FnOnce(&A::Api, &BlockId<Block>) -> Result<R, Error>,
+	) -> Result<(BuiltBlock<Block, backend::StateBackendFor<B, Block>>, R), Error> {
+		let extrinsic_timings = self.execute_extrinsics()?;
 
 		let header = self
 			.api
@@ -285,13 +326,20 @@ where
 
 		let proof = self.api.extract_proof();
 
+		// Inspect after the proof is extracted so that it doesn't record the inspected state.
+		let inspected = inspect(&*self.api, &self.block_id)?;
+
 		let storage_changes = self.collect_storage_changes()?;
 
-		Ok(BuiltBlock {
-			block: <Block as BlockT>::new(header, self.extrinsics),
-			storage_changes,
-			proof,
-		})
+		Ok((
+			BuiltBlock {
+				block: <Block as BlockT>::new(header, self.extrinsics),
+				storage_changes,
+				proof,
+				extrinsic_timings,
+			},
+			inspected,
+		))
 	}
 
 	/// Create the inherents for the block.
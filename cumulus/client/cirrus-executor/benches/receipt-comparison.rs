@@ -0,0 +1,49 @@
+use cirrus_test_service::{
+	run_primary_chain_validator_node,
+	Keyring::{Alice, Ferdie},
+};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use sc_service::Role;
+
+pub fn criterion_benchmark(c: &mut Criterion) {
+	let runtime = tokio::runtime::Runtime::new().expect("Failed to create the tokio runtime");
+	let tokio_handle = runtime.handle().clone();
+
+	let (_ferdie, alice) = runtime.block_on(async {
+		// Start Ferdie
+		let (ferdie, ferdie_network_starter) =
+			run_primary_chain_validator_node(tokio_handle.clone(), Ferdie, vec![]);
+		ferdie_network_starter.start_network();
+
+		// Run Alice (a secondary chain authority node)
+		let alice = cirrus_test_service::TestNodeBuilder::new(tokio_handle, Alice)
+			.connect_to_primary_chain_node(&ferdie)
+			.build(Role::Authority)
+			.await;
+
+		alice.wait_for_blocks(3).await;
+
+		(ferdie, alice)
+	});
+
+	let local_receipt = alice
+		.executor
+		.receipt_at(2)
+		.expect("Failed to load the local receipt")
+		.expect("Receipt of the processed primary block must exist");
+	let external_receipt = local_receipt.clone();
+	let external_receipt_hash = external_receipt.hash();
+
+	c.bench_function("identical-receipts-comparison", |b| {
+		b.iter(|| {
+			alice.executor.compare_with_local_receipt(
+				black_box(local_receipt.clone()),
+				black_box(&external_receipt),
+				black_box(external_receipt_hash),
+			)
+		})
+	});
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
@@ -3,20 +3,45 @@
 use codec::{Decode, Encode};
 use sc_client_api::backend::AuxStore;
 use sp_blockchain::{Error as ClientError, Result as ClientResult};
+use sp_core::H256;
 use sp_executor::ExecutionReceipt;
 use sp_runtime::traits::{Block as BlockT, NumberFor, One, SaturatedConversion};
 use subspace_core_primitives::BlockNumber;
 
+const SCHEMA_VERSION_KEY: &[u8] = b"executor_schema_version";
+/// Version of the executor aux-db layout supported by this node.
+///
+/// v2 indexes the execution receipts by their hashes.
+const CURRENT_SCHEMA_VERSION: u32 = 2;
+
 const EXECUTION_RECEIPT_KEY: &[u8] = b"execution_receipt";
 const EXECUTION_RECEIPT_START: &[u8] = b"execution_receipt_start";
 const EXECUTION_RECEIPT_BLOCK_NUMBER: &[u8] = b"execution_receipt_block_number";
+/// Reverse index of the execution receipts, from the receipt hash to the block hash.
+const EXECUTION_RECEIPT_HASH_KEY: &[u8] = b"execution_receipt_hash";
+/// Trace indexes of the disputes submitted over a block, pruned along with its receipt.
+const SUBMITTED_DISPUTES_KEY: &[u8] = b"submitted_disputes";
 /// Prune the execution receipts when they reach this number.
 const PRUNING_DEPTH: BlockNumber = 1000;
 
+/// Number of the receipts kept below the best execution chain number, including the
+/// `retention_extra` receipts retained beyond [`PRUNING_DEPTH`].
+fn retained_depth(retention_extra: BlockNumber) -> BlockNumber {
+	PRUNING_DEPTH.saturating_add(retention_extra)
+}
+
 fn execution_receipt_key(block_hash: impl Encode) -> Vec<u8> {
 	(EXECUTION_RECEIPT_KEY, block_hash).encode()
 }
 
+fn execution_receipt_hash_key(receipt_hash: H256) -> Vec<u8> {
+	(EXECUTION_RECEIPT_HASH_KEY, receipt_hash).encode()
+}
+
+fn submitted_disputes_key(block_hash: impl Encode) -> Vec<u8> {
+	(SUBMITTED_DISPUTES_KEY, block_hash).encode()
+}
+
 fn load_decode<Backend: AuxStore, T: Decode>(
 	backend: &Backend,
 	key: &[u8],
@@ -31,12 +56,78 @@ fn load_decode<Backend: AuxStore, T: Decode>(
 	}
 }
 
+/// Upgrades the aux-db layout written by an older node to the current version, `best_number` is
+/// the number of the best secondary block.
+///
+/// Fails if the aux-db was written by a newer node with a layout this node doesn't understand.
+pub(super) fn migrate<Backend: AuxStore, Block: BlockT, PBlock: BlockT>(
+	backend: &Backend,
+	best_number: NumberFor<Block>,
+) -> ClientResult<()> {
+	let version = load_decode::<_, u32>(backend, SCHEMA_VERSION_KEY)?;
+
+	match version {
+		// The layout before the schema version was introduced is identical to v1.
+		None | Some(1) => migrate_v1_to_v2::<_, Block, PBlock>(backend, best_number),
+		Some(CURRENT_SCHEMA_VERSION) => Ok(()),
+		Some(version) if version > CURRENT_SCHEMA_VERSION => Err(ClientError::Backend(format!(
+			"Executor aux-db schema version {version} is newer than the latest supported \
+				version {CURRENT_SCHEMA_VERSION}, please upgrade the node"
+		))),
+		Some(version) => Err(ClientError::Backend(format!(
+			"Unsupported executor aux-db schema version: {version}"
+		))),
+	}
+}
+
+/// Indexes the execution receipts kept by a v1 node by their hashes.
+///
+/// v1 has no iterable record of the receipts above the oldest one, the receipts are looked up by
+/// block number up to `best_number`.
+fn migrate_v1_to_v2<Backend: AuxStore, Block: BlockT, PBlock: BlockT>(
+	backend: &Backend,
+	best_number: NumberFor<Block>,
+) -> ClientResult<()> {
+	let mut receipt_hash_index = Vec::new();
+	if let Some(mut block_number) =
+		load_decode::<_, NumberFor<Block>>(backend, EXECUTION_RECEIPT_START)?
+	{
+		while block_number <= best_number {
+			let block_number_key = (EXECUTION_RECEIPT_BLOCK_NUMBER, block_number).encode();
+			let hashes_at_block_number =
+				load_decode::<_, Vec<Block::Hash>>(backend, block_number_key.as_slice())?
+					.unwrap_or_default();
+			for block_hash in hashes_at_block_number {
+				if let Some(receipt) =
+					load_execution_receipt::<_, Block::Hash, NumberFor<PBlock>, PBlock::Hash>(
+						backend, block_hash,
+					)? {
+					receipt_hash_index
+						.push((execution_receipt_hash_key(receipt.hash()), block_hash.encode()));
+				}
+			}
+			block_number += One::one();
+		}
+	}
+
+	let version = 2u32.encode();
+	let inserts = receipt_hash_index
+		.iter()
+		.map(|(key, value)| (key.as_slice(), value.as_slice()))
+		.chain(std::iter::once((SCHEMA_VERSION_KEY, version.as_slice())))
+		.collect::<Vec<_>>();
+	backend.insert_aux(&inserts, &[])
+}
+
 /// Write the execution receipt of a block to aux storage, optionally prune the receipts that are
 /// too old.
+///
+/// `retention_extra` more receipts are kept below the pruning depth.
 pub(super) fn write_execution_receipt<Backend: AuxStore, Block: BlockT, PBlock: BlockT>(
 	backend: &Backend,
 	(block_hash, block_number): (Block::Hash, NumberFor<Block>),
 	best_execution_chain_number: NumberFor<Block>,
+	retention_extra: BlockNumber,
 	execution_receipt: &ExecutionReceipt<NumberFor<PBlock>, PBlock::Hash, Block::Hash>,
 ) -> Result<(), sp_blockchain::Error> {
 	let block_number_key = (EXECUTION_RECEIPT_BLOCK_NUMBER, block_number).encode();
@@ -54,7 +145,7 @@ pub(super) fn write_execution_receipt<Backend: AuxStore, Block: BlockT, PBlock:
 
 	if let Some(delete_receipts_to) = best_execution_chain_number
 		.saturated_into::<BlockNumber>()
-		.checked_sub(PRUNING_DEPTH)
+		.checked_sub(retained_depth(retention_extra))
 	{
 		new_first_saved_receipt = Into::<NumberFor<Block>>::into(delete_receipts_to) + One::one();
 		for receipt_to_delete in first_saved_receipt.saturated_into()..=delete_receipts_to {
@@ -64,9 +155,16 @@ pub(super) fn write_execution_receipt<Backend: AuxStore, Block: BlockT, PBlock:
 			if let Some(hashes_to_delete) =
 				load_decode::<_, Vec<Block::Hash>>(backend, delete_block_number_key.as_slice())?
 			{
-				keys_to_delete.extend(
-					hashes_to_delete.into_iter().map(|h| (EXECUTION_RECEIPT_KEY, h).encode()),
-				);
+				for block_hash in hashes_to_delete {
+					if let Some(receipt) =
+						load_execution_receipt::<_, Block::Hash, NumberFor<PBlock>, PBlock::Hash>(
+							backend, block_hash,
+						)? {
+						keys_to_delete.push(execution_receipt_hash_key(receipt.hash()));
+					}
+					keys_to_delete.push(execution_receipt_key(block_hash));
+					keys_to_delete.push(submitted_disputes_key(block_hash));
+				}
 				keys_to_delete.push(delete_block_number_key);
 			}
 		}
@@ -75,6 +173,10 @@ pub(super) fn write_execution_receipt<Backend: AuxStore, Block: BlockT, PBlock:
 	backend.insert_aux(
 		&[
 			(execution_receipt_key(block_hash).as_slice(), execution_receipt.encode().as_slice()),
+			(
+				execution_receipt_hash_key(execution_receipt.hash()).as_slice(),
+				block_hash.encode().as_slice(),
+			),
 			(block_number_key.as_slice(), hashes_at_block_number.encode().as_slice()),
 			(EXECUTION_RECEIPT_START, new_first_saved_receipt.encode().as_slice()),
 		],
@@ -96,11 +198,107 @@ where
 	load_decode(backend, execution_receipt_key(block_hash).as_slice())
 }
 
+/// Load the hash of the block whose execution receipt hashes to `receipt_hash`, `None` if no such
+/// receipt has been stored or it has been pruned.
+pub(super) fn load_block_hash_by_receipt_hash<Backend: AuxStore, Hash: Decode>(
+	backend: &Backend,
+	receipt_hash: H256,
+) -> ClientResult<Option<Hash>> {
+	load_decode(backend, execution_receipt_hash_key(receipt_hash).as_slice())
+}
+
+/// Load the execution receipt of the block at `block_number` derived from the primary block
+/// `primary_hash`, among the receipts of all the forks at that height.
+pub(super) fn load_execution_receipt_by_primary_hash<Backend, Block, PBlock>(
+	backend: &Backend,
+	block_number: NumberFor<Block>,
+	primary_hash: PBlock::Hash,
+) -> ClientResult<Option<ExecutionReceipt<NumberFor<PBlock>, PBlock::Hash, Block::Hash>>>
+where
+	Backend: AuxStore,
+	Block: BlockT,
+	PBlock: BlockT,
+{
+	let block_number_key = (EXECUTION_RECEIPT_BLOCK_NUMBER, block_number).encode();
+	let hashes_at_block_number =
+		load_decode::<_, Vec<Block::Hash>>(backend, block_number_key.as_slice())?
+			.unwrap_or_default();
+
+	for block_hash in hashes_at_block_number {
+		if let Some(receipt) =
+			load_execution_receipt::<_, Block::Hash, NumberFor<PBlock>, PBlock::Hash>(
+				backend, block_hash,
+			)? {
+			if receipt.primary_hash == primary_hash {
+				return Ok(Some(receipt))
+			}
+		}
+	}
+
+	Ok(None)
+}
+
+/// Returns the number of the oldest block whose execution receipt is still kept.
+pub(super) fn oldest_receipt_number<Backend: AuxStore, Number: Decode>(
+	backend: &Backend,
+) -> ClientResult<Option<Number>> {
+	load_decode(backend, EXECUTION_RECEIPT_START)
+}
+
+/// Returns the numbers of the blocks in `[from, to]` lacking a stored execution receipt.
+pub(super) fn detect_receipt_gaps<Backend: AuxStore, Block: BlockT>(
+	backend: &Backend,
+	from: NumberFor<Block>,
+	to: NumberFor<Block>,
+) -> ClientResult<Vec<NumberFor<Block>>> {
+	let mut gaps = Vec::new();
+	let mut block_number = from;
+	while block_number <= to {
+		let block_number_key = (EXECUTION_RECEIPT_BLOCK_NUMBER, block_number).encode();
+		if load_decode::<_, Vec<Block::Hash>>(backend, block_number_key.as_slice())?.is_none() {
+			gaps.push(block_number);
+		}
+		block_number += One::one();
+	}
+	Ok(gaps)
+}
+
+/// Records the fraud proof of the dispute over the trace root `trace_idx` of block `block_hash`
+/// has been submitted, so that the dispute is not raised again after a restart.
+///
+/// The record is pruned along with the execution receipt of the block.
+pub(super) fn write_submitted_dispute<Backend: AuxStore>(
+	backend: &Backend,
+	block_hash: impl Encode,
+	trace_idx: u32,
+) -> ClientResult<()> {
+	let key = submitted_disputes_key(block_hash);
+	let mut trace_indexes =
+		load_decode::<_, Vec<u32>>(backend, key.as_slice())?.unwrap_or_default();
+	if trace_indexes.contains(&trace_idx) {
+		return Ok(())
+	}
+	trace_indexes.push(trace_idx);
+	backend.insert_aux(&[(key.as_slice(), trace_indexes.encode().as_slice())], &[])
+}
+
+/// Returns `true` if the fraud proof of the dispute over the trace root `trace_idx` of block
+/// `block_hash` has been submitted.
+pub(super) fn is_dispute_submitted<Backend: AuxStore>(
+	backend: &Backend,
+	block_hash: impl Encode,
+	trace_idx: u32,
+) -> ClientResult<bool> {
+	Ok(load_decode::<_, Vec<u32>>(backend, submitted_disputes_key(block_hash).as_slice())?
+		.map_or(false, |trace_indexes| trace_indexes.contains(&trace_idx)))
+}
+
 pub(super) fn target_receipt_is_pruned(
 	best_execution_chain_number: BlockNumber,
 	target_block: BlockNumber,
+	retention_extra: BlockNumber,
 ) -> bool {
-	best_execution_chain_number.saturating_sub(target_block) >= PRUNING_DEPTH
+	best_execution_chain_number.saturating_sub(target_block) >= retained_depth(retention_extra)
 }
 
 #[cfg(test)]
@@ -123,6 +321,185 @@ mod tests {
 		}
 	}
 
+	#[test]
+	fn schema_version_should_be_written_on_migration() {
+		let client = substrate_test_runtime_client::new();
+
+		assert_eq!(load_decode::<_, u32>(&client, SCHEMA_VERSION_KEY).unwrap(), None);
+		migrate::<_, Block, PBlock>(&client, 0).unwrap();
+		assert_eq!(
+			load_decode::<_, u32>(&client, SCHEMA_VERSION_KEY).unwrap(),
+			Some(CURRENT_SCHEMA_VERSION)
+		);
+
+		// Migrating an up-to-date aux-db is a no-op.
+		migrate::<_, Block, PBlock>(&client, 0).unwrap();
+	}
+
+	#[test]
+	fn receipts_written_by_v1_should_be_indexed_on_migration() {
+		let client = substrate_test_runtime_client::new();
+
+		// A v1 node writes the receipts without indexing them.
+		let receipts = (1..=3)
+			.map(|block_number| {
+				let block_hash = Hash::random();
+				let receipt = create_execution_receipt(block_number);
+				write_execution_receipt::<_, Block, PBlock>(
+					&client,
+					(block_hash, block_number),
+					block_number - 1,
+					0,
+					&receipt,
+				)
+				.unwrap();
+				client
+					.insert_aux(&[], &[execution_receipt_hash_key(receipt.hash()).as_slice()])
+					.unwrap();
+				(block_hash, receipt)
+			})
+			.collect::<Vec<_>>();
+		client.insert_aux(&[(SCHEMA_VERSION_KEY, 1u32.encode().as_slice())], &[]).unwrap();
+
+		let block_hash_of = |receipt: &ExecutionReceipt| {
+			load_block_hash_by_receipt_hash::<_, Hash>(&client, receipt.hash()).unwrap()
+		};
+		assert!(receipts.iter().all(|(_, receipt)| block_hash_of(receipt).is_none()));
+
+		migrate::<_, Block, PBlock>(&client, 3).unwrap();
+
+		assert_eq!(
+			load_decode::<_, u32>(&client, SCHEMA_VERSION_KEY).unwrap(),
+			Some(CURRENT_SCHEMA_VERSION)
+		);
+		for (block_hash, receipt) in &receipts {
+			assert_eq!(block_hash_of(receipt), Some(*block_hash));
+		}
+	}
+
+	#[test]
+	fn submitted_dispute_should_be_recorded() {
+		let client = substrate_test_runtime_client::new();
+		let block_hash = H256::random();
+
+		assert!(!is_dispute_submitted(&client, block_hash, 2).unwrap());
+		write_submitted_dispute(&client, block_hash, 2).unwrap();
+		assert!(is_dispute_submitted(&client, block_hash, 2).unwrap());
+
+		// Other disputes are not affected.
+		assert!(!is_dispute_submitted(&client, block_hash, 3).unwrap());
+		assert!(!is_dispute_submitted(&client, H256::random(), 2).unwrap());
+	}
+
+	#[test]
+	fn future_schema_version_should_be_rejected() {
+		let client = substrate_test_runtime_client::new();
+
+		client
+			.insert_aux(
+				&[(SCHEMA_VERSION_KEY, (CURRENT_SCHEMA_VERSION + 1).encode().as_slice())],
+				&[],
+			)
+			.unwrap();
+
+		match migrate::<_, Block, PBlock>(&client, 0).unwrap_err() {
+			ClientError::Backend(error) =>
+				assert!(error.contains("newer than the latest supported")),
+			error => panic!("Unexpected error: {error:?}"),
+		}
+	}
+
+	#[test]
+	fn execution_receipt_should_be_found_by_its_hash() {
+		let client = substrate_test_runtime_client::new();
+
+		let write_receipt_at = |block_hash: Hash, block_number, best_execution_chain_number| {
+			let receipt = create_execution_receipt(block_number);
+			write_execution_receipt::<_, Block, PBlock>(
+				&client,
+				(block_hash, block_number),
+				best_execution_chain_number,
+				0,
+				&receipt,
+			)
+			.unwrap();
+			receipt
+		};
+		let block_hash_of = |receipt: &ExecutionReceipt| {
+			load_block_hash_by_receipt_hash::<_, Hash>(&client, receipt.hash()).unwrap()
+		};
+
+		let block_hash = Hash::random();
+		let receipt = write_receipt_at(block_hash, 1, 0);
+		assert_eq!(block_hash_of(&receipt), Some(block_hash));
+		assert_eq!(
+			load_block_hash_by_receipt_hash::<_, Hash>(&client, H256::random()).unwrap(),
+			None
+		);
+
+		// The index entry is pruned along with the receipt.
+		write_receipt_at(Hash::random(), PRUNING_DEPTH + 2, PRUNING_DEPTH + 1);
+		assert_eq!(
+			load_execution_receipt::<_, _, BlockNumber, Hash>(&client, block_hash).unwrap(),
+			None
+		);
+		assert_eq!(block_hash_of(&receipt), None);
+	}
+
+	#[test]
+	fn missing_receipts_should_be_detected_as_gaps() {
+		let client = substrate_test_runtime_client::new();
+
+		for block_number in [1, 2, 4] {
+			write_execution_receipt::<_, Block, PBlock>(
+				&client,
+				(Hash::random(), block_number),
+				0,
+				0,
+				&create_execution_receipt(block_number),
+			)
+			.unwrap();
+		}
+
+		assert_eq!(oldest_receipt_number::<_, BlockNumber>(&client).unwrap(), Some(1));
+		assert_eq!(detect_receipt_gaps::<_, Block>(&client, 1, 4).unwrap(), vec![3]);
+		assert_eq!(detect_receipt_gaps::<_, Block>(&client, 1, 2).unwrap(), Vec::new());
+		assert_eq!(detect_receipt_gaps::<_, Block>(&client, 4, 5).unwrap(), vec![5]);
+	}
+
+	#[test]
+	fn execution_receipt_should_be_loaded_by_primary_hash() {
+		let client = substrate_test_runtime_client::new();
+
+		// Two forks at #3 derived from the different primary blocks.
+		let receipt = create_execution_receipt(3);
+		let fork_receipt = create_execution_receipt(3);
+		for receipt in [&receipt, &fork_receipt] {
+			write_execution_receipt::<_, Block, PBlock>(
+				&client,
+				(receipt.secondary_hash, 3),
+				0,
+				0,
+				receipt,
+			)
+			.unwrap();
+		}
+
+		let load = |block_number, primary_hash| {
+			load_execution_receipt_by_primary_hash::<_, Block, PBlock>(
+				&client,
+				block_number,
+				primary_hash,
+			)
+			.unwrap()
+		};
+		assert_eq!(load(3, receipt.primary_hash), Some(receipt.clone()));
+		assert_eq!(load(3, fork_receipt.primary_hash), Some(fork_receipt.clone()));
+		assert_eq!(load(3, H256::random()), None);
+		// No receipt is kept at #2, e.g., it has been pruned.
+		assert_eq!(load(2, receipt.primary_hash), None);
+	}
+
 	#[test]
 	fn normal_prune_execution_receipt_works() {
 		let client = substrate_test_runtime_client::new();
@@ -147,6 +524,7 @@ mod tests {
 				&client,
 				(hash, number),
 				number - 1, // Ideally, the receipt of previous block has been included when writing the receipt of current block.
+				0,
 				receipt,
 			)
 			.unwrap()
@@ -167,7 +545,12 @@ mod tests {
 			})
 			.collect::<Vec<_>>();
 
-		assert!(!target_receipt_is_pruned(PRUNING_DEPTH, 1));
+		assert!(!target_receipt_is_pruned(PRUNING_DEPTH, 1, 0));
+
+		// Disputes over the ER of block #1 have been submitted.
+		write_submitted_dispute(&client, block_hash_list[0], 2).unwrap();
+		write_submitted_dispute(&client, block_hash_list[0], 5).unwrap();
+		assert!(is_dispute_submitted(&client, block_hash_list[0], 5).unwrap());
 
 		// Create PRUNING_DEPTH + 1 receipt, best_execution_chain_number is PRUNING_DEPTH.
 		let block_hash = Hash::random();
@@ -192,7 +575,10 @@ mod tests {
 		assert!(receipt_at(block_hash_list[0]).is_none());
 		// block number mapping should be pruned as well.
 		assert!(hashes_at(1).is_none());
-		assert!(target_receipt_is_pruned(PRUNING_DEPTH + 1, 1));
+		// So are the disputes submitted over it.
+		assert!(client.get_aux(&submitted_disputes_key(block_hash_list[0])).unwrap().is_none());
+		assert!(!is_dispute_submitted(&client, block_hash_list[0], 2).unwrap());
+		assert!(target_receipt_is_pruned(PRUNING_DEPTH + 1, 1, 0));
 		assert_eq!(receipt_start(), Some(2));
 
 		// Create PRUNING_DEPTH + 3 receipt, best_execution_chain_number is PRUNING_DEPTH + 2.
@@ -205,8 +591,8 @@ mod tests {
 		assert!(receipt_at(block_hash).is_some());
 		// ER of block #2 should be pruned.
 		assert!(receipt_at(block_hash_list[1]).is_none());
-		assert!(target_receipt_is_pruned(PRUNING_DEPTH + 2, 2));
-		assert!(!target_receipt_is_pruned(PRUNING_DEPTH + 2, 3));
+		assert!(target_receipt_is_pruned(PRUNING_DEPTH + 2, 2, 0));
+		assert!(!target_receipt_is_pruned(PRUNING_DEPTH + 2, 3, 0));
 		assert_eq!(receipt_start(), Some(3));
 
 		// Multiple hashes attached to the block #(PRUNING_DEPTH + 3)
@@ -246,6 +632,7 @@ mod tests {
 				&client,
 				(hash, number),
 				best_execution_chain_number,
+				0,
 				receipt,
 			)
 			.unwrap()
@@ -267,7 +654,7 @@ mod tests {
 			})
 			.collect::<Vec<_>>();
 
-		assert!(!target_receipt_is_pruned(PRUNING_DEPTH, 1));
+		assert!(!target_receipt_is_pruned(PRUNING_DEPTH, 1, 0));
 
 		// Create PRUNING_DEPTH + 1 receipt, best_execution_chain_number is 0.
 		let block_hash = Hash::random();
@@ -290,7 +677,7 @@ mod tests {
 		assert!(receipt_at(block_hash_list[0]).is_some());
 		// block number mapping for #1 should not be pruned neither.
 		assert!(hashes_at(1).is_some());
-		assert!(!target_receipt_is_pruned(0, 1));
+		assert!(!target_receipt_is_pruned(0, 1, 0));
 		assert_eq!(receipt_start(), Some(1));
 
 		// Create PRUNING_DEPTH + 3 receipt, best_execution_chain_number is 0.
@@ -312,8 +699,59 @@ mod tests {
 		// receipt and block number mapping for [1, 2, 3] should be pruned.
 		(1..=3).for_each(|pruned| {
 			assert!(hashes_at(pruned).is_none());
-			assert!(target_receipt_is_pruned(PRUNING_DEPTH + 3, pruned));
+			assert!(target_receipt_is_pruned(PRUNING_DEPTH + 3, pruned, 0));
 		});
 		assert_eq!(receipt_start(), Some(4));
 	}
+
+	#[test]
+	fn execution_receipts_within_retention_extra_should_be_kept() {
+		let client = substrate_test_runtime_client::new();
+
+		const RETENTION_EXTRA: BlockNumber = 5;
+
+		let block_hash_list = (1..=PRUNING_DEPTH + RETENTION_EXTRA + 2)
+			.map(|block_number| {
+				let block_hash = Hash::random();
+				write_execution_receipt::<_, Block, PBlock>(
+					&client,
+					(block_hash, block_number),
+					block_number - 1,
+					RETENTION_EXTRA,
+					&create_execution_receipt(block_number),
+				)
+				.unwrap();
+				block_hash
+			})
+			.collect::<Vec<_>>();
+
+		// The best execution chain number is `PRUNING_DEPTH + RETENTION_EXTRA + 1` now, only the
+		// receipt of #1 is beyond the retention.
+		let best_execution_chain_number = PRUNING_DEPTH + RETENTION_EXTRA + 1;
+		assert!(load_execution_receipt::<_, _, BlockNumber, Hash>(&client, block_hash_list[0])
+			.unwrap()
+			.is_none());
+		assert!(target_receipt_is_pruned(best_execution_chain_number, 1, RETENTION_EXTRA));
+		assert_eq!(oldest_receipt_number::<_, BlockNumber>(&client).unwrap(), Some(2));
+
+		// The receipts within the extra buffer are still retained and accessible, although they
+		// are beyond the pruning depth.
+		for block_number in 2..=RETENTION_EXTRA + 1 {
+			assert!(target_receipt_is_pruned(best_execution_chain_number, block_number, 0));
+			assert!(!target_receipt_is_pruned(
+				best_execution_chain_number,
+				block_number,
+				RETENTION_EXTRA
+			));
+			assert_eq!(
+				load_execution_receipt::<_, _, BlockNumber, Hash>(
+					&client,
+					block_hash_list[block_number as usize - 1]
+				)
+				.unwrap()
+				.map(|receipt| receipt.primary_number),
+				Some(block_number)
+			);
+		}
+	}
 }
@@ -1,28 +1,27 @@
-use crate::{ExecutionReceiptFor, SignedExecutionReceiptFor};
+use crate::{
+	notification::ExecutorNotificationSender, ExecutionReceiptFor, ExecutorConfig,
+	SignedExecutionReceiptFor,
+};
 use cirrus_block_builder::{BlockBuilder, BuiltBlock, RecordProof};
 use cirrus_primitives::{AccountId, SecondaryApi};
 use codec::{Decode, Encode};
+use lru::LruCache;
+use parking_lot::Mutex;
 use rand::{seq::SliceRandom, SeedableRng};
 use rand_chacha::ChaCha8Rng;
-use sc_client_api::{AuxStore, BlockBackend};
+use sc_client_api::{backend::StateBackendFor, AuxStore, BlockBackend, BlockchainEvents};
 use sc_consensus::{
 	BlockImport, BlockImportParams, ForkChoiceStrategy, ImportResult, StateAction, StorageChanges,
 };
 use sc_network::NetworkService;
-use sc_utils::mpsc::TracingUnboundedSender;
 use sp_api::{NumberFor, ProvideRuntimeApi, TransactionFor};
 use sp_blockchain::HeaderBackend;
-use sp_consensus::BlockOrigin;
-use sp_core::ByteArray;
-use sp_executor::{
-	ExecutionReceipt, ExecutorApi, ExecutorId, ExecutorSignature, OpaqueBundle,
-	SignedExecutionReceipt,
-};
-use sp_keystore::{SyncCryptoStore, SyncCryptoStorePtr};
+use sp_consensus::{BlockOrigin, BlockStatus};
+use sp_executor::{DomainId, ExecutionReceipt, ExecutorApi, OpaqueBundle, SignedExecutionReceipt};
+use sp_keystore::SyncCryptoStorePtr;
 use sp_runtime::{
 	generic::BlockId,
-	traits::{Block as BlockT, Header as HeaderT, One},
-	RuntimeAppPublic,
+	traits::{AtLeast32BitUnsigned, Block as BlockT, CheckedSub, Header as HeaderT, One, Zero},
 };
 use std::{
 	borrow::Cow,
@@ -30,11 +29,16 @@ use std::{
 	fmt::Debug,
 	marker::PhantomData,
 	sync::Arc,
+	time::Duration,
 };
 use subspace_core_primitives::Randomness;
 
 const LOG_TARGET: &str = "bundle-processor";
 
+/// Wall-clock time of applying each extrinsic of the recently built blocks, keyed by the block
+/// hash.
+pub(crate) type ExtrinsicTimingsCache<Hash> = Arc<Mutex<LruCache<Hash, Vec<Duration>>>>;
+
 /// Shuffles the extrinsics in a deterministic way.
 ///
 /// The extrinsics are grouped by the signer. The extrinsics without a signer, i.e., unsigned
@@ -78,6 +82,255 @@ fn shuffle_extrinsics<Extrinsic: Debug>(
 	shuffled_extrinsics
 }
 
+/// Returns `true` if the block with `block_status` is good to be built on.
+fn is_block_status_buildable<Block: BlockT>(
+	block_status: sp_blockchain::Result<BlockStatus>,
+	hash: Block::Hash,
+	number: NumberFor<Block>,
+) -> bool {
+	match block_status {
+		Ok(BlockStatus::Queued) => {
+			tracing::debug!(
+				target: LOG_TARGET,
+				block_hash = ?hash,
+				"Skipping bundle processing, because block is still queued for import.",
+			);
+			false
+		},
+		Ok(BlockStatus::InChainWithState) => true,
+		Ok(BlockStatus::InChainPruned) => {
+			tracing::error!(
+				target: LOG_TARGET,
+				"Skipping bundle processing, because block `{:?}` is already pruned!",
+				hash,
+			);
+			false
+		},
+		Ok(BlockStatus::KnownBad) => {
+			tracing::error!(
+				target: LOG_TARGET,
+				block_hash = ?hash,
+				"Block is tagged as known bad! Skipping bundle processing!",
+			);
+			false
+		},
+		Ok(BlockStatus::Unknown) => {
+			if number.is_zero() {
+				tracing::error!(
+					target: LOG_TARGET,
+					block_hash = ?hash,
+					"Could not find the header of the genesis block in the database!",
+				);
+			} else {
+				tracing::debug!(
+					target: LOG_TARGET,
+					block_hash = ?hash,
+					"Skipping bundle processing, because block is unknown.",
+				);
+			}
+			false
+		},
+		Err(e) => {
+			tracing::error!(
+				target: LOG_TARGET,
+				block_hash = ?hash,
+				error = ?e,
+				"Failed to get block status.",
+			);
+			false
+		},
+	}
+}
+
+/// Returns the bundles produced for the domain `domain_id`, the bundles of other domains are
+/// processed by their own executors.
+pub(crate) fn bundles_of_domain(
+	bundles: Vec<OpaqueBundle>,
+	domain_id: DomainId,
+) -> Vec<OpaqueBundle> {
+	bundles
+		.into_iter()
+		.filter(|bundle| bundle.header.domain_id == domain_id)
+		.collect()
+}
+
+/// Returns the number of the latest block whose receipt has reached the confirmation depth
+/// `confirmation_depth` once the block `header_number` is imported, `None` if no block after
+/// `best_execution_chain_number` is confirmed yet.
+fn confirmed_receipt_number<Number: AtLeast32BitUnsigned + Copy>(
+	header_number: Number,
+	best_execution_chain_number: Number,
+	confirmation_depth: Number,
+) -> Option<Number> {
+	header_number
+		.checked_sub(&confirmation_depth)
+		.filter(|confirmed_number| *confirmed_number > best_execution_chain_number)
+}
+
+fn bundles_to_extrinsics<Block, Client>(
+	client: &Client,
+	parent_hash: Block::Hash,
+	bundles: Vec<OpaqueBundle>,
+	shuffling_seed: Randomness,
+) -> Result<Vec<Block::Extrinsic>, sp_blockchain::Error>
+where
+	Block: BlockT,
+	Client: ProvideRuntimeApi<Block>,
+	Client::Api: SecondaryApi<Block, AccountId>,
+{
+	let mut extrinsics = bundles
+		.into_iter()
+		.flat_map(|bundle| {
+			bundle.opaque_extrinsics.into_iter().filter_map(|opaque_extrinsic| {
+				match <<Block as BlockT>::Extrinsic>::decode(
+					&mut opaque_extrinsic.encode().as_slice(),
+				) {
+					Ok(uxt) => Some(uxt),
+					Err(e) => {
+						tracing::error!(
+							target: LOG_TARGET,
+							error = ?e,
+							"Failed to decode the opaque extrisic in bundle, this should not happen"
+						);
+						None
+					},
+				}
+			})
+		})
+		.collect::<Vec<_>>();
+
+	// TODO: or just Vec::new()?
+	// Ideally there should be only a few duplicated transactions.
+	let mut seen = Vec::with_capacity(extrinsics.len());
+	extrinsics.retain(|uxt| match seen.contains(uxt) {
+		true => {
+			tracing::trace!(target: LOG_TARGET, extrinsic = ?uxt, "Duplicated extrinsic");
+			false
+		},
+		false => {
+			seen.push(uxt.clone());
+			true
+		},
+	});
+	drop(seen);
+
+	tracing::trace!(target: LOG_TARGET, ?extrinsics, "Origin deduplicated extrinsics");
+
+	let extrinsics: Vec<_> =
+		match client.runtime_api().extract_signer(&BlockId::Hash(parent_hash), extrinsics) {
+			Ok(res) => res,
+			Err(e) => {
+				tracing::error!(
+					target: LOG_TARGET,
+					error = ?e,
+					"Error at calling runtime api: extract_signer"
+				);
+				return Err(e.into())
+			},
+		};
+
+	let extrinsics = shuffle_extrinsics::<<Block as BlockT>::Extrinsic>(extrinsics, shuffling_seed);
+
+	Ok(extrinsics)
+}
+
+/// Builds the secondary block on top of `parent_hash` from the extrinsics of `bundles`, returns the
+/// block with its storage changes, the execution trace and the trace root.
+///
+/// The time of applying each extrinsic is recorded in the built block if
+/// `record_extrinsic_timings` is set.
+fn prepare_secondary_block<Block, Client, Backend>(
+	client: &Client,
+	backend: &Backend,
+	(parent_hash, parent_number): (Block::Hash, NumberFor<Block>),
+	bundles: Vec<OpaqueBundle>,
+	shuffling_seed: Randomness,
+	maybe_new_runtime: Option<Cow<'static, [u8]>>,
+	record_extrinsic_timings: bool,
+) -> Result<
+	(BuiltBlock<Block, StateBackendFor<Backend, Block>>, Vec<Block::Hash>, [u8; 32]),
+	sp_blockchain::Error,
+>
+where
+	Block: BlockT,
+	Client: ProvideRuntimeApi<Block>,
+	Client::Api: SecondaryApi<Block, AccountId>
+		+ sp_block_builder::BlockBuilder<Block>
+		+ sp_api::ApiExt<Block, StateBackend = StateBackendFor<Backend, Block>>,
+	Backend: sc_client_api::Backend<Block>,
+{
+	let mut extrinsics =
+		bundles_to_extrinsics::<Block, _>(client, parent_hash, bundles, shuffling_seed)?;
+
+	if let Some(new_runtime) = maybe_new_runtime {
+		let encoded_set_code = client
+			.runtime_api()
+			.construct_set_code_extrinsic(&BlockId::Hash(parent_hash), new_runtime.to_vec())?;
+		let set_code_extrinsic =
+			Block::Extrinsic::decode(&mut encoded_set_code.as_slice()).unwrap();
+		extrinsics.push(set_code_extrinsic);
+	}
+
+	let mut block_builder = BlockBuilder::new(
+		client,
+		parent_hash,
+		parent_number,
+		RecordProof::No,
+		Default::default(),
+		backend,
+		extrinsics,
+	)?;
+	if record_extrinsic_timings {
+		block_builder.record_extrinsic_timings();
+	}
+
+	let (built_block, roots) = block_builder
+		.build_and_inspect(|api, at| api.intermediate_roots(at).map_err(Into::into))?;
+
+	let (trace, trace_root) =
+		crate::trace::execution_trace::<Block>(roots, *built_block.block.header().state_root())?;
+
+	Ok((built_block, trace, trace_root))
+}
+
+/// Builds the secondary block on top of `parent_hash` from `bundles` exactly like the bundle
+/// processing does, returns the block and its execution trace without importing the block.
+///
+/// `bundles` are expected to be of the same domain, `maybe_new_runtime` is the runtime code
+/// the block upgrades to, if any.
+pub fn build_secondary_block<Block, Client, Backend>(
+	client: &Client,
+	backend: &Backend,
+	parent_hash: Block::Hash,
+	bundles: Vec<OpaqueBundle>,
+	shuffling_seed: Randomness,
+	maybe_new_runtime: Option<Cow<'static, [u8]>>,
+) -> Result<(Block, Vec<Block::Hash>), sp_blockchain::Error>
+where
+	Block: BlockT,
+	Client: HeaderBackend<Block> + ProvideRuntimeApi<Block>,
+	Client::Api: SecondaryApi<Block, AccountId>
+		+ sp_block_builder::BlockBuilder<Block>
+		+ sp_api::ApiExt<Block, StateBackend = StateBackendFor<Backend, Block>>,
+	Backend: sc_client_api::Backend<Block>,
+{
+	let parent_number = client.number(parent_hash)?.ok_or_else(|| {
+		sp_blockchain::Error::UnknownBlock(format!("Header for {parent_hash:?} not found"))
+	})?;
+
+	let (BuiltBlock { block, .. }, trace, _trace_root) = prepare_secondary_block(
+		client,
+		backend,
+		(parent_hash, parent_number),
+		bundles,
+		shuffling_seed,
+		maybe_new_runtime,
+		false,
+	)?;
+
+	Ok((block, trace))
+}
+
 pub(crate) struct BundleProcessor<Block, PBlock, Client, PClient, Backend>
 where
 	Block: BlockT,
@@ -87,10 +340,12 @@ where
 	primary_network: Arc<NetworkService<PBlock, PBlock::Hash>>,
 	client: Arc<Client>,
 	execution_receipt_sender:
-		Arc<TracingUnboundedSender<SignedExecutionReceiptFor<PBlock, Block::Hash>>>,
+		ExecutorNotificationSender<SignedExecutionReceiptFor<PBlock, Block::Hash>>,
 	backend: Arc<Backend>,
 	is_authority: bool,
 	keystore: SyncCryptoStorePtr,
+	config: ExecutorConfig,
+	extrinsic_timings: Option<ExtrinsicTimingsCache<Block::Hash>>,
 	_phantom_data: PhantomData<PBlock>,
 }
 
@@ -109,6 +364,8 @@ where
 			backend: self.backend.clone(),
 			is_authority: self.is_authority,
 			keystore: self.keystore.clone(),
+			config: self.config,
+			extrinsic_timings: self.extrinsic_timings.clone(),
 			_phantom_data: self._phantom_data,
 		}
 	}
@@ -119,7 +376,11 @@ impl<Block, PBlock, Client, PClient, Backend>
 where
 	Block: BlockT,
 	PBlock: BlockT,
-	Client: HeaderBackend<Block> + BlockBackend<Block> + AuxStore + ProvideRuntimeApi<Block>,
+	Client: HeaderBackend<Block>
+		+ BlockBackend<Block>
+		+ AuxStore
+		+ ProvideRuntimeApi<Block>
+		+ BlockchainEvents<Block>,
 	Client::Api: SecondaryApi<Block, AccountId>
 		+ sp_block_builder::BlockBuilder<Block>
 		+ sp_api::ApiExt<
@@ -139,12 +400,14 @@ where
 		primary_chain_client: Arc<PClient>,
 		primary_network: Arc<NetworkService<PBlock, PBlock::Hash>>,
 		client: Arc<Client>,
-		execution_receipt_sender: Arc<
-			TracingUnboundedSender<SignedExecutionReceiptFor<PBlock, Block::Hash>>,
+		execution_receipt_sender: ExecutorNotificationSender<
+			SignedExecutionReceiptFor<PBlock, Block::Hash>,
 		>,
 		backend: Arc<Backend>,
 		is_authority: bool,
 		keystore: SyncCryptoStorePtr,
+		config: ExecutorConfig,
+		extrinsic_timings: Option<ExtrinsicTimingsCache<Block::Hash>>,
 	) -> Self {
 		Self {
 			primary_chain_client,
@@ -154,10 +417,14 @@ where
 			backend,
 			is_authority,
 			keystore,
+			config,
+			extrinsic_timings,
 			_phantom_data: PhantomData::default(),
 		}
 	}
 
+	/// Builds the secondary block driven by the primary block `primary_number` on top of the
+	/// secondary block at `primary_number - 1`, which must be good to be built on.
 	pub(crate) async fn process_bundles(
 		self,
 		(primary_hash, primary_number): (PBlock::Hash, NumberFor<PBlock>),
@@ -165,38 +432,98 @@ where
 		shuffling_seed: Randomness,
 		maybe_new_runtime: Option<Cow<'static, [u8]>>,
 	) -> Result<(), sp_blockchain::Error> {
-		let parent_hash = self.client.info().best_hash;
-		let parent_number = self.client.info().best_number;
-
-		let mut extrinsics = self.bundles_to_extrinsics(parent_hash, bundles, shuffling_seed)?;
+		let parent_number = crate::to_secondary_number::<NumberFor<Block>>(primary_number)
+			.ok()
+			.and_then(|number| number.checked_sub(&One::one()))
+			.ok_or_else(|| {
+				sp_blockchain::Error::Backend(format!(
+					"Primary block #{primary_number:?} drives no secondary block"
+				))
+			})?;
+
+		if let Some(timeout) = self.config.queued_block_wait {
+			crate::wait_for_queued_block(
+				|| self.parent_block_status(parent_number),
+				self.client.import_notification_stream(),
+				timeout,
+			)
+			.await;
+		}
 
-		if let Some(new_runtime) = maybe_new_runtime {
-			let encoded_set_code = self
-				.client
-				.runtime_api()
-				.construct_set_code_extrinsic(&BlockId::Hash(parent_hash), new_runtime.to_vec())?;
-			let set_code_extrinsic =
-				Block::Extrinsic::decode(&mut encoded_set_code.as_slice()).unwrap();
-			extrinsics.push(set_code_extrinsic);
+		let parent_hash = self.client.hash(parent_number)?.ok_or_else(|| {
+			sp_blockchain::Error::Backend(format!(
+				"Secondary block #{parent_number:?} to build the block of primary block \
+					#{primary_number:?} on is not imported"
+			))
+		})?;
+
+		if !self.check_block_status(parent_hash, parent_number) {
+			return Err(sp_blockchain::Error::Backend(format!(
+				"Secondary block #{parent_number:?} ({parent_hash:?}) can't be built on, the block \
+					of primary block #{primary_number:?} is not produced"
+			)))
 		}
 
-		let block_builder = BlockBuilder::new(
-			&*self.client,
-			parent_hash,
-			parent_number,
-			RecordProof::No,
-			Default::default(),
-			&*self.backend,
-			extrinsics,
-		)?;
+		self.build_and_import_block(
+			(primary_hash, primary_number),
+			(parent_hash, parent_number),
+			bundles,
+			shuffling_seed,
+			maybe_new_runtime,
+		)
+		.await
+	}
 
-		let BuiltBlock { block, storage_changes, proof: _ } = block_builder.build()?;
+	/// Builds the secondary block of the primary block `primary_info` on top of the best block,
+	/// regardless of the primary block number.
+	///
+	/// Only for the tests injecting the bundles of a primary block that has been processed.
+	pub(crate) async fn process_bundles_on_best(
+		self,
+		primary_info: (PBlock::Hash, NumberFor<PBlock>),
+		bundles: Vec<OpaqueBundle>,
+		shuffling_seed: Randomness,
+		maybe_new_runtime: Option<Cow<'static, [u8]>>,
+	) -> Result<(), sp_blockchain::Error> {
+		let best_info = (self.client.info().best_hash, self.client.info().best_number);
+		self.build_and_import_block(
+			primary_info,
+			best_info,
+			bundles,
+			shuffling_seed,
+			maybe_new_runtime,
+		)
+		.await
+	}
+
+	async fn build_and_import_block(
+		self,
+		(primary_hash, primary_number): (PBlock::Hash, NumberFor<PBlock>),
+		(parent_hash, parent_number): (Block::Hash, NumberFor<Block>),
+		bundles: Vec<OpaqueBundle>,
+		shuffling_seed: Randomness,
+		maybe_new_runtime: Option<Cow<'static, [u8]>>,
+	) -> Result<(), sp_blockchain::Error> {
+		let (BuiltBlock { block, storage_changes, proof: _, extrinsic_timings }, trace, trace_root) =
+			prepare_secondary_block(
+				&*self.client,
+				&*self.backend,
+				(parent_hash, parent_number),
+				bundles_of_domain(bundles, self.config.domain_id),
+				shuffling_seed,
+				maybe_new_runtime,
+				self.extrinsic_timings.is_some(),
+			)?;
 
 		let (header, body) = block.deconstruct();
-		let state_root = *header.state_root();
 		let header_hash = header.hash();
 		let header_number = *header.number();
 
+		if let (Some(cache), Some(extrinsic_timings)) = (&self.extrinsic_timings, extrinsic_timings)
+		{
+			cache.lock().put(header_hash, extrinsic_timings);
+		}
+
 		let block_import_params = {
 			let mut import_block = BlockImportParams::new(BlockOrigin::Own, header);
 			import_block.body = Some(body);
@@ -231,25 +558,6 @@ where
 			},
 		}
 
-		let mut roots =
-			self.client.runtime_api().intermediate_roots(&BlockId::Hash(header_hash))?;
-
-		let state_root = state_root
-			.encode()
-			.try_into()
-			.expect("State root uses the same Block hash type which must fit into [u8; 32]; qed");
-
-		roots.push(state_root);
-
-		let trace_root = crate::merkle_tree::construct_trace_merkle_tree(roots.clone())?.root();
-		let trace = roots
-			.into_iter()
-			.map(|r| {
-				Block::Hash::decode(&mut r.as_slice())
-					.expect("Storage root uses the same Block hash type; qed")
-			})
-			.collect();
-
 		tracing::debug!(
 			target: LOG_TARGET,
 			?trace,
@@ -284,6 +592,7 @@ where
 			&*self.client,
 			(header_hash, header_number),
 			best_execution_chain_number,
+			self.config.receipt_retention_extra,
 			&execution_receipt,
 		)?;
 
@@ -297,9 +606,29 @@ where
 			return Ok(())
 		}
 
+		let receipt_confirmation_depth: NumberFor<Block> =
+			self.config.receipt_confirmation_depth.into();
+		let confirmed_number = match confirmed_receipt_number(
+			header_number,
+			best_execution_chain_number,
+			receipt_confirmation_depth,
+		) {
+			Some(confirmed_number) => confirmed_number,
+			None => {
+				tracing::debug!(
+					target: LOG_TARGET,
+					"Skip sending the execution receipt as no new block has reached the confirmation depth {:?}",
+					receipt_confirmation_depth
+				);
+				return Ok(())
+			},
+		};
+
 		// Ideally, the receipt of current block will be included in the next block, i.e., no
 		// missing receipts.
-		if header_number == best_execution_chain_number + One::one() {
+		if confirmed_number == header_number &&
+			header_number == best_execution_chain_number + One::one()
+		{
 			self.try_sign_and_send_receipt(primary_hash, execution_receipt)
 		} else {
 			// Receipts for some previous blocks are missing.
@@ -311,7 +640,7 @@ where
 			let max_drift = <NumberFor<Block>>::decode(&mut max_drift.encode().as_slice())
 				.expect("Primary number and secondary number must use the same type; qed");
 
-			let max_allowed = (best_execution_chain_number + max_drift).min(header_number);
+			let max_allowed = (best_execution_chain_number + max_drift).min(confirmed_number);
 
 			// TODO: parallelize and avoid spamming the missing receipts?
 			let mut to_send = best_execution_chain_number + One::one();
@@ -347,70 +676,26 @@ where
 		}
 	}
 
-	fn bundles_to_extrinsics(
-		&self,
-		parent_hash: Block::Hash,
-		bundles: Vec<OpaqueBundle>,
-		shuffling_seed: Randomness,
-	) -> Result<Vec<Block::Extrinsic>, sp_blockchain::Error> {
-		let mut extrinsics = bundles
-			.into_iter()
-			.flat_map(|bundle| {
-				bundle.opaque_extrinsics.into_iter().filter_map(|opaque_extrinsic| {
-					match <<Block as BlockT>::Extrinsic>::decode(
-						&mut opaque_extrinsic.encode().as_slice(),
-					) {
-						Ok(uxt) => Some(uxt),
-						Err(e) => {
-							tracing::error!(
-								target: LOG_TARGET,
-								error = ?e,
-								"Failed to decode the opaque extrisic in bundle, this should not happen"
-							);
-							None
-						},
-					}
-				})
-			})
-			.collect::<Vec<_>>();
-
-		// TODO: or just Vec::new()?
-		// Ideally there should be only a few duplicated transactions.
-		let mut seen = Vec::with_capacity(extrinsics.len());
-		extrinsics.retain(|uxt| match seen.contains(uxt) {
-			true => {
-				tracing::trace!(target: LOG_TARGET, extrinsic = ?uxt, "Duplicated extrinsic");
-				false
-			},
-			false => {
-				seen.push(uxt.clone());
-				true
-			},
-		});
-		drop(seen);
-
-		tracing::trace!(target: LOG_TARGET, ?extrinsics, "Origin deduplicated extrinsics");
-
-		let extrinsics: Vec<_> = match self
-			.client
-			.runtime_api()
-			.extract_signer(&BlockId::Hash(parent_hash), extrinsics)
-		{
-			Ok(res) => res,
-			Err(e) => {
-				tracing::error!(
-					target: LOG_TARGET,
-					error = ?e,
-					"Error at calling runtime api: extract_signer"
-				);
-				return Err(e.into())
-			},
-		};
-
-		let extrinsics =
-			shuffle_extrinsics::<<Block as BlockT>::Extrinsic>(extrinsics, shuffling_seed);
+	/// Returns the status of the secondary block at `number`.
+	///
+	/// A block not imported yet is reported as [`BlockStatus::Queued`], the block of the previous
+	/// primary block may still be under import when the next primary block arrives.
+	fn parent_block_status(&self, number: NumberFor<Block>) -> sp_blockchain::Result<BlockStatus> {
+		match self.client.hash(number)? {
+			Some(hash) => self.client.block_status(&BlockId::Hash(hash)),
+			None => Ok(BlockStatus::Queued),
+		}
+	}
 
-		Ok(extrinsics)
+	/// Checks the status of the given block hash in the secondary chain.
+	///
+	/// Returns `true` if the block could be found and is good to be build on.
+	fn check_block_status(&self, hash: Block::Hash, number: NumberFor<Block>) -> bool {
+		is_block_status_buildable::<Block>(
+			self.client.block_status(&BlockId::Hash(hash)),
+			hash,
+			number,
+		)
 	}
 
 	fn try_sign_and_send_receipt(
@@ -423,58 +708,29 @@ where
 			.runtime_api()
 			.executor_id(&BlockId::Hash(primary_hash))?;
 
-		if self.is_authority &&
-			SyncCryptoStore::has_keys(
-				&*self.keystore,
-				&[(ByteArray::to_raw_vec(&executor_id), ExecutorId::ID)],
-			) {
-			let to_sign = execution_receipt.hash();
-			match SyncCryptoStore::sign_with(
-				&*self.keystore,
-				ExecutorId::ID,
-				&executor_id.clone().into(),
-				to_sign.as_ref(),
-			) {
-				Ok(Some(signature)) => {
-					let signed_execution_receipt = SignedExecutionReceipt {
-						execution_receipt,
-						signature: ExecutorSignature::decode(&mut signature.as_slice()).map_err(
-							|err| {
-								sp_blockchain::Error::Application(Box::from(format!(
-									"Failed to decode the signature of execution receipt: {err}"
-								)))
-							},
-						)?,
-						signer: executor_id,
-					};
-
-					if let Err(e) = self
-						.execution_receipt_sender
-						.unbounded_send(signed_execution_receipt.clone())
-					{
-						tracing::error!(target: LOG_TARGET, error = ?e, "Failed to send signed execution receipt");
-					}
-
-					let best_hash = self.primary_chain_client.info().best_hash;
-
-					// Broadcast ER to all farmers via unsigned extrinsic.
-					self.primary_chain_client.runtime_api().submit_execution_receipt_unsigned(
-						&BlockId::Hash(best_hash),
-						signed_execution_receipt,
-					)?;
-
-					Ok(())
-				},
-				Ok(None) => Err(sp_blockchain::Error::Application(Box::from(
-					"This should not happen as the existence of key was just checked",
-				))),
-				Err(error) => Err(sp_blockchain::Error::Application(Box::from(format!(
-					"Error occurred when signing the execution receipt: {error}"
-				)))),
-			}
-		} else {
-			Ok(())
+		if !self.is_authority {
+			return Ok(())
+		}
+
+		let to_sign = execution_receipt.hash();
+		if let Some(signature) =
+			crate::sign_as_executor(&self.keystore, &executor_id, to_sign.as_ref())?
+		{
+			let signed_execution_receipt =
+				SignedExecutionReceipt { execution_receipt, signature, signer: executor_id };
+
+			self.execution_receipt_sender.notify(|| signed_execution_receipt.clone());
+
+			let best_hash = self.primary_chain_client.info().best_hash;
+
+			// Broadcast ER to all farmers via unsigned extrinsic.
+			self.primary_chain_client.runtime_api().submit_execution_receipt_unsigned(
+				&BlockId::Hash(best_hash),
+				signed_execution_receipt,
+			)?;
 		}
+
+		Ok(())
 	}
 }
 
@@ -521,4 +777,68 @@ mod tests {
 		let roots = vec![root1, root2, root3];
 		crate::merkle_tree::construct_trace_merkle_tree(roots).unwrap();
 	}
+
+	#[test]
+	fn block_status_check_should_prevent_building_on_bad_blocks() {
+		use substrate_test_runtime_client::runtime::{Block, Hash};
+
+		let hash = Hash::random();
+		let check = |status| is_block_status_buildable::<Block>(Ok(status), hash, 1);
+
+		assert!(check(BlockStatus::InChainWithState));
+		assert!(!check(BlockStatus::KnownBad));
+		assert!(!check(BlockStatus::InChainPruned));
+		assert!(!check(BlockStatus::Queued));
+		assert!(!check(BlockStatus::Unknown));
+		assert!(!is_block_status_buildable::<Block>(
+			Err(sp_blockchain::Error::UnknownBlock("Test".into())),
+			hash,
+			1
+		));
+	}
+
+	#[test]
+	fn receipt_should_be_sent_once_confirmed() {
+		// No confirmation depth, the receipt of the imported block is sent right away.
+		assert_eq!(confirmed_receipt_number(5u32, 4, 0), Some(5));
+		assert_eq!(confirmed_receipt_number(5u32, 5, 0), None);
+
+		// Block #5 is not yet confirmed when it's imported.
+		assert_eq!(confirmed_receipt_number(5u32, 4, 2), None);
+		assert_eq!(confirmed_receipt_number(6u32, 4, 2), None);
+		// Block #5 is confirmed once block #7 is imported.
+		assert_eq!(confirmed_receipt_number(7u32, 4, 2), Some(5));
+
+		// The depth is deeper than the chain.
+		assert_eq!(confirmed_receipt_number(1u32, 0, 2), None);
+	}
+
+	#[test]
+	fn bundles_should_be_dispatched_to_their_domain() {
+		let domain_a = DomainId::new(1);
+		let domain_b = DomainId::new(2);
+
+		let bundle_of = |domain_id: DomainId, slot_number: u64| OpaqueBundle {
+			header: sp_executor::BundleHeader {
+				domain_id,
+				primary_hash: Default::default(),
+				slot_number,
+				extrinsics_root: Default::default(),
+			},
+			opaque_extrinsics: Vec::new(),
+		};
+
+		let bundles = vec![bundle_of(domain_a, 1), bundle_of(domain_b, 2), bundle_of(domain_a, 3)];
+
+		let slots_of = |domain_id| {
+			bundles_of_domain(bundles.clone(), domain_id)
+				.into_iter()
+				.map(|bundle| bundle.header.slot_number)
+				.collect::<Vec<_>>()
+		};
+
+		assert_eq!(slots_of(domain_a), vec![1, 3]);
+		assert_eq!(slots_of(domain_b), vec![2]);
+		assert!(slots_of(DomainId::SYSTEM).is_empty());
+	}
 }
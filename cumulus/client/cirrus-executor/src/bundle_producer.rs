@@ -1,29 +1,115 @@
 use crate::worker::ExecutorSlotInfo;
-use cirrus_primitives::{AccountId, SecondaryApi};
+use cirrus_primitives::{AccountId, Balance, SecondaryApi};
 use codec::{Decode, Encode};
-use futures::{select, FutureExt};
+use futures::{select, Future, FutureExt};
+use pallet_transaction_payment_rpc_runtime_api::TransactionPaymentApi;
+use parking_lot::Mutex;
 use sc_client_api::BlockBackend;
 use sc_transaction_pool_api::InPoolTransaction;
 use sc_utils::mpsc::TracingUnboundedSender;
-use sp_api::ProvideRuntimeApi;
+use sp_api::{ProvideRuntimeApi, RuntimeApiInfo};
 use sp_block_builder::BlockBuilder;
 use sp_blockchain::HeaderBackend;
-use sp_core::ByteArray;
-use sp_executor::{
-	Bundle, BundleHeader, ExecutorApi, ExecutorId, ExecutorSignature, SignedBundle,
-	SignedOpaqueBundle,
-};
-use sp_keystore::{SyncCryptoStore, SyncCryptoStorePtr};
+use sp_consensus_slots::Slot;
+use sp_executor::{Bundle, BundleHeader, DomainId, ExecutorApi, SignedBundle, SignedOpaqueBundle};
+use sp_keystore::SyncCryptoStorePtr;
 use sp_runtime::{
 	generic::BlockId,
-	traits::{BlakeTwo256, Block as BlockT, Hash as HashT, Header as HeaderT},
-	RuntimeAppPublic,
+	traits::{BlakeTwo256, Block as BlockT, Hash as HashT, Header as HeaderT, NumberFor},
+	FixedPointNumber, FixedU128,
+};
+use std::{
+	collections::BTreeMap,
+	marker::PhantomData,
+	sync::{
+		atomic::{AtomicBool, Ordering},
+		Arc,
+	},
+	time,
 };
-use std::{marker::PhantomData, sync::Arc, time};
 use subspace_runtime_primitives::Hash as PHash;
 
 const LOG_TARGET: &str = "bundle-producer";
 
+/// Groups the extrinsics by their signer, keeping the order of the extrinsics within each group,
+/// the unsigned extrinsics are grouped under `None`.
+fn group_by_sender<AccountId: Ord, Extrinsic>(
+	extrinsics: Vec<(Option<AccountId>, Extrinsic)>,
+) -> BTreeMap<Option<AccountId>, Vec<Extrinsic>> {
+	extrinsics
+		.into_iter()
+		.fold(BTreeMap::new(), |mut groups, (maybe_signer, extrinsic)| {
+			groups.entry(maybe_signer).or_insert_with(Vec::new).push(extrinsic);
+			groups
+		})
+}
+
+/// Returns `true` if a bundle is to be produced at `slot`.
+///
+/// Only the authority nodes with the production not halted produce the bundles, on every
+/// `slot_interval`th slot.
+fn should_produce_bundle(
+	is_authority: bool,
+	producing: bool,
+	slot: Slot,
+	slot_interval: u64,
+) -> bool {
+	is_authority && producing && u64::from(slot) % slot_interval.max(1) == 0
+}
+
+/// Returns `true` if what a transaction offers, i.e., its fee `tx_fee` plus `tip`, covers both the
+/// minimum bundle fee and its fee scaled by `fee_multiplier`, the premium the authority requires
+/// for including the transaction in its bundle.
+///
+/// The fee is what the runtime charges for the length and weight of the transaction, only the tip
+/// can pay the premium.
+fn covers_bundle_fee(
+	tx_fee: Balance,
+	tip: Balance,
+	minimum_bundle_tx_fee: Balance,
+	fee_multiplier: FixedU128,
+) -> bool {
+	let offered_fee = tx_fee.saturating_add(tip);
+	offered_fee >= minimum_bundle_tx_fee &&
+		offered_fee >= fee_multiplier.saturating_mul_int(tx_fee)
+}
+
+/// Returns the minimum bundle transaction fee provided by the runtime at `at`, `None` if the
+/// runtime predates the `SecondaryApi` v2 introducing it.
+pub(crate) fn minimum_bundle_tx_fee_at<Block, Api>(
+	runtime_api: &Api,
+	at: &BlockId<Block>,
+) -> Result<Option<Balance>, sp_api::ApiError>
+where
+	Block: BlockT,
+	Api: SecondaryApi<Block, AccountId>,
+{
+	let secondary_api_version = sp_api::Core::<Block>::version(runtime_api, at)?
+		.api_version(&<dyn SecondaryApi<Block, AccountId>>::ID);
+	if secondary_api_version.map_or(false, |version| version >= 2) {
+		runtime_api.minimum_bundle_tx_fee(at).map(Some)
+	} else {
+		Ok(None)
+	}
+}
+
+/// Waits for the transaction pool to be ready via `ready_at`, falling back to the snapshot
+/// returned by `ready` if it doesn't resolve within `timeout`, so that a slow pool maintenance
+/// won't stall the slot-driven bundle production.
+async fn ready_at_with_timeout<R>(
+	ready_at: impl Future<Output = R>,
+	timeout: time::Duration,
+	ready: impl FnOnce() -> R,
+) -> R {
+	let mut ready_at = Box::pin(ready_at.fuse());
+	let mut delay = futures_timer::Delay::new(timeout).fuse();
+
+	select! {
+		res = ready_at => res,
+		_ = delay => ready(),
+	}
+}
+
 pub(super) struct BundleProducer<Block, PBlock, Client, PClient, TransactionPool>
 where
 	Block: BlockT,
@@ -34,6 +120,12 @@ where
 	bundle_sender: Arc<TracingUnboundedSender<SignedBundle<Block::Extrinsic>>>,
 	is_authority: bool,
 	keystore: SyncCryptoStorePtr,
+	slot_interval: u64,
+	latest_slot: Arc<Mutex<Option<Slot>>>,
+	slot_skew_tolerance: u64,
+	fee_multiplier: FixedU128,
+	domain_id: DomainId,
+	producing: Arc<AtomicBool>,
 	_phantom_data: PhantomData<PBlock>,
 }
 
@@ -50,18 +142,42 @@ where
 			bundle_sender: self.bundle_sender.clone(),
 			is_authority: self.is_authority,
 			keystore: self.keystore.clone(),
+			slot_interval: self.slot_interval,
+			latest_slot: self.latest_slot.clone(),
+			slot_skew_tolerance: self.slot_skew_tolerance,
+			fee_multiplier: self.fee_multiplier,
+			domain_id: self.domain_id,
+			producing: self.producing.clone(),
 			_phantom_data: self._phantom_data,
 		}
 	}
 }
 
+impl<Block, PBlock, Client, PClient, TransactionPool>
+	BundleProducer<Block, PBlock, Client, PClient, TransactionPool>
+where
+	Block: BlockT,
+{
+	/// Returns `true` if the node is a secondary chain authority.
+	pub(super) fn is_authority(&self) -> bool {
+		self.is_authority
+	}
+
+	/// Returns `true` if the bundles are produced on each new slot.
+	pub(super) fn is_producing(&self) -> bool {
+		self.producing.load(Ordering::SeqCst)
+	}
+}
+
 impl<Block, PBlock, Client, PClient, TransactionPool>
 	BundleProducer<Block, PBlock, Client, PClient, TransactionPool>
 where
 	Block: BlockT,
 	PBlock: BlockT,
 	Client: HeaderBackend<Block> + BlockBackend<Block> + ProvideRuntimeApi<Block>,
-	Client::Api: SecondaryApi<Block, AccountId> + BlockBuilder<Block>,
+	Client::Api: SecondaryApi<Block, AccountId>
+		+ BlockBuilder<Block>
+		+ TransactionPaymentApi<Block, Balance>,
 	PClient: ProvideRuntimeApi<PBlock>,
 	PClient::Api: ExecutorApi<PBlock, Block::Hash>,
 	TransactionPool: sc_transaction_pool_api::TransactionPool<Block = Block>,
@@ -73,6 +189,11 @@ where
 		bundle_sender: Arc<TracingUnboundedSender<SignedBundle<Block::Extrinsic>>>,
 		is_authority: bool,
 		keystore: SyncCryptoStorePtr,
+		slot_interval: u64,
+		latest_slot: Arc<Mutex<Option<Slot>>>,
+		slot_skew_tolerance: u64,
+		fee_multiplier: FixedU128,
+		domain_id: DomainId,
 	) -> Self {
 		Self {
 			primary_chain_client,
@@ -81,32 +202,74 @@ where
 			bundle_sender,
 			is_authority,
 			keystore,
+			slot_interval,
+			latest_slot,
+			slot_skew_tolerance,
+			fee_multiplier,
+			domain_id,
+			producing: Arc::new(AtomicBool::new(true)),
 			_phantom_data: PhantomData::default(),
 		}
 	}
 
-	pub(super) async fn produce_bundle(
-		self,
-		primary_hash: PHash,
-		slot_info: ExecutorSlotInfo,
-	) -> Result<Option<SignedOpaqueBundle>, sp_blockchain::Error> {
-		let parent_number = self.client.info().best_number;
+	/// Resumes or halts the bundle production of the following slots, shared by all the clones.
+	pub(super) fn set_producing(&self, producing: bool) {
+		self.producing.store(producing, Ordering::SeqCst);
+	}
+
+	/// Returns `true` if a bundle is to be produced at `slot`, the slots the node can't act on
+	/// are skipped before doing any production work.
+	///
+	/// A slot beyond the skew tolerance of the latest primary slot is skipped as well, the
+	/// bundle would be rejected by the other executors applying the same tolerance.
+	pub(super) fn should_produce_at(&self, slot: Slot) -> bool {
+		should_produce_bundle(self.is_authority, self.is_producing(), slot, self.slot_interval) &&
+			!crate::is_future_slot(
+				slot.into(),
+				*self.latest_slot.lock(),
+				self.slot_skew_tolerance,
+			)
+	}
+
+	/// Returns the ready transactions of the transaction pool grouped by their signer, see
+	/// [`group_by_sender`].
+	pub(super) fn ready_grouped_by_sender(
+		&self,
+	) -> Result<BTreeMap<Option<AccountId>, Vec<Block::Extrinsic>>, sp_blockchain::Error> {
+		let extrinsics = self
+			.transaction_pool
+			.ready()
+			.map(|pending_tx| pending_tx.data().clone())
+			.collect();
 
-		let mut t1 = self.transaction_pool.ready_at(parent_number).fuse();
-		// TODO: proper timeout
-		let mut t2 = futures_timer::Delay::new(time::Duration::from_micros(100)).fuse();
+		let extrinsics_with_signer = self
+			.client
+			.runtime_api()
+			.extract_signer(&BlockId::Hash(self.client.info().best_hash), extrinsics)?;
 
-		let pending_iterator = select! {
-			res = t1 => res,
-			_ = t2 => {
+		Ok(group_by_sender(extrinsics_with_signer))
+	}
+
+	/// Returns the ordered extrinsics of the transaction pool that would be included in the bundle
+	/// built on top of the secondary block `parent_number`.
+	pub(super) async fn select_extrinsics(
+		&self,
+		parent_number: NumberFor<Block>,
+	) -> Result<Vec<Block::Extrinsic>, sp_blockchain::Error> {
+		let pending_iterator = ready_at_with_timeout(
+			self.transaction_pool.ready_at(parent_number),
+			// TODO: proper timeout
+			time::Duration::from_micros(100),
+			|| {
 				tracing::warn!(
 					target: LOG_TARGET,
 					"Timeout fired waiting for transaction pool at #{}, proceeding with production.",
 					parent_number,
 				);
 				self.transaction_pool.ready()
-			}
-		};
+			},
+		)
+		.await;
 
 		// TODO: proper deadline
 		let pushing_duration = time::Duration::from_micros(500);
@@ -128,6 +291,45 @@ where
 			extrinsics.push(pending_tx_data);
 		}
 
+		let parent_id = BlockId::Number(parent_number);
+		let runtime_api = self.client.runtime_api();
+		let minimum_bundle_tx_fee = match minimum_bundle_tx_fee_at(&*runtime_api, &parent_id)? {
+			Some(minimum_bundle_tx_fee) => minimum_bundle_tx_fee,
+			None => return Ok(extrinsics),
+		};
+		let mut extrinsics_covering_fee = Vec::with_capacity(extrinsics.len());
+		for extrinsic in extrinsics {
+			let tx_fee = runtime_api
+				.query_info(&parent_id, extrinsic.clone(), extrinsic.encoded_size() as u32)?
+				.partial_fee;
+			let tip = runtime_api.extrinsic_tip(&parent_id, extrinsic.clone())?;
+			if covers_bundle_fee(tx_fee, tip, minimum_bundle_tx_fee, self.fee_multiplier) {
+				extrinsics_covering_fee.push(extrinsic);
+			} else {
+				tracing::trace!(
+					target: LOG_TARGET,
+					?extrinsic,
+					?tx_fee,
+					?tip,
+					?minimum_bundle_tx_fee,
+					fee_multiplier = ?self.fee_multiplier,
+					"Skipping the transaction unable to cover the minimum bundle fee",
+				);
+			}
+		}
+
+		Ok(extrinsics_covering_fee)
+	}
+
+	pub(super) async fn produce_bundle(
+		self,
+		primary_hash: PHash,
+		slot_info: ExecutorSlotInfo,
+	) -> Result<Option<SignedOpaqueBundle>, sp_blockchain::Error> {
+		let parent_number = self.client.info().best_number;
+
+		let extrinsics = self.select_extrinsics(parent_number).await?;
+
 		let extrinsics_root = BlakeTwo256::ordered_trie_root(
 			extrinsics.iter().map(|xt| xt.encode()).collect(),
 			sp_core::storage::StateVersion::V1,
@@ -137,6 +339,7 @@ where
 
 		let bundle = Bundle {
 			header: BundleHeader {
+				domain_id: self.domain_id,
 				primary_hash,
 				slot_number: slot_info.slot.into(),
 				extrinsics_root,
@@ -149,46 +352,116 @@ where
 				.expect("Primary block hash must be the correct type; qed"),
 		))?;
 
-		if self.is_authority &&
-			SyncCryptoStore::has_keys(
-				&*self.keystore,
-				&[(ByteArray::to_raw_vec(&executor_id), ExecutorId::ID)],
-			) {
-			let to_sign = bundle.hash();
-			match SyncCryptoStore::sign_with(
-				&*self.keystore,
-				ExecutorId::ID,
-				&executor_id.clone().into(),
-				to_sign.as_ref(),
-			) {
-				Ok(Some(signature)) => {
-					let signed_bundle = SignedBundle {
-						bundle,
-						signature: ExecutorSignature::decode(&mut signature.as_slice()).map_err(
-							|err| {
-								sp_blockchain::Error::Application(Box::from(format!(
-									"Failed to decode the signature of bundle: {err}"
-								)))
-							},
-						)?,
-						signer: executor_id,
-					};
-
-					if let Err(e) = self.bundle_sender.unbounded_send(signed_bundle.clone()) {
-						tracing::error!(target: LOG_TARGET, error = ?e, "Failed to send transaction bundle");
-					}
-
-					Ok(Some(signed_bundle.into()))
-				},
-				Ok(None) => Err(sp_blockchain::Error::Application(Box::from(
-					"This should not happen as the existence of key was just checked",
-				))),
-				Err(error) => Err(sp_blockchain::Error::Application(Box::from(format!(
-					"Error occurred when signing the bundle: {error}"
-				)))),
-			}
-		} else {
-			Ok(None)
+		if !self.is_authority {
+			return Ok(None)
+		}
+
+		match crate::sign_as_executor(&self.keystore, &executor_id, bundle.hash().as_ref())? {
+			Some(signature) => {
+				let signed_bundle = SignedBundle { bundle, signature, signer: executor_id };
+
+				if let Err(e) = self.bundle_sender.unbounded_send(signed_bundle.clone()) {
+					tracing::error!(target: LOG_TARGET, error = ?e, "Failed to send transaction bundle");
+				}
+
+				Ok(Some(signed_bundle.into()))
+			},
+			None => Ok(None),
 		}
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn bundle_fee_should_be_scaled_by_the_multiplier() {
+		let minimum_fee: Balance = 100;
+		let premium = FixedU128::saturating_from_rational(6, 5);
+
+		// A transaction paying its fee without a tip.
+		assert!(covers_bundle_fee(1_000, 0, minimum_fee, FixedU128::one()));
+		assert!(!covers_bundle_fee(1_000, 0, minimum_fee, premium));
+
+		// The premium is paid by the tip, whatever the size of the transaction.
+		assert!(covers_bundle_fee(1_000, 200, minimum_fee, premium));
+		assert!(!covers_bundle_fee(1_000, 199, minimum_fee, premium));
+		assert!(covers_bundle_fee(1_000_000, 200_000, minimum_fee, premium));
+		assert!(!covers_bundle_fee(1_000_000, 199_999, minimum_fee, premium));
+
+		// A transaction lighter than the minimum fee has to top it up with a tip.
+		assert!(!covers_bundle_fee(50, 0, minimum_fee, FixedU128::one()));
+		assert!(covers_bundle_fee(50, 50, minimum_fee, FixedU128::one()));
+	}
+
+	#[test]
+	fn ready_at_with_timeout_should_fall_back_to_current_ready_set() {
+		let delayed_ready_at = async {
+			futures_timer::Delay::new(time::Duration::from_secs(10)).await;
+			vec![1, 2, 3]
+		};
+		let ready_set = futures::executor::block_on(ready_at_with_timeout(
+			delayed_ready_at,
+			time::Duration::from_millis(10),
+			|| vec![1],
+		));
+		assert_eq!(ready_set, vec![1]);
+	}
+
+	#[test]
+	fn ready_at_with_timeout_should_prefer_resolved_ready_at() {
+		let ready_set = futures::executor::block_on(ready_at_with_timeout(
+			async { vec![1, 2, 3] },
+			time::Duration::from_secs(10),
+			|| vec![1],
+		));
+		assert_eq!(ready_set, vec![1, 2, 3]);
+	}
+
+	#[test]
+	fn extrinsics_should_be_grouped_by_sender_in_order() {
+		let extrinsics = vec![
+			(Some(1), "a1"),
+			(Some(2), "b1"),
+			(None, "u1"),
+			(Some(1), "a2"),
+			(Some(2), "b2"),
+			(Some(1), "a3"),
+		];
+
+		let groups = group_by_sender(extrinsics);
+		assert_eq!(
+			groups.into_iter().collect::<Vec<_>>(),
+			vec![
+				(None, vec!["u1"]),
+				(Some(1), vec!["a1", "a2", "a3"]),
+				(Some(2), vec!["b1", "b2"]),
+			]
+		);
+	}
+
+	#[test]
+	fn non_authority_should_not_produce_bundle_at_any_slot() {
+		assert!((0..100)
+			.map(Slot::from)
+			.all(|slot| !should_produce_bundle(false, true, slot, 1)));
+		assert!((0..100)
+			.map(Slot::from)
+			.all(|slot| !should_produce_bundle(true, false, slot, 1)));
+		assert!((0..100).map(Slot::from).all(|slot| should_produce_bundle(true, true, slot, 1)));
+	}
+
+	#[test]
+	fn bundle_should_be_produced_at_the_sampled_slots() {
+		let sampled_slots = (0..10)
+			.map(Slot::from)
+			.filter(|slot| should_produce_bundle(true, true, *slot, 3))
+			.map(u64::from)
+			.collect::<Vec<_>>();
+		assert_eq!(sampled_slots, vec![0, 3, 6, 9]);
+
+		// An interval of `0` is treated as producing on each slot.
+		assert!((0..10).map(Slot::from).all(|slot| should_produce_bundle(true, true, slot, 0)));
+	}
+}
@@ -0,0 +1,102 @@
+//! Per-author rate limiting of the bundles received from the executor network.
+
+use parking_lot::Mutex;
+use sp_executor::ExecutorId;
+use std::{
+	collections::HashMap,
+	time::{Duration, Instant},
+};
+
+/// Maximum rate of the bundles accepted from the same author.
+#[derive(Debug, Clone, Copy)]
+pub struct BundleRateLimit {
+	/// Maximum number of bundles accepted from an author within `interval`.
+	pub max_bundles: u32,
+	/// Interval over which `max_bundles` bundles are allowed.
+	pub interval: Duration,
+}
+
+impl Default for BundleRateLimit {
+	fn default() -> Self {
+		Self { max_bundles: 20, interval: Duration::from_secs(10) }
+	}
+}
+
+struct Bucket {
+	tokens: f64,
+	last_refill: Instant,
+}
+
+/// Token-bucket rate limiter keyed by the bundle author.
+pub(crate) struct BundleRateLimiter {
+	limit: BundleRateLimit,
+	buckets: Mutex<HashMap<ExecutorId, Bucket>>,
+}
+
+impl BundleRateLimiter {
+	pub(crate) fn new(limit: BundleRateLimit) -> Self {
+		Self { limit, buckets: Mutex::new(HashMap::new()) }
+	}
+
+	/// Returns `true` if a bundle from `author` received at `now` is within the rate limit.
+	pub(crate) fn try_acquire(&self, author: &ExecutorId, now: Instant) -> bool {
+		let capacity = f64::from(self.limit.max_bundles);
+		let refill_per_sec = capacity / self.limit.interval.as_secs_f64();
+
+		let mut buckets = self.buckets.lock();
+		let bucket = buckets
+			.entry(author.clone())
+			.or_insert_with(|| Bucket { tokens: capacity, last_refill: now });
+
+		let elapsed = now.saturating_duration_since(bucket.last_refill);
+		bucket.tokens = (bucket.tokens + elapsed.as_secs_f64() * refill_per_sec).min(capacity);
+		bucket.last_refill = now;
+
+		if bucket.tokens >= 1.0 {
+			bucket.tokens -= 1.0;
+			true
+		} else {
+			false
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const LIMIT: BundleRateLimit =
+		BundleRateLimit { max_bundles: 3, interval: Duration::from_secs(3) };
+
+	fn author(byte: u8) -> ExecutorId {
+		ExecutorId::from(sp_core::sr25519::Public::from_raw([byte; 32]))
+	}
+
+	#[test]
+	fn bundles_exceeding_the_limit_should_be_dropped() {
+		let limiter = BundleRateLimiter::new(LIMIT);
+		let now = Instant::now();
+
+		for _ in 0..LIMIT.max_bundles {
+			assert!(limiter.try_acquire(&author(1), now));
+		}
+		assert!(!limiter.try_acquire(&author(1), now));
+
+		// Other authors are not affected.
+		assert!(limiter.try_acquire(&author(2), now));
+	}
+
+	#[test]
+	fn rate_limit_should_reset_over_time() {
+		let limiter = BundleRateLimiter::new(LIMIT);
+		let now = Instant::now();
+
+		for _ in 0..LIMIT.max_bundles {
+			assert!(limiter.try_acquire(&author(1), now));
+		}
+		assert!(!limiter.try_acquire(&author(1), now));
+
+		assert!(limiter.try_acquire(&author(1), now + Duration::from_secs(1)));
+		assert!(!limiter.try_acquire(&author(1), now + Duration::from_secs(1)));
+	}
+}
@@ -0,0 +1,87 @@
+//! Routing of the gossip messages to the executors of the domains run by the same process.
+
+use cirrus_client_executor_gossip::{Action, GossipMessageHandler};
+use sp_executor::{DomainId, SignedBundle, SignedExecutionReceipt};
+use sp_runtime::traits::{Block as BlockT, NumberFor};
+use std::collections::BTreeMap;
+
+const LOG_TARGET: &str = "domain-registry";
+
+/// Registry of the executors run by this process, keyed by the domain they serve.
+///
+/// The registry is the [`GossipMessageHandler`] of the executor gossip, each bundle received from
+/// the network is routed to the executor of the domain in its header. The execution receipts
+/// carry no domain id and are only produced for the system domain, they are routed to the
+/// executor of [`DomainId::SYSTEM`].
+pub struct DomainRegistry<Executor> {
+	executors: BTreeMap<DomainId, Executor>,
+}
+
+impl<Executor> Default for DomainRegistry<Executor> {
+	fn default() -> Self {
+		Self { executors: BTreeMap::new() }
+	}
+}
+
+impl<Executor: Clone> Clone for DomainRegistry<Executor> {
+	fn clone(&self) -> Self {
+		Self { executors: self.executors.clone() }
+	}
+}
+
+impl<Executor> DomainRegistry<Executor> {
+	/// Creates an empty registry.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Registers `executor` as the executor of `domain_id`, returns the executor previously
+	/// registered for the domain if any.
+	pub fn register(&mut self, domain_id: DomainId, executor: Executor) -> Option<Executor> {
+		self.executors.insert(domain_id, executor)
+	}
+
+	/// Returns the executor of `domain_id`.
+	pub fn executor(&self, domain_id: DomainId) -> Option<&Executor> {
+		self.executors.get(&domain_id)
+	}
+
+	/// Returns the domains with a registered executor.
+	pub fn domains(&self) -> impl Iterator<Item = DomainId> + '_ {
+		self.executors.keys().copied()
+	}
+}
+
+impl<PBlock, Block, Executor> GossipMessageHandler<PBlock, Block> for DomainRegistry<Executor>
+where
+	PBlock: BlockT,
+	Block: BlockT,
+	Executor: GossipMessageHandler<PBlock, Block>,
+{
+	type Error = Executor::Error;
+
+	fn on_bundle(&self, bundle: &SignedBundle<Block::Extrinsic>) -> Result<Action, Self::Error> {
+		let domain_id = bundle.bundle.header.domain_id;
+		match self.executor(domain_id) {
+			Some(executor) => executor.on_bundle(bundle),
+			None => {
+				tracing::debug!(
+					target: LOG_TARGET,
+					?domain_id,
+					"Ignoring the bundle of a domain not run by this node",
+				);
+				Ok(Action::Empty)
+			},
+		}
+	}
+
+	fn on_execution_receipt(
+		&self,
+		execution_receipt: &SignedExecutionReceipt<NumberFor<PBlock>, PBlock::Hash, Block::Hash>,
+	) -> Result<Action, Self::Error> {
+		match self.executor(DomainId::SYSTEM) {
+			Some(executor) => executor.on_execution_receipt(execution_receipt),
+			None => Ok(Action::Empty),
+		}
+	}
+}
@@ -0,0 +1,265 @@
+//! Tracking of the fraud proofs submitted by the executor until they land in the primary chain.
+//!
+//! The tracking is in-memory only, the fraud proofs still pending on a restart are neither
+//! confirmed nor rejected afterwards. The submitted disputes are persisted separately in the
+//! aux-db so that they are not raised again.
+
+use crate::worker::to_opaque_extrinsics;
+use codec::{Decode, Encode};
+use parking_lot::Mutex;
+use sc_client_api::BlockBackend;
+use sp_api::{ApiError, ProvideRuntimeApi};
+use sp_blockchain::HeaderBackend;
+use sp_core::H256;
+use sp_executor::{ExecutionPhase, ExecutorApi, FraudProof};
+use sp_runtime::{
+	generic::BlockId,
+	traits::{Block as BlockT, NumberFor, Saturating},
+};
+
+/// Outcome of a fraud proof submitted by this executor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FraudProofEvent<Number, Hash> {
+	/// The fraud proof is included in the primary block `primary_hash`.
+	FraudProofConfirmed { fraud_proof: FraudProof, primary_number: Number, primary_hash: Hash },
+	/// The fraud proof is not going to be included in the primary chain.
+	FraudProofRejected { fraud_proof: FraudProof, reason: FraudProofRejection },
+}
+
+/// Why a submitted fraud proof is considered rejected by the primary chain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FraudProofRejection {
+	/// Submitting the fraud proof to the primary chain failed.
+	SubmissionFailed,
+	/// The fraud proof is not included in the primary chain within the
+	/// [`crate::ExecutorConfig::fraud_proof_inclusion_deadline`] blocks.
+	InclusionTimeout,
+}
+
+/// The fields identifying the state transition a fraud proof disputes.
+///
+/// The storage proof is not part of it, as the proofs of the same transition built from the
+/// different local states are equally valid.
+fn fraud_proof_id(fraud_proof: &FraudProof) -> (H256, H256, H256, &ExecutionPhase) {
+	(
+		fraud_proof.parent_hash,
+		fraud_proof.pre_state_root,
+		fraud_proof.post_state_root,
+		&fraud_proof.execution_phase,
+	)
+}
+
+struct PendingFraudProof<Number> {
+	fraud_proof: FraudProof,
+	/// Primary best number when the fraud proof is submitted.
+	submitted_at: Number,
+}
+
+/// Set of the submitted fraud proofs awaiting the inclusion in the primary chain, kept in memory
+/// only.
+pub(crate) struct FraudProofTracker<Number> {
+	inclusion_deadline: Number,
+	pending: Mutex<Vec<PendingFraudProof<Number>>>,
+}
+
+impl<Number> FraudProofTracker<Number>
+where
+	Number: Saturating + PartialOrd + Copy,
+{
+	pub(crate) fn new(inclusion_deadline: Number) -> Self {
+		Self { inclusion_deadline, pending: Mutex::new(Vec::new()) }
+	}
+
+	/// Starts tracking `fraud_proof` submitted when the primary best block is `submitted_at`.
+	pub(crate) fn track(&self, fraud_proof: FraudProof, submitted_at: Number) {
+		let mut pending = self.pending.lock();
+		if !pending
+			.iter()
+			.any(|p| fraud_proof_id(&p.fraud_proof) == fraud_proof_id(&fraud_proof))
+		{
+			pending.push(PendingFraudProof { fraud_proof, submitted_at });
+		}
+	}
+
+	/// Stops tracking `fraud_proof` whose submission failed, returns the rejection event if it
+	/// was tracked.
+	pub(crate) fn on_submission_failed<Hash>(
+		&self,
+		fraud_proof: &FraudProof,
+	) -> Option<FraudProofEvent<Number, Hash>> {
+		let mut pending = self.pending.lock();
+		let index = pending
+			.iter()
+			.position(|p| fraud_proof_id(&p.fraud_proof) == fraud_proof_id(fraud_proof))?;
+		Some(FraudProofEvent::FraudProofRejected {
+			fraud_proof: pending.swap_remove(index).fraud_proof,
+			reason: FraudProofRejection::SubmissionFailed,
+		})
+	}
+
+	/// Returns `true` if no fraud proof is awaiting the inclusion.
+	pub(crate) fn is_empty(&self) -> bool {
+		self.pending.lock().is_empty()
+	}
+
+	/// Resolves the pending fraud proofs against the primary block `(primary_number,
+	/// primary_hash)` including `included` fraud proofs.
+	///
+	/// The included fraud proofs are confirmed, the ones not included within the deadline are
+	/// rejected.
+	pub(crate) fn on_primary_block<Hash: Copy>(
+		&self,
+		(primary_number, primary_hash): (Number, Hash),
+		included: &[FraudProof],
+	) -> Vec<FraudProofEvent<Number, Hash>> {
+		let mut pending = self.pending.lock();
+		let mut events = Vec::new();
+		pending.retain(|p| {
+			if included
+				.iter()
+				.any(|fraud_proof| fraud_proof_id(fraud_proof) == fraud_proof_id(&p.fraud_proof))
+			{
+				events.push(FraudProofEvent::FraudProofConfirmed {
+					fraud_proof: p.fraud_proof.clone(),
+					primary_number,
+					primary_hash,
+				});
+				false
+			} else if primary_number > p.submitted_at.saturating_add(self.inclusion_deadline) {
+				events.push(FraudProofEvent::FraudProofRejected {
+					fraud_proof: p.fraud_proof.clone(),
+					reason: FraudProofRejection::InclusionTimeout,
+				});
+				false
+			} else {
+				true
+			}
+		});
+		events
+	}
+}
+
+/// Scans the primary block `primary_number` for the fraud proofs pending in `tracker`.
+pub(crate) fn scan_primary_block<PBlock, PClient, SecondaryHash>(
+	primary_chain_client: &PClient,
+	tracker: &FraudProofTracker<NumberFor<PBlock>>,
+	primary_number: NumberFor<PBlock>,
+) -> Result<Vec<FraudProofEvent<NumberFor<PBlock>, PBlock::Hash>>, ApiError>
+where
+	PBlock: BlockT,
+	PClient: HeaderBackend<PBlock> + BlockBackend<PBlock> + ProvideRuntimeApi<PBlock>,
+	PClient::Api: ExecutorApi<PBlock, SecondaryHash>,
+	SecondaryHash: Encode + Decode,
+{
+	// Skip loading the block body in the common case of nothing to track.
+	if tracker.is_empty() {
+		return Ok(Vec::new())
+	}
+
+	let primary_hash = match primary_chain_client.hash(primary_number)? {
+		Some(primary_hash) => primary_hash,
+		None => return Ok(Vec::new()),
+	};
+	let body = primary_chain_client
+		.block_body(&BlockId::Hash(primary_hash))?
+		.unwrap_or_default();
+
+	let included = primary_chain_client.runtime_api().extract_fraud_proofs(
+		&BlockId::Hash(primary_hash),
+		to_opaque_extrinsics::<PBlock>(body, "extract_fraud_proofs")?,
+	)?;
+
+	Ok(tracker.on_primary_block((primary_number, primary_hash), &included))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use sp_trie::StorageProof;
+
+	fn fraud_proof(parent_number: u32) -> FraudProof {
+		FraudProof {
+			parent_number,
+			parent_hash: H256::repeat_byte(parent_number as u8),
+			pre_state_root: H256::repeat_byte(0x01),
+			post_state_root: H256::repeat_byte(0x02),
+			proof: StorageProof::empty(),
+			execution_phase: ExecutionPhase::FinalizeBlock,
+		}
+	}
+
+	#[test]
+	fn included_fraud_proof_should_be_confirmed() {
+		let tracker = FraudProofTracker::<u32>::new(10);
+		tracker.track(fraud_proof(1), 100);
+		tracker.track(fraud_proof(2), 100);
+
+		assert!(tracker.on_primary_block((101, H256::repeat_byte(0xaa)), &[]).is_empty());
+
+		// The included proof is identified by the disputed transition, not the storage proof.
+		let included = FraudProof { proof: StorageProof::new(vec![vec![0xff]]), ..fraud_proof(2) };
+		assert_eq!(
+			tracker.on_primary_block((102, H256::repeat_byte(0xbb)), &[included]),
+			vec![FraudProofEvent::FraudProofConfirmed {
+				fraud_proof: fraud_proof(2),
+				primary_number: 102,
+				primary_hash: H256::repeat_byte(0xbb),
+			}]
+		);
+		assert!(!tracker.is_empty());
+	}
+
+	#[test]
+	fn fraud_proofs_of_different_phases_should_be_tracked_apart() {
+		let tracker = FraudProofTracker::<u32>::new(10);
+		let initialize_block_fraud_proof = FraudProof {
+			execution_phase: ExecutionPhase::InitializeBlock { call_data: vec![1, 2, 3] },
+			..fraud_proof(1)
+		};
+		tracker.track(fraud_proof(1), 100);
+		tracker.track(initialize_block_fraud_proof.clone(), 100);
+
+		assert_eq!(
+			tracker.on_primary_block((101, H256::zero()), &[initialize_block_fraud_proof.clone()]),
+			vec![FraudProofEvent::FraudProofConfirmed {
+				fraud_proof: initialize_block_fraud_proof,
+				primary_number: 101,
+				primary_hash: H256::zero(),
+			}]
+		);
+		// The proof of the other phase is still awaiting the inclusion.
+		assert!(!tracker.is_empty());
+	}
+
+	#[test]
+	fn fraud_proof_not_included_in_time_should_be_rejected() {
+		let tracker = FraudProofTracker::<u32>::new(10);
+		tracker.track(fraud_proof(1), 100);
+
+		assert!(tracker.on_primary_block((110, H256::zero()), &[]).is_empty());
+		assert_eq!(
+			tracker.on_primary_block((111, H256::zero()), &[]),
+			vec![FraudProofEvent::FraudProofRejected {
+				fraud_proof: fraud_proof(1),
+				reason: FraudProofRejection::InclusionTimeout,
+			}]
+		);
+		assert!(tracker.is_empty());
+	}
+
+	#[test]
+	fn fraud_proof_failed_to_submit_should_be_rejected() {
+		let tracker = FraudProofTracker::<u32>::new(10);
+		tracker.track(fraud_proof(1), 100);
+
+		assert_eq!(
+			tracker.on_submission_failed::<H256>(&fraud_proof(1)),
+			Some(FraudProofEvent::FraudProofRejected {
+				fraud_proof: fraud_proof(1),
+				reason: FraudProofRejection::SubmissionFailed,
+			})
+		);
+		assert!(tracker.on_submission_failed::<H256>(&fraud_proof(1)).is_none());
+		assert!(tracker.is_empty());
+	}
+}
@@ -59,47 +59,284 @@
 mod aux_schema;
 mod bundle_processor;
 mod bundle_producer;
+mod bundle_rate_limiter;
+mod domain_registry;
+mod fraud_proof_tracker;
 mod merkle_tree;
+mod metrics;
+mod notification;
+mod proof_submission;
+mod prover;
+mod rebroadcast_window;
+mod receipt_diff;
+mod receipt_json;
 #[cfg(test)]
 mod tests;
+mod trace;
+mod validation_cache;
 mod worker;
 
+pub use crate::{
+	bundle_processor::build_secondary_block,
+	bundle_rate_limiter::BundleRateLimit,
+	domain_registry::DomainRegistry,
+	fraud_proof_tracker::{FraudProofEvent, FraudProofRejection},
+	notification::ExecutorNotificationStream,
+	proof_submission::{PrimaryChainProofSubmitter, ProofSubmitter, SubmissionRetry, SubmitError},
+	receipt_diff::{diff_receipts, ReceiptDiff},
+	receipt_json::receipt_to_json,
+	trace::replay_block,
+	worker::{extract_bundles, BlockInfo, WorkerRestartPolicy},
+};
 use crate::{
-	bundle_processor::BundleProcessor, bundle_producer::BundleProducer, worker::BlockInfo,
+	bundle_processor::{BundleProcessor, ExtrinsicTimingsCache},
+	bundle_producer::BundleProducer,
+	bundle_rate_limiter::BundleRateLimiter,
+	fraud_proof_tracker::{scan_primary_block, FraudProofTracker},
+	metrics::ProvingMetrics,
+	notification::ExecutorNotificationSender,
+	prover::{Prover, ProvingError},
+	rebroadcast_window::RebroadcastWindow,
+	validation_cache::ValidationCache,
 };
 use cirrus_block_builder::{BlockBuilder, RecordProof};
-use cirrus_client_executor_gossip::{Action, GossipMessageHandler};
-use cirrus_primitives::{AccountId, SecondaryApi};
-use codec::{Decode, Encode};
-use futures::{FutureExt, Stream};
-use sc_client_api::{AuxStore, BlockBackend};
-use sc_network::NetworkService;
+use cirrus_client_executor_gossip::{cost, Action, GossipMessageHandler, PeerReputation};
+use cirrus_primitives::{AccountId, Balance, SecondaryApi};
+use codec::{Decode, DecodeAll, Encode};
+use futures::{FutureExt, Stream, StreamExt};
+use lru::LruCache;
+use pallet_transaction_payment_rpc_runtime_api::TransactionPaymentApi;
+use parking_lot::Mutex;
+use sc_client_api::{AuxStore, BlockBackend, BlockchainEvents};
+use sc_network::{NetworkService, ReputationChange};
 use sc_utils::mpsc::TracingUnboundedSender;
-use sp_api::ProvideRuntimeApi;
+use sp_api::{ProvideRuntimeApi, RuntimeApiInfo};
 use sp_blockchain::HeaderBackend;
 use sp_consensus::{BlockStatus, SelectChain};
 use sp_consensus_slots::Slot;
 use sp_core::{
 	traits::{CodeExecutor, SpawnEssentialNamed, SpawnNamed},
-	H256,
+	ByteArray, H256,
 };
 use sp_executor::{
-	Bundle, BundleEquivocationProof, ExecutionPhase, ExecutionReceipt, ExecutorApi, ExecutorId,
-	FraudProof, InvalidTransactionProof, OpaqueBundle, SignedBundle, SignedExecutionReceipt,
+	Bundle, BundleEquivocationProof, DomainId, ExecutionPhase, ExecutionReceipt, ExecutorApi,
+	ExecutorId, ExecutorSignature, FraudProof, InvalidTransactionProof, OpaqueBundle, SignedBundle,
+	SignedExecutionReceipt, SignedOpaqueBundle,
 };
-use sp_keystore::SyncCryptoStorePtr;
+use sp_keystore::{SyncCryptoStore, SyncCryptoStorePtr};
 use sp_runtime::{
 	generic::BlockId,
-	traits::{Block as BlockT, HashFor, Header as HeaderT, NumberFor, One, Saturating, Zero},
-	RuntimeAppPublic, SaturatedConversion,
+	traits::{
+		AtLeast32BitUnsigned, BlakeTwo256, Block as BlockT, Hash as HashT, HashFor,
+		Header as HeaderT, NumberFor, One, Saturating, Zero,
+	},
+	transaction_validity::TransactionSource,
+	FixedU128, PerThing, Perbill, RuntimeAppPublic, SaturatedConversion,
 };
+use sp_transaction_pool::runtime_api::TaggedTransactionQueue;
 use sp_trie::StorageProof;
-use std::{borrow::Cow, sync::Arc};
+use std::{borrow::Cow, collections::BTreeMap, fmt, sync::Arc, time::Duration};
 use subspace_core_primitives::{BlockNumber, Randomness, Sha256Hash};
+use substrate_prometheus_endpoint::Registry;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tracing::Instrument;
 
 /// The logging target.
 const LOG_TARGET: &str = "cirrus::executor";
 
+/// Default number of slots a bundle may be ahead of the latest primary slot seen locally,
+/// tolerating the clock drift between the nodes.
+const BUNDLE_SLOT_TOLERANCE: u64 = 2;
+
+/// Minimum version of the [`ExecutorApi`] of the primary runtime supported by this executor.
+///
+/// Version 2 introduces `transaction_bundle_extrinsic` and `extract_fraud_proofs`.
+const MIN_EXECUTOR_API_VERSION: u32 = 2;
+
+/// Execution phase a fraud proof is built for.
+#[derive(Debug, Clone, Copy)]
+enum FraudProofPhase {
+	/// The `initialize_block` hook.
+	InitializeBlock,
+	/// The extrinsic at the given index of the block body.
+	ApplyExtrinsic(usize),
+	/// The `finalize_block` hook.
+	FinalizeBlock,
+}
+
+/// Tunables of the [`Executor`].
+#[derive(Debug, Clone, Copy)]
+pub struct ExecutorConfig {
+	/// Retry policy for submitting the proofs to the primary chain.
+	pub submission_retry: SubmissionRetry,
+	/// How long to wait for the parent of the secondary block to build that is still under import
+	/// before failing the bundle processing, `None` to not wait at all.
+	pub queued_block_wait: Option<Duration>,
+	/// Maximum rate of the bundles accepted from the same author.
+	pub bundle_rate_limit: BundleRateLimit,
+	/// Maximum number of the extrinsics in a bundle received from the network, the bundles with
+	/// more extrinsics are rejected before the extrinsics are checked.
+	pub max_received_bundle_extrinsics: usize,
+	/// Maximum encoded size in bytes of a bundle received from the network, the larger bundles
+	/// are rejected before the extrinsics are checked.
+	pub max_received_bundle_size: usize,
+	/// Window within which the same bundle or execution receipt is rebroadcast at most once,
+	/// [`Duration::ZERO`] to rebroadcast each time it's handled.
+	pub rebroadcast_suppression_window: Duration,
+	/// Interval of checking whether the local receipt of a future block is ready.
+	pub receipt_poll_interval: Duration,
+	/// Interval of the checkpoint blocks whose receipts are fully verified, `1` to fully verify
+	/// the receipt of each block.
+	///
+	/// Only the final state root of the receipts between the checkpoints is compared, the full
+	/// verification is deferred unless the final state root diverges. The receipts whose full
+	/// verification is deferred are not rebroadcast.
+	pub receipt_checkpoint_interval: u32,
+	/// Fraction of the blocks whose receipts are compared with the local ones,
+	/// [`Perbill::one`] to verify the receipts of all the blocks.
+	///
+	/// The receipts of the blocks not sampled are rebroadcast without the local comparison, which
+	/// suits the lightweight verifiers that collectively cover all the blocks.
+	pub verification_sampling_rate: Perbill,
+	/// Seed of the pseudo-random block sampling, the verifiers are expected to use the different
+	/// seeds so that they sample the different blocks.
+	pub verification_sampling_seed: u64,
+	/// Number of the extra execution receipts retained below the pruning boundary, e.g., for
+	/// debugging the disputes, `0` to keep only the receipts within the pruning depth.
+	///
+	/// The gossiped receipts beyond the widened retention are still ignored.
+	pub receipt_retention_extra: u32,
+	/// Capacity of the cache of the block bodies prefetched when a receipt arrives, `None` to
+	/// load the block bodies only when a fraud proof is constructed.
+	///
+	/// Prefetching cuts the latency of constructing a fraud proof for the mismatched receipts.
+	pub block_body_prefetch: Option<usize>,
+	/// Number of the most recent secondary blocks whose receipts are warmed up on startup, `None`
+	/// to skip the warm-up.
+	///
+	/// The bodies of the blocks with a local receipt are loaded into the block body cache, so
+	/// that the node constructs the fraud proofs of the recent blocks as fast as before a
+	/// restart, it has no effect unless [`Self::block_body_prefetch`] is enabled.
+	pub warm_up_depth: Option<u32>,
+	/// Number of the most recent blocks whose per-extrinsic execution time is kept for
+	/// diagnostics, `None` to not time the extrinsics.
+	///
+	/// The timings are exposed via [`Executor::extrinsic_timings`], they are not part of the
+	/// execution receipts.
+	pub extrinsic_timings_capacity: Option<usize>,
+	/// Capacity of the cache of the transaction validity results of the received bundle
+	/// extrinsics, `0` to validate each extrinsic on every check.
+	///
+	/// The cache is invalidated when the secondary best block changes.
+	pub transaction_validation_cache_size: usize,
+	/// Whether to submit the produced proofs to the primary chain.
+	///
+	/// When disabled, the misbehaviors are still detected and the proofs are still generated, but
+	/// they are only logged, which is useful for running a detection-only node.
+	pub proof_submission_enabled: bool,
+	/// Number of the primary blocks a submitted fraud proof has to be included within, otherwise
+	/// it's reported as [`FraudProofEvent::FraudProofRejected`].
+	pub fraud_proof_inclusion_deadline: u32,
+	/// Maximum number of the fraud proofs constructed concurrently, the constructions beyond it
+	/// are queued.
+	pub max_concurrent_provers: usize,
+	/// Maximum number of the gossiped receipts of the future blocks waiting for the local receipts
+	/// simultaneously, the future receipts beyond it are rejected.
+	///
+	/// Unlike the provers, the waiters are induced directly by the peers, the cap prevents a peer
+	/// from piling them up by sending the receipts of the far-future blocks.
+	pub max_pending_future_receipts: usize,
+	/// Maximum trace length of the gossiped receipts, the longer receipts are rejected before
+	/// anything is looked up for them.
+	///
+	/// Once the local block is available, the trace is further bounded by its extrinsics plus the
+	/// roots of initializing and finalizing the block.
+	pub max_trace_len: usize,
+	/// Number of the blocks that must be built on top of a secondary block before its execution
+	/// receipt is sent, `0` to send the receipt as soon as the block is imported.
+	///
+	/// A deeper confirmation avoids gossiping the receipts of the short-lived forks.
+	pub receipt_confirmation_depth: u32,
+	/// Interval of the slots the bundles are produced at, e.g., `3` to produce a bundle on every
+	/// third slot, `1` to produce a bundle on each slot.
+	///
+	/// A larger interval reduces the load of the bundle production.
+	pub bundle_slot_interval: u64,
+	/// Number of slots a bundle may be ahead of the latest primary slot seen locally, tolerating
+	/// the clock drift between the nodes.
+	///
+	/// The received bundles beyond it are rejected as [`GossipMessageError::FutureBundleSlot`],
+	/// the bundle producer applies the same bound to its own bundles so that the honest bundles
+	/// are never rejected by the nodes using the same tolerance.
+	pub slot_skew_tolerance: u64,
+	/// Multiplier of its own fee a transaction has to offer, i.e., its fee plus tip, to be
+	/// included in the bundles produced by this executor, e.g., `1.2` to require a tip of 20% of
+	/// the fee, `1` to include all the transactions covering the minimum bundle fee.
+	pub bundle_fee_multiplier: FixedU128,
+	/// The domain this executor produces and processes the bundles for.
+	pub domain_id: DomainId,
+	/// What to do when the worker processing the primary blocks and slots panics.
+	pub worker_restart_policy: WorkerRestartPolicy,
+	/// What to do with a bundle received from the network once it's found to be an equivocation,
+	/// its equivocation proof is submitted either way.
+	pub equivocation_policy: EquivocationPolicy,
+}
+
+impl Default for ExecutorConfig {
+	fn default() -> Self {
+		Self {
+			submission_retry: SubmissionRetry::default(),
+			queued_block_wait: None,
+			bundle_rate_limit: BundleRateLimit::default(),
+			max_received_bundle_extrinsics: 10_000,
+			max_received_bundle_size: 5 * 1024 * 1024,
+			rebroadcast_suppression_window: Duration::from_secs(2),
+			receipt_poll_interval: Duration::from_millis(100),
+			receipt_checkpoint_interval: 1,
+			verification_sampling_rate: Perbill::one(),
+			verification_sampling_seed: 0,
+			receipt_retention_extra: 0,
+			block_body_prefetch: None,
+			warm_up_depth: None,
+			extrinsic_timings_capacity: None,
+			transaction_validation_cache_size: 1024,
+			proof_submission_enabled: true,
+			fraud_proof_inclusion_deadline: 10,
+			max_concurrent_provers: std::thread::available_parallelism()
+				.map(|parallelism| parallelism.get())
+				.unwrap_or(1),
+			max_pending_future_receipts: 128,
+			max_trace_len: 65_536,
+			receipt_confirmation_depth: 0,
+			bundle_slot_interval: 1,
+			slot_skew_tolerance: BUNDLE_SLOT_TOLERANCE,
+			bundle_fee_multiplier: FixedU128::one(),
+			domain_id: DomainId::SYSTEM,
+			worker_restart_policy: WorkerRestartPolicy::default(),
+			equivocation_policy: EquivocationPolicy::default(),
+		}
+	}
+}
+
+/// What to do with a bundle received from the network that is an equivocation of its author.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EquivocationPolicy {
+	/// Reports the equivocation and stops processing the bundle, the peer sending it is punished.
+	ReportAndAbort,
+	/// Reports the equivocation and processes the bundle like any other valid bundle.
+	ReportAndContinue,
+}
+
+impl Default for EquivocationPolicy {
+	fn default() -> Self {
+		Self::ReportAndAbort
+	}
+}
+
+/// Checks whether the bundle is an equivocation of its author, returns the proof if so.
+type BundleEquivocationCheck<Extrinsic> =
+	Arc<dyn Fn(&Bundle<Extrinsic>) -> Option<BundleEquivocationProof> + Send + Sync>;
+
 /// The implementation of the Cirrus `Executor`.
 pub struct Executor<Block, PBlock, Client, PClient, TransactionPool, Backend, E>
 where
@@ -113,7 +350,47 @@ where
 	transaction_pool: Arc<TransactionPool>,
 	backend: Arc<Backend>,
 	code_executor: Arc<E>,
+	bundle_producer: BundleProducer<Block, PBlock, Client, PClient, TransactionPool>,
 	bundle_processor: BundleProcessor<Block, PBlock, Client, PClient, Backend>,
+	submission_retry: SubmissionRetry,
+	proof_submitter: Arc<dyn ProofSubmitter<PBlock>>,
+	bundle_rate_limiter: Arc<BundleRateLimiter>,
+	max_received_bundle_extrinsics: usize,
+	max_received_bundle_size: usize,
+	rebroadcast_window: Arc<RebroadcastWindow>,
+	receipt_poll_interval: Duration,
+	receipt_checkpoint_interval: u32,
+	verification_sampling_rate: Perbill,
+	verification_sampling_seed: u64,
+	receipt_retention_extra: u32,
+	/// Returns `true` while the primary node is major syncing, the gossiped receipts are ignored
+	/// meanwhile as the local ones are far behind.
+	is_major_syncing: Arc<dyn Fn() -> bool + Send + Sync>,
+	check_bundle_equivocation: BundleEquivocationCheck<Block::Extrinsic>,
+	equivocation_policy: EquivocationPolicy,
+	block_body_cache: Option<Arc<Mutex<LruCache<Block::Hash, Vec<Block::Extrinsic>>>>>,
+	extrinsic_timings: Option<ExtrinsicTimingsCache<Block::Hash>>,
+	validation_cache: Arc<ValidationCache<Block::Hash>>,
+	proof_submission_enabled: bool,
+	execution_receipt_stream:
+		ExecutorNotificationStream<SignedExecutionReceiptFor<PBlock, Block::Hash>>,
+	fraud_proof_envelope_sender: ExecutorNotificationSender<Vec<u8>>,
+	fraud_proof_envelope_stream: ExecutorNotificationStream<Vec<u8>>,
+	fraud_proof_tracker: Arc<FraudProofTracker<NumberFor<PBlock>>>,
+	fraud_proof_event_sender: ExecutorNotificationSender<FraudProofEventFor<PBlock>>,
+	fraud_proof_event_stream: ExecutorNotificationStream<FraudProofEventFor<PBlock>>,
+	invalid_transaction_proof_sender: ExecutorNotificationSender<InvalidTransactionProof>,
+	invalid_transaction_proof_stream: ExecutorNotificationStream<InvalidTransactionProof>,
+	provers: Arc<Semaphore>,
+	future_receipt_waiters: FutureReceiptWaiters,
+	max_trace_len: usize,
+	proving_metrics: Option<ProvingMetrics>,
+	domain_id: DomainId,
+	active_leaves: Vec<BlockInfo<PBlock>>,
+	latest_slot: Arc<Mutex<Option<Slot>>>,
+	slot_skew_tolerance: u64,
+	/// Span identifying this executor instance, every log of the instance is emitted within it.
+	span: tracing::Span,
 }
 
 impl<Block, PBlock, Client, PClient, TransactionPool, Backend, E> Clone
@@ -130,17 +407,72 @@ where
 			transaction_pool: self.transaction_pool.clone(),
 			backend: self.backend.clone(),
 			code_executor: self.code_executor.clone(),
+			bundle_producer: self.bundle_producer.clone(),
 			bundle_processor: self.bundle_processor.clone(),
+			submission_retry: self.submission_retry,
+			proof_submitter: self.proof_submitter.clone(),
+			bundle_rate_limiter: self.bundle_rate_limiter.clone(),
+			max_received_bundle_extrinsics: self.max_received_bundle_extrinsics,
+			max_received_bundle_size: self.max_received_bundle_size,
+			rebroadcast_window: self.rebroadcast_window.clone(),
+			receipt_poll_interval: self.receipt_poll_interval,
+			receipt_checkpoint_interval: self.receipt_checkpoint_interval,
+			verification_sampling_rate: self.verification_sampling_rate,
+			verification_sampling_seed: self.verification_sampling_seed,
+			receipt_retention_extra: self.receipt_retention_extra,
+			is_major_syncing: self.is_major_syncing.clone(),
+			check_bundle_equivocation: self.check_bundle_equivocation.clone(),
+			equivocation_policy: self.equivocation_policy,
+			block_body_cache: self.block_body_cache.clone(),
+			extrinsic_timings: self.extrinsic_timings.clone(),
+			validation_cache: self.validation_cache.clone(),
+			proof_submission_enabled: self.proof_submission_enabled,
+			execution_receipt_stream: self.execution_receipt_stream.clone(),
+			fraud_proof_envelope_sender: self.fraud_proof_envelope_sender.clone(),
+			fraud_proof_envelope_stream: self.fraud_proof_envelope_stream.clone(),
+			fraud_proof_tracker: self.fraud_proof_tracker.clone(),
+			fraud_proof_event_sender: self.fraud_proof_event_sender.clone(),
+			fraud_proof_event_stream: self.fraud_proof_event_stream.clone(),
+			invalid_transaction_proof_sender: self.invalid_transaction_proof_sender.clone(),
+			invalid_transaction_proof_stream: self.invalid_transaction_proof_stream.clone(),
+			provers: self.provers.clone(),
+			future_receipt_waiters: self.future_receipt_waiters.clone(),
+			max_trace_len: self.max_trace_len,
+			proving_metrics: self.proving_metrics.clone(),
+			domain_id: self.domain_id,
+			active_leaves: self.active_leaves.clone(),
+			latest_slot: self.latest_slot.clone(),
+			slot_skew_tolerance: self.slot_skew_tolerance,
+			span: self.span.clone(),
 		}
 	}
 }
 
+// The clients, backend and keystore are deliberately left out, only a summary is printed.
+impl<Block, PBlock, Client, PClient, TransactionPool, Backend, E> fmt::Debug
+	for Executor<Block, PBlock, Client, PClient, TransactionPool, Backend, E>
+where
+	Block: BlockT,
+	PBlock: BlockT,
+{
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("Executor")
+			.field("domain_id", &self.domain_id)
+			.field("is_authority", &self.bundle_producer.is_authority())
+			.field("is_producing", &self.bundle_producer.is_producing())
+			.field("active_leaves", &self.active_leaves.len())
+			.finish_non_exhaustive()
+	}
+}
+
 type ExecutionReceiptFor<PBlock, Hash> =
 	ExecutionReceipt<NumberFor<PBlock>, <PBlock as BlockT>::Hash, Hash>;
 
 type SignedExecutionReceiptFor<PBlock, Hash> =
 	SignedExecutionReceipt<NumberFor<PBlock>, <PBlock as BlockT>::Hash, Hash>;
 
+type FraudProofEventFor<PBlock> = FraudProofEvent<NumberFor<PBlock>, <PBlock as BlockT>::Hash>;
+
 type TransactionFor<Backend, Block> =
 	<<Backend as sc_client_api::Backend<Block>>::State as sc_client_api::backend::StateBackend<
 		HashFor<Block>,
@@ -151,10 +483,16 @@ impl<Block, PBlock, Client, PClient, TransactionPool, Backend, E>
 where
 	Block: BlockT,
 	PBlock: BlockT,
-	Client:
-		HeaderBackend<Block> + BlockBackend<Block> + AuxStore + ProvideRuntimeApi<Block> + 'static,
+	Client: HeaderBackend<Block>
+		+ BlockBackend<Block>
+		+ AuxStore
+		+ ProvideRuntimeApi<Block>
+		+ BlockchainEvents<Block>
+		+ 'static,
 	Client::Api: SecondaryApi<Block, AccountId>
 		+ sp_block_builder::BlockBuilder<Block>
+		+ TaggedTransactionQueue<Block>
+		+ TransactionPaymentApi<Block, Balance>
 		+ sp_api::ApiExt<
 			Block,
 			StateBackend = sc_client_api::backend::StateBackendFor<Backend, Block>,
@@ -174,37 +512,116 @@ where
 	Backend: sc_client_api::Backend<Block> + Send + Sync + 'static,
 	TransactionFor<Backend, Block>: sp_trie::HashDBT<HashFor<Block>, sp_trie::DBValue>,
 	TransactionPool: sc_transaction_pool_api::TransactionPool<Block = Block> + 'static,
-	E: CodeExecutor,
+	E: CodeExecutor<Error = sc_executor::error::Error>,
 {
 	/// Create a new instance.
+	///
+	/// `subscribe_new_slot_notifications` is called again to re-subscribe to the slot
+	/// notifications whenever the subscribed stream ends unexpectedly.
 	#[allow(clippy::too_many_arguments)]
-	pub async fn new<SE, SC, IBNS, NSNS>(
+	pub async fn new<SE, SC, IBNS, NSNS, SubscribeNSNS>(
 		primary_chain_client: Arc<PClient>,
 		primary_network: Arc<NetworkService<PBlock, PBlock::Hash>>,
 		spawn_essential: &SE,
 		select_chain: &SC,
 		imported_block_notification_stream: IBNS,
-		new_slot_notification_stream: NSNS,
+		subscribe_new_slot_notifications: SubscribeNSNS,
 		client: Arc<Client>,
 		spawner: Box<dyn SpawnNamed + Send + Sync>,
 		transaction_pool: Arc<TransactionPool>,
 		bundle_sender: Arc<TracingUnboundedSender<SignedBundle<Block::Extrinsic>>>,
-		execution_receipt_sender: Arc<
-			TracingUnboundedSender<SignedExecutionReceiptFor<PBlock, Block::Hash>>,
-		>,
 		backend: Arc<Backend>,
 		code_executor: Arc<E>,
 		is_authority: bool,
 		keystore: SyncCryptoStorePtr,
+		prometheus_registry: Option<&Registry>,
+		config: ExecutorConfig,
 	) -> Result<Self, sp_consensus::Error>
 	where
+		PClient: BlockchainEvents<PBlock>,
 		SE: SpawnEssentialNamed,
 		SC: SelectChain<PBlock>,
 		IBNS: Stream<Item = NumberFor<PBlock>> + Send + 'static,
 		NSNS: Stream<Item = (Slot, Sha256Hash)> + Send + 'static,
+		SubscribeNSNS: FnMut() -> NSNS + Send + 'static,
 	{
+		let ExecutorConfig {
+			submission_retry,
+			queued_block_wait: _,
+			bundle_rate_limit,
+			max_received_bundle_extrinsics,
+			max_received_bundle_size,
+			rebroadcast_suppression_window,
+			receipt_poll_interval,
+			receipt_checkpoint_interval,
+			verification_sampling_rate,
+			verification_sampling_seed,
+			receipt_retention_extra,
+			block_body_prefetch,
+			warm_up_depth,
+			extrinsic_timings_capacity,
+			transaction_validation_cache_size,
+			proof_submission_enabled,
+			fraud_proof_inclusion_deadline,
+			max_concurrent_provers,
+			max_pending_future_receipts,
+			max_trace_len,
+			receipt_confirmation_depth: _,
+			bundle_slot_interval,
+			slot_skew_tolerance,
+			bundle_fee_multiplier,
+			domain_id,
+			worker_restart_policy,
+			equivocation_policy,
+		} = config;
+
+		let executor_api_version = sp_api::Core::<PBlock>::version(
+			&*primary_chain_client.runtime_api(),
+			&BlockId::Hash(primary_chain_client.info().best_hash),
+		)
+		.map_err(|error| sp_consensus::Error::Other(Box::new(error)))?
+		.api_version(&<dyn ExecutorApi<PBlock, Block::Hash>>::ID);
+		ensure_executor_api_version(executor_api_version)?;
+
+		let proving_metrics = prometheus_registry
+			.map(ProvingMetrics::register)
+			.transpose()
+			.map_err(|error| sp_consensus::Error::Other(Box::new(error)))?;
+
+		aux_schema::migrate::<_, Block, PBlock>(&*client, client.info().best_number)
+			.map_err(|error| sp_consensus::Error::Other(Box::new(error)))?;
+
+		log_receipt_gaps::<Block, _>(&*client)
+			.map_err(|error| sp_consensus::Error::Other(Box::new(error)))?;
+
 		let active_leaves = active_leaves(primary_chain_client.as_ref(), select_chain).await?;
 
+		let latest_slot = Arc::new(Mutex::new(None));
+		let new_slot_notification_stream = worker::resubscribing(
+			"new slot",
+			subscribe_new_slot_notifications,
+			(worker::RESUBSCRIPTION_BASE_DELAY, worker::RESUBSCRIPTION_MAX_DELAY),
+			worker::MAX_RESUBSCRIPTION_ATTEMPTS,
+		)
+		.inspect({
+			let latest_slot = latest_slot.clone();
+			move |(slot, _global_challenge)| *latest_slot.lock() = Some(*slot)
+		});
+
+		let (execution_receipt_sender, execution_receipt_stream) =
+			notification::channel("cirrus_execution_receipt_notification_stream");
+
+		let (fraud_proof_envelope_sender, fraud_proof_envelope_stream) =
+			notification::channel("cirrus_fraud_proof_envelope_notification_stream");
+
+		let (fraud_proof_event_sender, fraud_proof_event_stream) =
+			notification::channel("cirrus_fraud_proof_event_notification_stream");
+
+		let (invalid_transaction_proof_sender, invalid_transaction_proof_stream) =
+			notification::channel("cirrus_invalid_transaction_proof_notification_stream");
+
+		let fraud_proof_tracker =
+			Arc::new(FraudProofTracker::new(fraud_proof_inclusion_deadline.into()));
 		let bundle_producer = BundleProducer::new(
 			primary_chain_client.clone(),
 			client.clone(),
@@ -212,8 +629,21 @@ where
 			bundle_sender,
 			is_authority,
 			keystore.clone(),
+			bundle_slot_interval,
+			latest_slot.clone(),
+			slot_skew_tolerance,
+			bundle_fee_multiplier,
+			domain_id,
 		);
 
+		let is_major_syncing = {
+			let primary_network = primary_network.clone();
+			Arc::new(move || primary_network.is_major_syncing())
+		};
+
+		let extrinsic_timings = extrinsic_timings_capacity
+			.map(|capacity| Arc::new(Mutex::new(LruCache::new(capacity.max(1)))));
+
 		let bundle_processor = BundleProcessor::new(
 			primary_chain_client.clone(),
 			primary_network,
@@ -222,327 +652,1759 @@ where
 			backend.clone(),
 			is_authority,
 			keystore,
+			config,
+			extrinsic_timings.clone(),
 		);
 
-		spawn_essential.spawn_essential_blocking(
-			"executor-worker",
+		let span = executor_span(domain_id);
+
+		match worker_restart_policy {
+			WorkerRestartPolicy::Terminate => spawn_essential.spawn_essential_blocking(
+				"executor-worker",
+				None,
+				worker::start_worker(
+					primary_chain_client.clone(),
+					bundle_producer.clone(),
+					bundle_processor.clone(),
+					imported_block_notification_stream,
+					new_slot_notification_stream,
+					active_leaves.clone(),
+				)
+				.instrument(span.clone())
+				.boxed(),
+			),
+			WorkerRestartPolicy::Restart { base_delay, max_delay } => {
+				let imported_block_notification_stream =
+					worker::SharedStream::new(imported_block_notification_stream);
+				let new_slot_notification_stream =
+					worker::SharedStream::new(new_slot_notification_stream);
+				let start_worker = {
+					let primary_chain_client = primary_chain_client.clone();
+					let bundle_producer = bundle_producer.clone();
+					let bundle_processor = bundle_processor.clone();
+					let active_leaves = active_leaves.clone();
+					move || {
+						worker::start_worker(
+							primary_chain_client.clone(),
+							bundle_producer.clone(),
+							bundle_processor.clone(),
+							imported_block_notification_stream.clone(),
+							new_slot_notification_stream.clone(),
+							active_leaves.clone(),
+						)
+					}
+				};
+				spawner.spawn_blocking(
+					"executor-worker",
+					None,
+					worker::supervise(base_delay, max_delay, start_worker)
+						.instrument(span.clone())
+						.boxed(),
+				);
+			},
+		}
+
+		// Scanning the primary blocks for the submitted fraud proofs must not hold up the worker
+		// processing them, the scan runs in its own task subscribed to the primary block imports.
+		spawner.spawn_blocking(
+			"cirrus-fraud-proof-tracker",
 			None,
-			worker::start_worker(
-				primary_chain_client.clone(),
-				bundle_producer,
-				bundle_processor.clone(),
-				imported_block_notification_stream,
-				new_slot_notification_stream,
-				active_leaves,
-			)
+			{
+				let primary_chain_client = primary_chain_client.clone();
+				let fraud_proof_tracker = fraud_proof_tracker.clone();
+				let fraud_proof_event_sender = fraud_proof_event_sender.clone();
+				primary_chain_client.import_notification_stream().for_each(move |notification| {
+					let primary_number = *notification.header.number();
+					let scanned = scan_primary_block::<PBlock, _, Block::Hash>(
+						&*primary_chain_client,
+						&fraud_proof_tracker,
+						primary_number,
+					);
+					match scanned {
+						Ok(events) =>
+							for event in events {
+								fraud_proof_event_sender.notify(|| event);
+							},
+						Err(error) => tracing::error!(
+							target: LOG_TARGET,
+							?error,
+							?primary_number,
+							"Failed to scan the primary block for the submitted fraud proofs",
+						),
+					}
+					futures::future::ready(())
+				})
+			}
+			.instrument(span.clone())
 			.boxed(),
 		);
 
-		Ok(Self {
+		let executor = Self {
 			primary_chain_client,
 			client,
 			spawner,
 			transaction_pool,
 			backend,
 			code_executor,
+			bundle_producer,
 			bundle_processor,
-		})
+			submission_retry,
+			proof_submitter: Arc::new(PrimaryChainProofSubmitter::<_, Block::Hash>::new(
+				primary_chain_client.clone(),
+			)),
+			bundle_rate_limiter: Arc::new(BundleRateLimiter::new(bundle_rate_limit)),
+			max_received_bundle_extrinsics,
+			max_received_bundle_size,
+			rebroadcast_window: Arc::new(RebroadcastWindow::new(rebroadcast_suppression_window)),
+			receipt_poll_interval,
+			receipt_checkpoint_interval,
+			verification_sampling_rate,
+			verification_sampling_seed,
+			receipt_retention_extra,
+			is_major_syncing,
+			// TODO: check bundle equivocation
+			check_bundle_equivocation: Arc::new(|_bundle: &Bundle<Block::Extrinsic>| None),
+			equivocation_policy,
+			block_body_cache: block_body_prefetch
+				.map(|capacity| Arc::new(Mutex::new(LruCache::new(capacity.max(1))))),
+			extrinsic_timings,
+			validation_cache: Arc::new(ValidationCache::new(transaction_validation_cache_size)),
+			proof_submission_enabled,
+			execution_receipt_stream,
+			fraud_proof_envelope_sender,
+			fraud_proof_envelope_stream,
+			fraud_proof_tracker,
+			fraud_proof_event_sender,
+			fraud_proof_event_stream,
+			invalid_transaction_proof_sender,
+			invalid_transaction_proof_stream,
+			provers: Arc::new(Semaphore::new(max_concurrent_provers.max(1))),
+			future_receipt_waiters: FutureReceiptWaiters::new(max_pending_future_receipts),
+			max_trace_len,
+			proving_metrics,
+			domain_id,
+			active_leaves,
+			latest_slot,
+			slot_skew_tolerance,
+			span,
+		};
+
+		if let Some(depth) = warm_up_depth {
+			executor
+				.warm_up(depth)
+				.map_err(|error| sp_consensus::Error::Other(Box::new(error)))?;
+		}
+
+		Ok(executor)
+	}
+
+	/// Replaces the transport of the produced proofs, which defaults to submitting them via the
+	/// local primary client.
+	///
+	/// It must be called before the executor is cloned, e.g., for handling the gossip messages,
+	/// the existing clones keep the previous submitter.
+	pub fn with_proof_submitter(
+		mut self,
+		proof_submitter: Arc<dyn ProofSubmitter<PBlock>>,
+	) -> Self {
+		self.proof_submitter = proof_submitter;
+		self
+	}
+
+	/// Returns the primary chain leaves the executor started to track on startup.
+	pub fn active_leaves(&self) -> Vec<BlockInfo<PBlock>> {
+		self.active_leaves.clone()
+	}
+
+	/// Returns the hash of the current primary chain tip.
+	pub fn primary_best_hash(&self) -> PBlock::Hash {
+		self.primary_chain_client.info().best_hash
+	}
+
+	/// Returns the number of the current primary chain tip.
+	pub fn primary_best_number(&self) -> NumberFor<PBlock> {
+		self.primary_chain_client.info().best_number
+	}
+
+	/// Returns the domain this executor produces and processes the bundles for.
+	pub fn domain_id(&self) -> DomainId {
+		self.domain_id
+	}
+
+	/// Returns `true` if the executor produces a bundle on each new slot.
+	pub fn is_producing(&self) -> bool {
+		self.bundle_producer.is_producing()
+	}
+
+	/// Halts or resumes the bundle production from the next slot, the bundles and receipts of the
+	/// other executors are still processed while the production is halted.
+	///
+	/// Unlike `is_authority`, it can be toggled while the executor is running.
+	pub fn set_producing(&self, producing: bool) {
+		self.bundle_producer.set_producing(producing);
 	}
 
-	/// Checks the status of the given block hash in the Parachain.
+	/// Returns the extrinsics the next bundle would currently include, in order.
 	///
-	/// Returns `true` if the block could be found and is good to be build on.
-	#[allow(unused)]
-	fn check_block_status(
+	/// The extrinsics are selected from the transaction pool the same way as the bundle
+	/// production, but nothing is signed or submitted.
+	pub async fn pending_extrinsics_for_next_bundle(
 		&self,
-		hash: Block::Hash,
-		number: <Block::Header as HeaderT>::Number,
-	) -> bool {
-		match self.client.block_status(&BlockId::Hash(hash)) {
-			Ok(BlockStatus::Queued) => {
-				tracing::debug!(
-					target: LOG_TARGET,
-					block_hash = ?hash,
-					"Skipping candidate production, because block is still queued for import.",
-				);
-				false
-			},
-			Ok(BlockStatus::InChainWithState) => true,
-			Ok(BlockStatus::InChainPruned) => {
-				tracing::error!(
-					target: LOG_TARGET,
-					"Skipping candidate production, because block `{:?}` is already pruned!",
-					hash,
-				);
-				false
-			},
-			Ok(BlockStatus::KnownBad) => {
-				tracing::error!(
-					target: LOG_TARGET,
-					block_hash = ?hash,
-					"Block is tagged as known bad and is included in the relay chain! Skipping candidate production!",
-				);
-				false
-			},
-			Ok(BlockStatus::Unknown) => {
-				if number.is_zero() {
-					tracing::error!(
-						target: LOG_TARGET,
-						block_hash = ?hash,
-						"Could not find the header of the genesis block in the database!",
-					);
-				} else {
-					tracing::debug!(
-						target: LOG_TARGET,
-						block_hash = ?hash,
-						"Skipping candidate production, because block is unknown.",
-					);
-				}
-				false
-			},
-			Err(e) => {
-				tracing::error!(
-					target: LOG_TARGET,
-					block_hash = ?hash,
-					error = ?e,
-					"Failed to get block status.",
-				);
-				false
-			},
-		}
+	) -> Result<Vec<Block::Extrinsic>, sp_blockchain::Error> {
+		self.bundle_producer.select_extrinsics(self.client.info().best_number).await
 	}
 
-	fn submit_bundle_equivocation_proof(&self, bundle_equivocation_proof: BundleEquivocationProof) {
-		let primary_chain_client = self.primary_chain_client.clone();
-		// TODO: No backpressure
-		self.spawner.spawn_blocking(
-			"cirrus-submit-bundle-equivocation-proof",
-			None,
-			async move {
-				tracing::debug!(
-					target: LOG_TARGET,
-					"Submitting bundle equivocation proof in a background task..."
-				);
-				if let Err(error) =
-					primary_chain_client.runtime_api().submit_bundle_equivocation_proof_unsigned(
-						&BlockId::Hash(primary_chain_client.info().best_hash),
-						bundle_equivocation_proof,
-					) {
-					tracing::debug!(
-						target: LOG_TARGET,
-						error = ?error,
-						"Failed to submit bundle equivocation proof"
-					);
-				}
-			}
-			.boxed(),
-		);
+	/// Returns the ready transactions of the transaction pool grouped by their signer, with the
+	/// ready order kept within each group, the unsigned transactions are grouped under `None`.
+	pub fn ready_grouped_by_sender(
+		&self,
+	) -> Result<BTreeMap<Option<AccountId>, Vec<Block::Extrinsic>>, sp_blockchain::Error> {
+		self.bundle_producer.ready_grouped_by_sender()
 	}
 
-	fn submit_fraud_proof(&self, fraud_proof: FraudProof) {
-		let primary_chain_client = self.primary_chain_client.clone();
-		// TODO: No backpressure
-		self.spawner.spawn_blocking(
-			"cirrus-submit-fraud-proof",
-			None,
-			async move {
-				tracing::debug!(
-					target: LOG_TARGET,
-					"Submitting fraud proof in a background task..."
-				);
-				if let Err(error) = primary_chain_client.runtime_api().submit_fraud_proof_unsigned(
-					&BlockId::Hash(primary_chain_client.info().best_hash),
-					fraud_proof,
-				) {
-					tracing::debug!(
-						target: LOG_TARGET,
-						error = ?error,
-						"Failed to submit fraud proof"
-					);
-				}
-			}
-			.boxed(),
-		);
+	/// Returns the stream of the execution receipts produced locally.
+	pub fn execution_receipt_stream(
+		&self,
+	) -> ExecutorNotificationStream<SignedExecutionReceiptFor<PBlock, Block::Hash>> {
+		self.execution_receipt_stream.clone()
+	}
+
+	/// Returns the stream of the fraud proofs submitted by this executor, each encoded as a
+	/// versioned envelope for the external verifiers, see [`FraudProof::to_envelope`].
+	///
+	/// The envelope is only encoded when there is a subscriber, even if the proof submission is
+	/// disabled.
+	pub fn fraud_proof_envelope_stream(&self) -> ExecutorNotificationStream<Vec<u8>> {
+		self.fraud_proof_envelope_stream.clone()
+	}
+
+	/// Returns the stream of the outcomes of the fraud proofs submitted by this executor, a fraud
+	/// proof is confirmed once it's included in an imported primary block.
+	pub fn fraud_proof_event_stream(
+		&self,
+	) -> ExecutorNotificationStream<FraudProofEventFor<PBlock>> {
+		self.fraud_proof_event_stream.clone()
+	}
+
+	/// Returns the stream of the invalid transaction proofs of the bundle extrinsics, each proof
+	/// is sent right before it's submitted.
+	///
+	/// The proofs are sent even if the proof submission is disabled.
+	pub fn invalid_transaction_proof_stream(
+		&self,
+	) -> ExecutorNotificationStream<InvalidTransactionProof> {
+		self.invalid_transaction_proof_stream.clone()
+	}
+
+	/// Returns `true` if the receipt of the secondary block `block_hash` belongs to the current
+	/// canonical secondary chain, `false` for the blocks orphaned by a reorg whose receipts may
+	/// still linger in the aux store.
+	pub fn is_receipt_canonical(
+		&self,
+		block_hash: Block::Hash,
+	) -> Result<bool, sp_blockchain::Error> {
+		is_block_canonical(&*self.client, block_hash)
+	}
+
+	/// Returns the local execution receipt of the secondary block derived from the canonical
+	/// primary block at `primary_number`, `None` if the receipt is not available, e.g., pruned.
+	///
+	/// The primary block is resolved on each call, so the receipt follows the primary reorgs.
+	pub fn receipt_at(
+		&self,
+		primary_number: NumberFor<PBlock>,
+	) -> Result<Option<ExecutionReceiptFor<PBlock, Block::Hash>>, GossipMessageError> {
+		let primary_hash = match self.primary_chain_client.hash(primary_number)? {
+			Some(primary_hash) => primary_hash,
+			None => return Ok(None),
+		};
+		let block_number = to_secondary_number::<NumberFor<Block>>(primary_number)?;
+
+		aux_schema::load_execution_receipt_by_primary_hash::<_, Block, PBlock>(
+			&*self.client,
+			block_number,
+			primary_hash,
+		)
+		.map_err(|error| GossipMessageError::ReceiptLoad(Box::new(error)))
+	}
+
+	/// Returns the hash of the secondary block whose local execution receipt hashes to
+	/// `receipt_hash`, `None` if no such receipt is kept locally.
+	///
+	/// Useful for resolving a dispute when only the hash of a gossiped receipt is at hand.
+	pub fn block_hash_by_receipt_hash(
+		&self,
+		receipt_hash: H256,
+	) -> Result<Option<Block::Hash>, sp_blockchain::Error> {
+		aux_schema::load_block_hash_by_receipt_hash(&*self.client, receipt_hash)
 	}
 
-	fn submit_invalid_transaction_proof(&self, invalid_transaction_proof: InvalidTransactionProof) {
-		let primary_chain_client = self.primary_chain_client.clone();
+	/// Returns the minimum fee a transaction has to pay to be included in a bundle at the best
+	/// block, `None` if the runtime doesn't provide it, in which case no minimum is applied.
+	pub fn minimum_bundle_tx_fee(&self) -> Result<Option<Balance>, sp_api::ApiError> {
+		bundle_producer::minimum_bundle_tx_fee_at(
+			&*self.client.runtime_api(),
+			&BlockId::Hash(self.client.info().best_hash),
+		)
+	}
+
+	/// Submits `proof` to the primary chain in a background task using `submit`, which is retried
+	/// according to the configured [`SubmissionRetry`] policy.
+	///
+	/// The proof is submitted at the primary block `at` if specified, otherwise at the primary best
+	/// block of each attempt.
+	///
+	/// The proof is only logged if the proof submission is disabled, `on_failure` is called if the
+	/// submission fails eventually.
+	fn submit_proof_in_background<P, F, OnFailure>(
+		&self,
+		task_name: &'static str,
+		proof_kind: &'static str,
+		at: Option<BlockId<PBlock>>,
+		proof: P,
+		submit: F,
+		on_failure: OnFailure,
+	) where
+		P: Clone + fmt::Debug + Send + Sync + 'static,
+		F: Fn(&dyn ProofSubmitter<PBlock>, &BlockId<PBlock>, P) -> Result<(), sp_api::ApiError>
+			+ Send
+			+ Sync
+			+ 'static,
+		OnFailure: FnOnce() + Send + 'static,
+	{
+		let executor = self.clone();
 		// TODO: No backpressure
 		self.spawner.spawn_blocking(
-			"cirrus-submit-invalid-transaction-proof",
+			task_name,
 			None,
 			async move {
 				tracing::debug!(
 					target: LOG_TARGET,
-					"Submitting invalid transaction proof in a background task..."
+					"Submitting {proof_kind} in a background task..."
 				);
-				if let Err(error) =
-					primary_chain_client.runtime_api().submit_invalid_transaction_proof_unsigned(
-						&BlockId::Hash(primary_chain_client.info().best_hash),
-						invalid_transaction_proof,
-					) {
-					tracing::debug!(
-						target: LOG_TARGET,
-						error = ?error,
-						"Failed to submit invalid transaction proof"
-					);
+				match executor
+					.submit_proof(at, |proof_submitter, at| {
+						submit(proof_submitter, at, proof.clone())
+					})
+					.await
+				{
+					Ok(()) => {},
+					Err(SubmitError::Disabled) => log_unsubmitted_proof(proof_kind, &proof),
+					Err(error) => {
+						tracing::debug!(
+							target: LOG_TARGET,
+							error = ?error,
+							"Failed to submit {proof_kind}"
+						);
+						on_failure();
+					},
 				}
 			}
+			.instrument(self.span.clone())
 			.boxed(),
 		);
 	}
 
-	fn header(&self, at: Block::Hash) -> Result<Block::Header, sp_blockchain::Error> {
-		self.client
-			.header(BlockId::Hash(at))?
-			.ok_or_else(|| sp_blockchain::Error::Backend(format!("Header not found for {:?}", at)))
-	}
+	/// Submits a proof to the primary chain using `submit` and waits for the result, the
+	/// submission is retried according to the configured [`SubmissionRetry`] policy.
+	///
+	/// The proof is submitted at the primary block `at` if specified, otherwise at the primary best
+	/// block of each attempt.
+	async fn submit_proof<F>(
+		&self,
+		at: Option<BlockId<PBlock>>,
+		submit: F,
+	) -> Result<(), SubmitError>
+	where
+		F: Fn(&dyn ProofSubmitter<PBlock>, &BlockId<PBlock>) -> Result<(), sp_api::ApiError>,
+	{
+		if !self.proof_submission_enabled {
+			return Err(SubmitError::Disabled)
+		}
 
-	fn block_body(&self, at: Block::Hash) -> Result<Vec<Block::Extrinsic>, sp_blockchain::Error> {
-		self.client.block_body(&BlockId::Hash(at))?.ok_or_else(|| {
-			sp_blockchain::Error::Backend(format!("Block body not found for {:?}", at))
+		proof_submission::submit_with_retry(self.submission_retry, || {
+			submit(
+				&*self.proof_submitter,
+				&proof_submission::submission_at(at, || self.primary_best_hash()),
+			)
 		})
+		.await
+		.map_err(SubmitError::RuntimeApi)
 	}
 
-	fn create_extrinsic_execution_proof(
+	/// Submits the fraud proof to the primary chain and waits for the result.
+	///
+	/// Unlike the fraud proofs of the mismatched execution receipts from the network, whose
+	/// submission failures are only logged, the submission failure is returned to the caller.
+	pub async fn submit_fraud_proof_awaitable(
 		&self,
-		extrinsic_index: usize,
-		parent_header: &Block::Header,
-		current_hash: Block::Hash,
-		prover: &subspace_fraud_proof::ExecutionProver<Block, Backend, E>,
-	) -> Result<(StorageProof, ExecutionPhase), GossipMessageError> {
-		let extrinsics = self.block_body(current_hash)?;
+		fraud_proof: FraudProof,
+		at: Option<BlockId<PBlock>>,
+	) -> Result<(), SubmitError> {
+		self.fraud_proof_envelope_sender.notify(|| fraud_proof.to_envelope());
+		self.track_fraud_proof(&fraud_proof);
+
+		let result = self
+			.submit_proof(at, |proof_submitter, at| {
+				proof_submitter.submit_fraud_proof(at, fraud_proof.clone())
+			})
+			.await;
 
-		let encoded_extrinsic = extrinsics
-			.get(extrinsic_index)
-			.ok_or(GossipMessageError::InvalidExtrinsicIndex {
-				index: extrinsic_index,
-				max: extrinsics.len() - 1,
-			})?
-			.encode();
+		match &result {
+			Ok(()) => {},
+			// Not a submission failure, the fraud proof has never been tracked.
+			Err(SubmitError::Disabled) => log_unsubmitted_proof("fraud proof", &fraud_proof),
+			Err(SubmitError::RuntimeApi(_)) => self.untrack_failed_fraud_proof(&fraud_proof),
+		}
 
-		let execution_phase = ExecutionPhase::ApplyExtrinsic { call_data: encoded_extrinsic };
+		result
+	}
 
-		let block_builder = BlockBuilder::new(
-			&*self.client,
-			parent_header.hash(),
-			*parent_header.number(),
-			RecordProof::No,
-			Default::default(),
-			&*self.backend,
-			extrinsics,
-		)?;
-		let storage_changes = block_builder.prepare_storage_changes_before(extrinsic_index)?;
+	/// Submits `signed_bundle` to the primary chain and returns the hash of the extrinsic
+	/// including it, which can be used to track its inclusion in the primary chain.
+	///
+	/// Unlike the proofs, the bundle is submitted even if the proof submission is disabled.
+	pub async fn submit_bundle(
+		&self,
+		signed_bundle: SignedOpaqueBundle,
+	) -> Result<PBlock::Hash, SubmitError> {
+		let at = BlockId::Hash(self.primary_best_hash());
 
-		let delta = storage_changes.transaction;
-		let post_delta_root = storage_changes.transaction_storage_root;
-		let execution_proof = prover.prove_execution(
-			BlockId::Hash(parent_header.hash()),
-			&execution_phase,
-			Some((delta, post_delta_root)),
-		)?;
+		let extrinsic = self
+			.primary_chain_client
+			.runtime_api()
+			.transaction_bundle_extrinsic(&at, signed_bundle.clone())
+			.map_err(SubmitError::RuntimeApi)?;
 
-		Ok((execution_proof, execution_phase))
+		proof_submission::submit_with_retry(self.submission_retry, || {
+			self.primary_chain_client
+				.runtime_api()
+				.submit_transaction_bundle_unsigned(&at, signed_bundle.clone())
+		})
+		.await
+		.map_err(SubmitError::RuntimeApi)?;
+
+		Ok(HashFor::<PBlock>::hash_of(&extrinsic))
 	}
 
-	/// The background is that a receipt received from the network points to a future block
-	/// from the local view, so we need to wait for the receipt for the block at the same
-	/// height to be produced locally in order to check the validity of the external receipt.
-	async fn wait_for_local_future_receipt(
-		&self,
-		secondary_block_hash: Block::Hash,
-		secondary_block_number: <Block::Header as HeaderT>::Number,
-		tx: crossbeam::channel::Sender<
-			sp_blockchain::Result<ExecutionReceiptFor<PBlock, Block::Hash>>,
-		>,
-	) -> Result<(), GossipMessageError> {
-		loop {
-			match aux_schema::load_execution_receipt(&*self.client, secondary_block_hash) {
-				Ok(Some(local_receipt)) =>
-					return tx.send(Ok(local_receipt)).map_err(|_| GossipMessageError::SendError),
-				Ok(None) => {
-					// TODO: test how this works under the primary forks.
-					//       ref https://github.com/subspace/subspace/pull/250#discussion_r804247551
-					//
-					// Whether or not the best execution chain number on primary chain has been
-					// updated, the local client has proceeded to a higher block, that means the receipt
-					// of `block_hash` received from the network does not match the local one,
-					// we should just send back the local receipt at the same height.
-					if self.client.info().best_number >= secondary_block_number {
-						let local_block_hash = self
-							.client
-							.expect_block_hash_from_id(&BlockId::Number(secondary_block_number))?;
-						let local_receipt_result =
-							aux_schema::load_execution_receipt(&*self.client, local_block_hash)?
-								.ok_or_else(|| {
-									sp_blockchain::Error::Backend(format!(
-										"Execution receipt not found for {:?}",
-										local_block_hash
-									))
-								});
-						return tx
-							.send(local_receipt_result)
-							.map_err(|_| GossipMessageError::SendError)
-					} else {
-						tokio::time::sleep(std::time::Duration::from_millis(100)).await;
-					}
-				},
-				Err(e) => return tx.send(Err(e)).map_err(|_| GossipMessageError::SendError),
-			}
+	/// Starts tracking the inclusion of `fraud_proof` in the primary chain if it's going to be
+	/// submitted.
+	fn track_fraud_proof(&self, fraud_proof: &FraudProof) {
+		if self.proof_submission_enabled {
+			self.fraud_proof_tracker.track(fraud_proof.clone(), self.primary_best_number());
 		}
 	}
 
-	/// Processes the bundles extracted from the primary block.
-	// TODO: Remove this whole method, `self.bundle_processor` as a property and fix
-	// `set_new_code_should_work` test to do an actual runtime upgrade
-	#[doc(hidden)]
-	pub async fn process_bundles(
-		self,
-		primary_info: (PBlock::Hash, NumberFor<PBlock>),
-		bundles: Vec<OpaqueBundle>,
-		shuffling_seed: Randomness,
-		maybe_new_runtime: Option<Cow<'static, [u8]>>,
-	) {
-		if let Err(err) = self
-			.bundle_processor
-			.process_bundles(primary_info, bundles, shuffling_seed, maybe_new_runtime)
-			.await
-		{
-			tracing::error!(
-				target: LOG_TARGET,
-				?primary_info,
-				error = ?err,
-				"Error at processing bundles.",
-			);
+	/// Stops tracking `fraud_proof` whose submission failed and reports it as rejected.
+	fn untrack_failed_fraud_proof(&self, fraud_proof: &FraudProof) {
+		if let Some(event) = self.fraud_proof_tracker.on_submission_failed(fraud_proof) {
+			self.fraud_proof_event_sender.notify(|| event);
 		}
 	}
-}
 
-/// Error type for cirrus gossip handling.
-#[derive(Debug, thiserror::Error)]
-pub enum GossipMessageError {
+	fn submit_bundle_equivocation_proof(
+		&self,
+		bundle_equivocation_proof: BundleEquivocationProof,
+		at: Option<BlockId<PBlock>>,
+	) {
+		self.submit_proof_in_background(
+			"cirrus-submit-bundle-equivocation-proof",
+			"bundle equivocation proof",
+			at,
+			bundle_equivocation_proof,
+			|proof_submitter, at, bundle_equivocation_proof| {
+				proof_submitter.submit_bundle_equivocation_proof(at, bundle_equivocation_proof)
+			},
+			|| (),
+		);
+	}
+
+	fn submit_invalid_transaction_proof(
+		&self,
+		invalid_transaction_proof: InvalidTransactionProof,
+		at: Option<BlockId<PBlock>>,
+	) {
+		self.invalid_transaction_proof_sender
+			.notify(|| invalid_transaction_proof.clone());
+
+		self.submit_proof_in_background(
+			"cirrus-submit-invalid-transaction-proof",
+			"invalid transaction proof",
+			at,
+			invalid_transaction_proof,
+			|proof_submitter, at, invalid_transaction_proof| {
+				proof_submitter.submit_invalid_transaction_proof(at, invalid_transaction_proof)
+			},
+			|| (),
+		);
+	}
+
+	/// Returns the hash of the secondary block derived from the primary block `primary_hash`,
+	/// falling back to the secondary best block if it hasn't been processed locally yet.
+	fn secondary_hash_for(
+		&self,
+		primary_hash: PBlock::Hash,
+	) -> Result<Block::Hash, GossipMessageError> {
+		let maybe_secondary_hash = match self.primary_chain_client.number(primary_hash)? {
+			Some(primary_number) =>
+				self.client.hash(to_secondary_number::<NumberFor<Block>>(primary_number)?)?,
+			None => None,
+		};
+		Ok(maybe_secondary_hash.unwrap_or_else(|| self.client.info().best_hash))
+	}
+
+	/// Checks the legality of the extrinsic at `extrinsic_index` in the bundle `bundle_hash`
+	/// against the secondary state referenced by `primary_hash`.
+	///
+	/// Returns an [`InvalidTransactionProof`] if the extrinsic is invalid.
+	fn check_bundle_extrinsic(
+		&self,
+		primary_hash: PBlock::Hash,
+		bundle_hash: H256,
+		extrinsic_index: usize,
+		extrinsic: &Block::Extrinsic,
+	) -> Result<Option<InvalidTransactionProof>, GossipMessageError> {
+		let at = self.secondary_hash_for(primary_hash)?;
+
+		let validation_result = self.validation_cache.get_or_validate(
+			self.client.info().best_hash,
+			at,
+			BlakeTwo256::hash_of(extrinsic),
+			|| {
+				self.client.runtime_api().validate_transaction(
+					&BlockId::Hash(at),
+					TransactionSource::External,
+					extrinsic.clone(),
+					at,
+				)
+			},
+		)?;
+
+		match validation_result {
+			Ok(_) => Ok(None),
+			Err(error) => {
+				tracing::debug!(
+					target: LOG_TARGET,
+					?bundle_hash,
+					extrinsic_index,
+					?error,
+					"Illegal transaction in bundle",
+				);
+				Ok(Some(InvalidTransactionProof {
+					bundle_hash,
+					extrinsic_index: extrinsic_index as u32,
+					extrinsic: extrinsic.encode(),
+				}))
+			},
+		}
+	}
+
+	fn header(&self, at: Block::Hash) -> Result<Block::Header, GossipMessageError> {
+		self.client
+			.header(BlockId::Hash(at))
+			.and_then(|maybe_header| {
+				maybe_header.ok_or_else(|| {
+					sp_blockchain::Error::Backend(format!("Header not found for {:?}", at))
+				})
+			})
+			.map_err(|error| GossipMessageError::HeaderLookup(Box::new(error)))
+	}
+
+	fn block_body(&self, at: Block::Hash) -> Result<Vec<Block::Extrinsic>, GossipMessageError> {
+		if let Some(block_body_cache) = &self.block_body_cache {
+			if let Some(extrinsics) = block_body_cache.lock().get(&at) {
+				return Ok(extrinsics.clone())
+			}
+		}
+
+		self.client
+			.block_body(&BlockId::Hash(at))
+			.and_then(|maybe_body| {
+				maybe_body.ok_or_else(|| {
+					sp_blockchain::Error::Backend(format!("Block body not found for {:?}", at))
+				})
+			})
+			.map_err(|error| GossipMessageError::BlockBodyMissing(Box::new(error)))
+	}
+
+	/// Recovers the bundles included in the primary block `primary_hash` from its block body.
+	///
+	/// The primary block is the canonical source of the bundles a secondary block is built from,
+	/// which are available even if they were never received locally via gossip.
+	pub fn recover_bundles(
+		&self,
+		primary_hash: PBlock::Hash,
+	) -> Result<Vec<OpaqueBundle>, GossipMessageError> {
+		let extrinsics = self
+			.primary_chain_client
+			.block_body(&BlockId::Hash(primary_hash))
+			.and_then(|maybe_body| {
+				maybe_body.ok_or_else(|| {
+					sp_blockchain::Error::Backend(format!(
+						"Primary block body not found for {:?}",
+						primary_hash
+					))
+				})
+			})
+			.map_err(|error| GossipMessageError::BlockBodyMissing(Box::new(error)))?;
+
+		Ok(extract_bundles::<PBlock, _, Block::Hash>(
+			&*self.primary_chain_client,
+			primary_hash,
+			extrinsics,
+		)?)
+	}
+
+	/// Rebuilds the secondary block on top of `parent_hash` from the bundles recovered from the
+	/// primary block `primary_hash`, returns the execution trace of the rebuilt block.
+	///
+	/// The rebuilt block is not imported.
+	fn rebuild_trace(
+		&self,
+		parent_hash: Block::Hash,
+		primary_hash: PBlock::Hash,
+	) -> Result<Vec<Block::Hash>, GossipMessageError> {
+		let bundles = self.recover_bundles(primary_hash)?;
+
+		let primary_header = self
+			.primary_chain_client
+			.header(BlockId::Hash(primary_hash))
+			.and_then(|maybe_header| {
+				maybe_header.ok_or_else(|| {
+					sp_blockchain::Error::Backend(format!(
+						"Primary header not found for {:?}",
+						primary_hash
+					))
+				})
+			})
+			.map_err(|error| GossipMessageError::HeaderLookup(Box::new(error)))?;
+		let (shuffling_seed, maybe_new_runtime) = worker::secondary_block_inputs::<
+			PBlock,
+			_,
+			Block::Hash,
+		>(&*self.primary_chain_client, primary_header)?;
+
+		let (_block, trace) = build_secondary_block(
+			&*self.client,
+			&*self.backend,
+			parent_hash,
+			bundle_processor::bundles_of_domain(bundles, self.domain_id),
+			shuffling_seed,
+			maybe_new_runtime,
+		)?;
+
+		Ok(trace)
+	}
+
+	/// Loads the bodies of the blocks with a local receipt among the `depth` most recent secondary
+	/// blocks into the block body cache, which is empty after a restart.
+	fn warm_up(&self, depth: u32) -> Result<(), sp_blockchain::Error> {
+		if self.block_body_cache.is_none() {
+			return Ok(())
+		}
+
+		let best_number = self.client.info().best_number;
+		let mut warmed_up = 0;
+		let mut block_number = best_number;
+		for _ in 0..depth {
+			if let Some(block_hash) = self.client.hash(block_number)? {
+				if aux_schema::load_execution_receipt::<_, _, NumberFor<PBlock>, PBlock::Hash>(
+					&*self.client,
+					block_hash,
+				)?
+				.is_some()
+				{
+					self.prefetch_block_body(block_hash);
+					warmed_up += 1;
+				}
+			}
+
+			if block_number.is_zero() {
+				break
+			}
+			block_number -= One::one();
+		}
+
+		tracing::debug!(target: LOG_TARGET, ?best_number, warmed_up, "Warmed up the recent receipts");
+
+		Ok(())
+	}
+
+	/// Returns the wall-clock time of applying each extrinsic of the block `block_hash` built
+	/// locally, in the order of the block extrinsics.
+	///
+	/// `None` if the timing is disabled or the block is not among the recently built ones.
+	pub fn extrinsic_timings(&self, block_hash: Block::Hash) -> Option<Vec<Duration>> {
+		self.extrinsic_timings.as_ref()?.lock().get(&block_hash).cloned()
+	}
+
+	/// Loads the body of block `at` into the block body cache if the prefetch is enabled.
+	fn prefetch_block_body(&self, at: Block::Hash) {
+		let block_body_cache = match &self.block_body_cache {
+			Some(block_body_cache) => block_body_cache,
+			None => return,
+		};
+
+		if block_body_cache.lock().contains(&at) {
+			return
+		}
+
+		// The body is loaded again on the proof path on failure.
+		match self.client.block_body(&BlockId::Hash(at)) {
+			Ok(Some(extrinsics)) => {
+				block_body_cache.lock().put(at, extrinsics);
+			},
+			Ok(None) => {},
+			Err(err) => {
+				tracing::debug!(target: LOG_TARGET, ?err, block_hash = ?at, "Failed to prefetch the block body");
+			},
+		}
+	}
+
+	/// Returns `true` if the extrinsic at `extrinsic_index` of block `block_hash` exists and
+	/// hence its execution proof can be built.
+	pub fn can_prove_extrinsic(
+		&self,
+		block_hash: Block::Hash,
+		extrinsic_index: usize,
+	) -> Result<bool, GossipMessageError> {
+		let extrinsics = self.block_body(block_hash)?;
+		Ok(extrinsic_at(&extrinsics, extrinsic_index).is_ok())
+	}
+
+	/// Proves `execution_phase` using `prover`, timing the proving per execution phase.
+	fn prove_phase(
+		&self,
+		prover: &impl Prover<Block, TransactionFor<Backend, Block>>,
+		at: BlockId<Block>,
+		execution_phase: &ExecutionPhase,
+		delta_changes: Option<(TransactionFor<Backend, Block>, Block::Hash)>,
+	) -> Result<StorageProof, ProvingError> {
+		let _span = tracing::debug_span!(
+			target: LOG_TARGET,
+			"prove_execution",
+			phase = metrics::phase_label(execution_phase),
+		)
+		.entered();
+
+		let started_at = std::time::Instant::now();
+		let result = prover.prove_execution(at, execution_phase, delta_changes);
+		if let Some(proving_metrics) = &self.proving_metrics {
+			proving_metrics.observe(execution_phase, started_at.elapsed());
+		}
+
+		result
+	}
+
+	fn create_extrinsic_execution_proof(
+		&self,
+		extrinsic_index: usize,
+		parent_header: &Block::Header,
+		current_hash: Block::Hash,
+		prover: &impl Prover<Block, TransactionFor<Backend, Block>>,
+	) -> Result<(StorageProof, ExecutionPhase), GossipMessageError> {
+		let extrinsics = self.block_body(current_hash)?;
+
+		let encoded_extrinsic = extrinsic_at(&extrinsics, extrinsic_index)?.encode();
+
+		let execution_phase = ExecutionPhase::ApplyExtrinsic { call_data: encoded_extrinsic };
+
+		let block_builder = BlockBuilder::new(
+			&*self.client,
+			parent_header.hash(),
+			*parent_header.number(),
+			RecordProof::No,
+			Default::default(),
+			&*self.backend,
+			extrinsics,
+		)?;
+		let storage_changes = block_builder.prepare_storage_changes_before(extrinsic_index)?;
+
+		let delta = storage_changes.transaction;
+		let post_delta_root = storage_changes.transaction_storage_root;
+		let execution_proof = self
+			.prove_phase(
+				prover,
+				BlockId::Hash(parent_header.hash()),
+				&execution_phase,
+				Some((delta, post_delta_root)),
+			)
+			.map_err(|error| match error {
+				ProvingError::ExecutionTrap(error) =>
+					GossipMessageError::ExecutionTrap { extrinsic_index, error: Box::new(error) },
+				ProvingError::Client(error) => error.into(),
+			})?;
+
+		Ok((execution_proof, execution_phase))
+	}
+
+	/// Creates the invalid transaction proof of the extrinsic at `extrinsic_index` of the block
+	/// `execution_receipt` is built for, which traps during the execution.
+	///
+	/// Returns `None` if the extrinsic is not found in the bundles of the primary block, e.g.,
+	/// it's not from a bundle.
+	fn create_trapped_extrinsic_proof(
+		&self,
+		execution_receipt: &ExecutionReceiptFor<PBlock, Block::Hash>,
+		extrinsic_index: usize,
+	) -> Result<Option<InvalidTransactionProof>, GossipMessageError> {
+		let extrinsics = self.block_body(execution_receipt.secondary_hash)?;
+		let extrinsic = extrinsic_at(&extrinsics, extrinsic_index)?.encode();
+
+		let bundles = self.recover_bundles(execution_receipt.primary_hash)?;
+
+		Ok(locate_in_bundles(&bundles, &extrinsic).map(|(bundle_hash, extrinsic_index)| {
+			InvalidTransactionProof { bundle_hash, extrinsic_index, extrinsic }
+		}))
+	}
+
+	/// Creates the fraud proof for the first mismatched root `local_trace_idx` between the local
+	/// receipt and the external receipt.
+	fn create_fraud_proof(
+		&self,
+		local_trace_idx: usize,
+		local_receipt: &ExecutionReceiptFor<PBlock, Block::Hash>,
+		execution_receipt: &ExecutionReceiptFor<PBlock, Block::Hash>,
+	) -> Result<FraudProof, GossipMessageError> {
+		if local_receipt.trace.is_empty() {
+			return Err(GossipMessageError::EmptyTrace)
+		}
+
+		let local_root = local_receipt.trace[local_trace_idx];
+
+		let header = self.header(execution_receipt.secondary_hash)?;
+		let parent_header = self.header(*header.parent_hash())?;
+
+		let phase_kind = fraud_proof_phase(local_trace_idx, local_receipt.trace.len());
+		let pre_state_root = match phase_kind {
+			FraudProofPhase::InitializeBlock => *parent_header.state_root(),
+			FraudProofPhase::ApplyExtrinsic(_) | FraudProofPhase::FinalizeBlock =>
+				execution_receipt.trace[local_trace_idx - 1],
+		};
+
+		log_trace_divergence(execution_receipt, local_trace_idx, phase_kind, &local_root);
+
+		self.build_fraud_proof(
+			&parent_header,
+			execution_receipt.secondary_hash,
+			phase_kind,
+			(pre_state_root, local_root),
+		)
+	}
+
+	/// Builds the fraud proof of executing `phase_kind` of block `block_hash` on top of
+	/// `parent_header`, claiming the state transition `(pre_state_root, post_state_root)`.
+	fn build_fraud_proof(
+		&self,
+		parent_header: &Block::Header,
+		block_hash: Block::Hash,
+		phase_kind: FraudProofPhase,
+		state_transition: (Block::Hash, Block::Hash),
+	) -> Result<FraudProof, GossipMessageError> {
+		let prover = subspace_fraud_proof::ExecutionProver::new(
+			self.backend.clone(),
+			self.code_executor.clone(),
+			self.spawner.clone() as Box<dyn SpawnNamed>,
+		);
+
+		self.build_fraud_proof_with(
+			&prover,
+			parent_header,
+			block_hash,
+			phase_kind,
+			state_transition,
+		)
+	}
+
+	/// Builds the fraud proof like [`Self::build_fraud_proof`], with the storage proofs created
+	/// by `prover`.
+	fn build_fraud_proof_with(
+		&self,
+		prover: &impl Prover<Block, TransactionFor<Backend, Block>>,
+		parent_header: &Block::Header,
+		block_hash: Block::Hash,
+		phase_kind: FraudProofPhase,
+		(pre_state_root, post_state_root): (Block::Hash, Block::Hash),
+	) -> Result<FraudProof, GossipMessageError> {
+		// The proof is constructed on top of the parent state, check it up front instead of
+		// failing deep in the block builder.
+		ensure_state_available(self.client.block_status(&BlockId::Hash(parent_header.hash())))?;
+
+		let parent_number = to_block_number(*parent_header.number())?;
+
+		let (proof, execution_phase) = match phase_kind {
+			FraudProofPhase::InitializeBlock => {
+				let new_header = Block::Header::new(
+					*parent_header.number() + One::one(),
+					Default::default(),
+					Default::default(),
+					parent_header.hash(),
+					Default::default(),
+				);
+				let execution_phase =
+					ExecutionPhase::InitializeBlock { call_data: new_header.encode() };
+
+				let proof = self
+					.prove_phase(prover, BlockId::Hash(parent_header.hash()), &execution_phase, None)
+					.map_err(ProvingError::into_client_error)?;
+
+				(proof, execution_phase)
+			},
+			FraudProofPhase::ApplyExtrinsic(extrinsic_index) => self
+				.create_extrinsic_execution_proof(
+					extrinsic_index,
+					parent_header,
+					block_hash,
+					prover,
+				)?,
+			FraudProofPhase::FinalizeBlock => {
+				let execution_phase = ExecutionPhase::FinalizeBlock;
+
+				let block_builder = BlockBuilder::new(
+					&*self.client,
+					parent_header.hash(),
+					*parent_header.number(),
+					RecordProof::No,
+					Default::default(),
+					&*self.backend,
+					self.block_body(block_hash)?,
+				)?;
+				let storage_changes =
+					block_builder.prepare_storage_changes_before_finalize_block()?;
+
+				let delta = storage_changes.transaction;
+				let post_delta_root = storage_changes.transaction_storage_root;
+
+				let proof = self
+					.prove_phase(
+						prover,
+						BlockId::Hash(parent_header.hash()),
+						&execution_phase,
+						Some((delta, post_delta_root)),
+					)
+					.map_err(ProvingError::into_client_error)?;
+
+				(proof, execution_phase)
+			},
+		};
+
+		// TODO: proof should be a CompactProof.
+		Ok(FraudProof {
+			parent_number,
+			parent_hash: as_h256(&parent_header.hash())?,
+			pre_state_root: as_h256(&pre_state_root)?,
+			post_state_root: as_h256(&post_state_root)?,
+			proof,
+			execution_phase,
+		})
+	}
+
+	/// Compares the execution receipt received from the network with the local one.
+	fn compare_execution_receipt(
+		&self,
+		signed_execution_receipt: &SignedExecutionReceiptFor<PBlock, Block::Hash>,
+	) -> Result<ReceiptComparison<ExecutionReceiptFor<PBlock, Block::Hash>>, GossipMessageError> {
+		let receipt_hash = Self::verify_receipt_signature(signed_execution_receipt)?;
+
+		let SignedExecutionReceipt { execution_receipt, signer, .. } = signed_execution_receipt;
+
+		// A receipt always contains at least the initial and final state roots.
+		if execution_receipt.trace.is_empty() {
+			return Err(GossipMessageError::EmptyTrace)
+		}
+
+		ensure_trace_len(execution_receipt.trace.len(), self.max_trace_len)?;
+
+		// Any root may end up in a fraud proof, reject the structurally invalid receipt before
+		// the comparison instead of only when the malformed root happens to diverge.
+		ensure_h256_trace(&execution_receipt.trace)?;
+
+		let expected_executor_id = configured_executor_id(
+			self.primary_chain_client
+				.runtime_api()
+				.executor_id(&BlockId::Hash(execution_receipt.primary_hash))?,
+		)
+		.ok_or(GossipMessageError::NoExecutorConfigured)?;
+		if *signer != expected_executor_id {
+			// TODO: handle the misbehavior.
+
+			return Err(GossipMessageError::InvalidExecutionReceiptAuthor {
+				got: signer.clone(),
+				expected: expected_executor_id,
+			})
+		}
+
+		let primary_number = execution_receipt.primary_number;
+
+		// The prune check below relies on the primary number, which must refer to the same
+		// primary block as the primary hash.
+		let local_primary_number =
+			self.primary_chain_client.number(execution_receipt.primary_hash)?;
+		if local_primary_number != Some(primary_number) {
+			return Err(GossipMessageError::InconsistentPrimaryReference {
+				stated: primary_number.saturated_into(),
+				local: local_primary_number.map(|number| number.saturated_into()),
+			})
+		}
+
+		let best_execution_chain_number = self
+			.primary_chain_client
+			.runtime_api()
+			.best_execution_chain_number(&BlockId::Hash(self.primary_best_hash()))?;
+
+		// Just ignore it if the receipt is too old and has been pruned.
+		if aux_schema::target_receipt_is_pruned(
+			best_execution_chain_number.saturated_into(),
+			primary_number.saturated_into(),
+			self.receipt_retention_extra,
+		) {
+			return Ok(ReceiptComparison::Skipped)
+		}
+
+		let block_hash = execution_receipt.secondary_hash;
+		let block_number = to_secondary_number::<NumberFor<Block>>(primary_number)?;
+
+		if !is_sampled_for_verification(
+			self.verification_sampling_seed,
+			primary_number.saturated_into(),
+			self.verification_sampling_rate,
+		) {
+			tracing::trace!(
+				target: LOG_TARGET,
+				?block_number,
+				"Rebroadcasting the receipt without the comparison as the block is not sampled",
+			);
+			return Ok(ReceiptComparison::Matched)
+		}
+
+		// TODO: more efficient execution receipt checking strategy?
+		let maybe_local_receipt =
+			match aux_schema::load_execution_receipt(&*self.client, block_hash)
+				.map_err(|error| GossipMessageError::ReceiptLoad(Box::new(error)))?
+			{
+				Some(local_receipt) => Some(Ok(local_receipt)),
+				None => poll_local_future_receipt::<Block, PBlock, _>(
+					&*self.client,
+					block_hash,
+					block_number,
+				),
+			};
+
+		match maybe_local_receipt {
+			Some(local_receipt) =>
+				self.compare_with_local_receipt(local_receipt?, execution_receipt, receipt_hash),
+			None => Ok(ReceiptComparison::LocalReceiptPending { receipt_hash }),
+		}
+	}
+
+	/// Compares the execution receipt hashing to `receipt_hash` with the local receipt at the same
+	/// height.
+	#[doc(hidden)]
+	pub fn compare_with_local_receipt(
+		&self,
+		local_receipt: ExecutionReceiptFor<PBlock, Block::Hash>,
+		execution_receipt: &ExecutionReceiptFor<PBlock, Block::Hash>,
+		receipt_hash: H256,
+	) -> Result<ReceiptComparison<ExecutionReceiptFor<PBlock, Block::Hash>>, GossipMessageError> {
+		let block_number =
+			to_secondary_number::<NumberFor<Block>>(execution_receipt.primary_number)?;
+
+		// The local block at this height is built from another primary block when the local node
+		// missed the primary block of the receipt, the secondary block of the receipt is unknown
+		// locally and a mismatch would only prove the inputs differ. Verify the receipt against
+		// the block rebuilt from the bundles of its primary block instead.
+		if local_receipt.primary_hash != execution_receipt.primary_hash {
+			return self.compare_with_rebuilt_block(&local_receipt, execution_receipt)
+		}
+
+		let local_block_hash = local_receipt.secondary_hash;
+
+		// The receipt hash commits to every field including the whole trace, an identical
+		// receipt from an honest executor needs no per-root comparison. The local receipts are
+		// indexed by their hashes, neither receipt is hashed again here.
+		let identical_receipt_block_hash = self
+			.block_hash_by_receipt_hash(receipt_hash)
+			.map_err(|error| GossipMessageError::ReceiptLoad(Box::new(error)))?;
+		if identical_receipt_block_hash == Some(local_block_hash) {
+			return Ok(ReceiptComparison::Matched)
+		}
+
+		self.prefetch_block_body(local_block_hash);
+
+		// A root beyond the extrinsics of the local block can't be right, reject the receipt
+		// before a matching final root defers its verification. An identical receipt has the
+		// same length as the local trace, the block body is only loaded for the others.
+		ensure_trace_len(
+			execution_receipt.trace.len(),
+			expected_trace_len(self.block_body(local_block_hash)?.len()),
+		)?;
+
+		if !requires_full_verification(
+			block_number,
+			self.receipt_checkpoint_interval,
+			local_receipt.trace.last(),
+			execution_receipt.trace.last(),
+		) {
+			tracing::trace!(
+				target: LOG_TARGET,
+				?block_number,
+				"Deferring the full verification of the receipt as its final state root matches",
+			);
+			// Not verified root by root, the receipt must not be rebroadcast as a matched one.
+			return Ok(ReceiptComparison::Skipped)
+		}
+
+		let receipt_diff = diff_receipts(&local_receipt, execution_receipt);
+		if let Some(local_trace_idx) = receipt_diff.first_divergent_index {
+			if !self.is_primary_block_canonical(execution_receipt)? {
+				return Ok(ReceiptComparison::Skipped)
+			}
+
+			// The dispute may have been raised before a restart, the in-memory tracking of the
+			// submitted fraud proofs is lost by then.
+			if aux_schema::is_dispute_submitted(
+				&*self.client,
+				execution_receipt.secondary_hash,
+				local_trace_idx as u32,
+			)? {
+				tracing::debug!(
+					target: LOG_TARGET,
+					secondary_hash = ?execution_receipt.secondary_hash,
+					local_trace_idx,
+					"Ignoring the mismatched receipt as its fraud proof has already been submitted",
+				);
+				return Ok(ReceiptComparison::Skipped)
+			}
+
+			Ok(ReceiptComparison::Mismatched { local_trace_idx, local_receipt })
+		} else if receipt_diff.lengths_differ {
+			// A longer trace is rejected by `ensure_trace_len` above, a shorter one has no root
+			// to dispute with a fraud proof but must not be rebroadcast as a matched one either.
+			tracing::debug!(
+				target: LOG_TARGET,
+				?block_number,
+				local_trace_len = local_receipt.trace.len(),
+				trace_len = execution_receipt.trace.len(),
+				"Ignoring the receipt whose trace is a truncated local trace",
+			);
+			Ok(ReceiptComparison::Skipped)
+		} else {
+			Ok(ReceiptComparison::Matched)
+		}
+	}
+
+	/// Compares the execution receipt of a primary block missed locally with the trace of the
+	/// secondary block rebuilt from the bundles of that primary block.
+	///
+	/// The block is rebuilt on top of the parent of the local block `local_receipt` is for, i.e.,
+	/// the local block at the height of the receipt minus one.
+	fn compare_with_rebuilt_block(
+		&self,
+		local_receipt: &ExecutionReceiptFor<PBlock, Block::Hash>,
+		execution_receipt: &ExecutionReceiptFor<PBlock, Block::Hash>,
+	) -> Result<ReceiptComparison<ExecutionReceiptFor<PBlock, Block::Hash>>, GossipMessageError> {
+		let parent_hash = *self.header(local_receipt.secondary_hash)?.parent_hash();
+		let trace = self.rebuild_trace(parent_hash, execution_receipt.primary_hash)?;
+		if trace == execution_receipt.trace {
+			return Ok(ReceiptComparison::Matched)
+		}
+
+		// The rebuilt block is not imported, there is no local state to prove against.
+		tracing::warn!(
+			target: LOG_TARGET,
+			primary_hash = ?execution_receipt.primary_hash,
+			local_primary_hash = ?local_receipt.primary_hash,
+			"The receipt mismatches the block rebuilt from its primary block, skipping the dispute",
+		);
+		Ok(ReceiptComparison::Skipped)
+	}
+
+	/// Returns `true` if the primary block of the execution receipt is still canonical.
+	///
+	/// The primary chain may have reorged while we were waiting for the local receipt, a receipt
+	/// built on an abandoned primary fork must not be disputed.
+	fn is_primary_block_canonical(
+		&self,
+		execution_receipt: &ExecutionReceiptFor<PBlock, Block::Hash>,
+	) -> Result<bool, GossipMessageError> {
+		let canonical = is_canonical(
+			&*self.primary_chain_client,
+			execution_receipt.primary_number,
+			execution_receipt.primary_hash,
+		)?;
+		if !canonical {
+			tracing::debug!(
+				target: LOG_TARGET,
+				primary_hash = ?execution_receipt.primary_hash,
+				primary_number = ?execution_receipt.primary_number,
+				"Ignoring the mismatched receipt as its primary block is no longer canonical",
+			);
+		}
+		Ok(canonical)
+	}
+
+	/// Verifies the signature of the bundle against its signer, nothing else of the bundle is
+	/// validated, see [`Self::validate_bundle`] for the full validation.
+	pub fn verify_bundle_signature(
+		SignedBundle { bundle, signature, signer }: &SignedBundle<Block::Extrinsic>,
+	) -> Result<(), GossipMessageError> {
+		check_bundle_signature(bundle, signature, signer).map_err(Into::into)
+	}
+
+	/// Verifies the signature of the execution receipt against its signer, nothing else of the
+	/// receipt is checked.
+	///
+	/// Returns the hash of the receipt the signature is made over.
+	pub fn verify_receipt_signature(
+		signed_execution_receipt: &SignedExecutionReceiptFor<PBlock, Block::Hash>,
+	) -> Result<H256, GossipMessageError> {
+		let SignedExecutionReceipt { execution_receipt, signature, signer } =
+			signed_execution_receipt;
+
+		let receipt_hash = execution_receipt.hash();
+		if signer.verify(&receipt_hash, signature) {
+			Ok(receipt_hash)
+		} else {
+			Err(GossipMessageError::BadExecutionReceiptSignature)
+		}
+	}
+
+	/// Validates the bundle received from the network, i.e., it's not an equivocation and it's
+	/// signed by the expected executor.
+	pub fn validate_bundle(
+		&self,
+		signed_bundle: &SignedBundle<Block::Extrinsic>,
+	) -> Result<(), BundleValidationError> {
+		if let Some(equivocation_proof) = (self.check_bundle_equivocation)(&signed_bundle.bundle) {
+			return Err(BundleValidationError::Equivocation(equivocation_proof))
+		}
+
+		self.validate_bundle_author(signed_bundle)
+	}
+
+	/// Validates the bundle received from the network is signed by the expected executor.
+	fn validate_bundle_author(
+		&self,
+		SignedBundle { bundle, signature, signer }: &SignedBundle<Block::Extrinsic>,
+	) -> Result<(), BundleValidationError> {
+		check_bundle_signature(bundle, signature, signer)?;
+
+		let primary_hash = decode_primary_hash::<PBlock::Hash>(bundle.header.primary_hash)?;
+
+		let expected_executor_id = configured_executor_id(
+			self.primary_chain_client
+				.runtime_api()
+				.executor_id(&BlockId::Hash(primary_hash))?,
+		)
+		.ok_or(BundleValidationError::NoExecutorConfigured)?;
+		if *signer != expected_executor_id {
+			// TODO: handle the misbehavior.
+
+			return Err(BundleValidationError::InvalidAuthor {
+				got: signer.clone(),
+				expected: expected_executor_id,
+			})
+		}
+
+		Ok(())
+	}
+
+	/// Evaluates the bundle received from the network without acting on it.
+	///
+	/// Unlike [`GossipMessageHandler::on_bundle`], neither the bundle equivocation proof nor the
+	/// invalid transaction proofs are submitted, they are returned instead. The bundle is still
+	/// accounted to the rate limit of its author and to the rebroadcast window.
+	pub fn evaluate_bundle(
+		&self,
+		signed_bundle: &SignedBundle<Block::Extrinsic>,
+	) -> Result<BundleOutcome, GossipMessageError> {
+		let SignedBundle { bundle, signer, .. } = signed_bundle;
+
+		// The bundles of other domains are handled by the executors of those domains.
+		if bundle.header.domain_id != self.domain_id {
+			tracing::debug!(
+				target: LOG_TARGET,
+				domain_id = ?bundle.header.domain_id,
+				"Ignoring the bundle of another domain",
+			);
+			return Ok(BundleOutcome::Ignored)
+		}
+
+		// Reject the oversized bundles before spending any effort on the extrinsics.
+		let size = bundle.encoded_size();
+		if bundle.extrinsics.len() > self.max_received_bundle_extrinsics ||
+			size > self.max_received_bundle_size
+		{
+			return Err(GossipMessageError::OversizedBundle {
+				extrinsics: bundle.extrinsics.len(),
+				max_extrinsics: self.max_received_bundle_extrinsics,
+				size,
+				max_size: self.max_received_bundle_size,
+			})
+		}
+
+		let latest_slot = *self.latest_slot.lock();
+		if is_future_slot(bundle.header.slot_number, latest_slot, self.slot_skew_tolerance) {
+			return Err(GossipMessageError::FutureBundleSlot {
+				slot: bundle.header.slot_number,
+				latest: latest_slot.map(Into::into).unwrap_or_default(),
+			})
+		}
+
+		// The primary hash comes from the peer, it's not necessarily of the primary hash type.
+		let primary_hash = decode_primary_hash::<PBlock::Hash>(bundle.header.primary_hash)?;
+
+		if self
+			.primary_chain_client
+			.header(BlockId::Hash(primary_hash))
+			.map_err(|error| GossipMessageError::HeaderLookup(Box::new(error)))?
+			.is_none()
+		{
+			tracing::debug!(
+				target: LOG_TARGET,
+				?primary_hash,
+				"Dropping the bundle as its primary block is unknown",
+			);
+			return Ok(BundleOutcome::Ignored)
+		}
+
+		let equivocation_proof = (self.check_bundle_equivocation)(bundle);
+		if let Some(equivocation_proof) = &equivocation_proof {
+			if self.equivocation_policy == EquivocationPolicy::ReportAndAbort {
+				return Ok(BundleOutcome::Equivocation(equivocation_proof.clone()))
+			}
+		}
+
+		match self.validate_bundle_author(signed_bundle) {
+			Ok(()) => {},
+			Err(BundleValidationError::BadSignature) => return Ok(BundleOutcome::BadSignature),
+			Err(BundleValidationError::InvalidAuthor { got, expected }) =>
+				return Ok(BundleOutcome::InvalidAuthor { got, expected }),
+			Err(error) => return Err(error.into()),
+		}
+
+		// TODO: check whether the bundle is already in the bundle pool.
+		let bundle_exists = false;
+		if bundle_exists {
+			return Ok(BundleOutcome::AlreadyKnown)
+		}
+
+		if !self.bundle_rate_limiter.try_acquire(signer, std::time::Instant::now()) {
+			tracing::debug!(
+				target: LOG_TARGET,
+				?signer,
+				"Dropping the bundle as the author exceeded the bundle rate limit",
+			);
+			return Ok(BundleOutcome::Ignored)
+		}
+
+		let bundle_hash = bundle_digest(bundle);
+
+		let mut invalid_transaction_proofs = Vec::new();
+		for (extrinsic_index, extrinsic) in bundle.extrinsics.iter().enumerate() {
+			let tx_hash = self.transaction_pool.hash_of(extrinsic);
+
+			if self.transaction_pool.ready_transaction(&tx_hash).is_some() {
+				// TODO: Set the status of each tx in the bundle to seen
+			} else if let Some(invalid_transaction_proof) =
+				self.check_bundle_extrinsic(primary_hash, bundle_hash, extrinsic_index, extrinsic)?
+			{
+				invalid_transaction_proofs.push(invalid_transaction_proof);
+			}
+		}
+
+		Ok(BundleOutcome::Accepted {
+			rebroadcast: self
+				.rebroadcast_window
+				.try_rebroadcast(bundle_hash, std::time::Instant::now()),
+			invalid_transaction_proofs,
+			equivocation_proof,
+		})
+	}
+
+	/// Evaluates the execution receipt received from the network without acting on it.
+	///
+	/// Unlike [`GossipMessageHandler::on_execution_receipt`], the fraud proof for a mismatched
+	/// receipt is constructed in place and returned instead of being submitted.
+	pub fn evaluate_execution_receipt(
+		&self,
+		signed_execution_receipt: &SignedExecutionReceiptFor<PBlock, Block::Hash>,
+	) -> Result<ExecutionReceiptDecision, GossipMessageError> {
+		match self.compare_execution_receipt(signed_execution_receipt)? {
+			ReceiptComparison::Skipped => Ok(ExecutionReceiptDecision::Ignored),
+			ReceiptComparison::LocalReceiptPending { .. } =>
+				Ok(ExecutionReceiptDecision::LocalReceiptPending),
+			ReceiptComparison::Matched => Ok(ExecutionReceiptDecision::WouldRebroadcast),
+			ReceiptComparison::Mismatched { local_trace_idx, local_receipt } => {
+				let fraud_proof = self.create_fraud_proof(
+					local_trace_idx,
+					&local_receipt,
+					&signed_execution_receipt.execution_receipt,
+				)?;
+				Ok(ExecutionReceiptDecision::WouldSubmitFraudProof(fraud_proof))
+			},
+		}
+	}
+
+	/// The background is that a receipt received from the network points to a future block
+	/// from the local view, so we need to wait for the receipt for the block at the same
+	/// height to be produced locally in order to check the validity of the external receipt.
+	async fn wait_for_local_future_receipt(
+		&self,
+		secondary_block_hash: Block::Hash,
+		secondary_block_number: <Block::Header as HeaderT>::Number,
+	) -> Result<ExecutionReceiptFor<PBlock, Block::Hash>, GossipMessageError> {
+		poll_until_ready(self.receipt_poll_interval, || {
+			poll_local_future_receipt::<Block, PBlock, _>(
+				&*self.client,
+				secondary_block_hash,
+				secondary_block_number,
+			)
+		})
+		.await
+	}
+
+	/// Constructs the fraud proof of the mismatched execution receipt and submits it in the
+	/// background.
+	fn spawn_fraud_proof_submission(
+		&self,
+		local_trace_idx: usize,
+		local_receipt: ExecutionReceiptFor<PBlock, Block::Hash>,
+		execution_receipt: ExecutionReceiptFor<PBlock, Block::Hash>,
+	) {
+		// Constructing the fraud proof is expensive, do it in the background to avoid
+		// blocking the gossip message handling.
+		let executor = self.clone();
+		self.spawner.spawn_blocking(
+			"cirrus-create-fraud-proof",
+			None,
+			async move {
+				let fraud_proof = run_prover(&executor.provers, || {
+					executor.create_fraud_proof(local_trace_idx, &local_receipt, &execution_receipt)
+				})
+				.await;
+				match fraud_proof {
+					Ok(fraud_proof) => {
+						match executor
+							.submit_fraud_proof_awaitable(
+								fraud_proof,
+								Some(BlockId::Hash(execution_receipt.primary_hash)),
+							)
+							.await
+						{
+							Ok(()) => {
+								if let Err(err) = aux_schema::write_submitted_dispute(
+									&*executor.client,
+									execution_receipt.secondary_hash,
+									local_trace_idx as u32,
+								) {
+									tracing::error!(
+										target: LOG_TARGET,
+										?err,
+										"Failed to record the submitted dispute"
+									);
+								}
+							},
+							// Logged by `submit_fraud_proof_awaitable`.
+							Err(SubmitError::Disabled) => {},
+							Err(error) => tracing::debug!(
+								target: LOG_TARGET,
+								?error,
+								"Failed to submit fraud proof"
+							),
+						}
+					},
+					// The trap is the evidence of the invalid extrinsic.
+					Err(GossipMessageError::ExecutionTrap { extrinsic_index, error }) => {
+						tracing::debug!(
+							target: LOG_TARGET,
+							extrinsic_index,
+							?error,
+							"Extrinsic traps during the execution",
+						);
+						match executor.create_trapped_extrinsic_proof(
+							&execution_receipt,
+							extrinsic_index,
+						) {
+							Ok(Some(invalid_transaction_proof)) => executor
+								.submit_invalid_transaction_proof(
+									invalid_transaction_proof,
+									Some(BlockId::Hash(execution_receipt.primary_hash)),
+								),
+							Ok(None) => tracing::debug!(
+								target: LOG_TARGET,
+								extrinsic_index,
+								"Trapped extrinsic is not from any bundle",
+							),
+							Err(err) => tracing::error!(
+								target: LOG_TARGET,
+								?err,
+								"Failed to create the invalid transaction proof"
+							),
+						}
+					},
+					Err(err) => tracing::error!(
+						target: LOG_TARGET,
+						?err,
+						"Failed to create the fraud proof"
+					),
+				}
+			}
+			.instrument(self.span.clone())
+			.boxed(),
+		);
+	}
+
+	/// Compares the execution receipt of a future block from the local view in the background
+	/// once the local receipt at the same height is produced, a mismatched receipt is disputed
+	/// as usual.
+	fn compare_once_local_receipt_ready(
+		&self,
+		execution_receipt: ExecutionReceiptFor<PBlock, Block::Hash>,
+		receipt_hash: H256,
+	) -> Result<(), GossipMessageError> {
+		let block_number =
+			to_secondary_number::<NumberFor<Block>>(execution_receipt.primary_number)?;
+		let waiter = self.future_receipt_waiters.reserve()?;
+		let executor = self.clone();
+		self.spawner.spawn(
+			"wait-for-local-execution-receipt",
+			None,
+			async move {
+				let _waiter = waiter;
+				let comparison = executor
+					.wait_for_local_future_receipt(execution_receipt.secondary_hash, block_number)
+					.await
+					.and_then(|local_receipt| {
+						executor.compare_with_local_receipt(
+							local_receipt,
+							&execution_receipt,
+							receipt_hash,
+						)
+					});
+				match comparison {
+					Ok(ReceiptComparison::Mismatched { local_trace_idx, local_receipt }) =>
+						executor.spawn_fraud_proof_submission(
+							local_trace_idx,
+							local_receipt,
+							execution_receipt,
+						),
+					Ok(_) => {},
+					Err(err) => tracing::error!(
+						target: LOG_TARGET,
+						?err,
+						"Error occurred while comparing the receipt with the local receipt"
+					),
+				}
+			}
+			.instrument(self.span.clone())
+			.boxed(),
+		);
+		Ok(())
+	}
+
+	/// Processes the bundles extracted from the primary block, the block is built on top of the
+	/// best block regardless of the primary block number.
+	// TODO: Remove this whole method, `self.bundle_processor` as a property and fix
+	// `set_new_code_should_work` test to do an actual runtime upgrade
+	#[doc(hidden)]
+	pub async fn process_bundles(
+		self,
+		primary_info: (PBlock::Hash, NumberFor<PBlock>),
+		bundles: Vec<OpaqueBundle>,
+		shuffling_seed: Randomness,
+		maybe_new_runtime: Option<Cow<'static, [u8]>>,
+	) {
+		if let Err(err) = self
+			.bundle_processor
+			.process_bundles_on_best(primary_info, bundles, shuffling_seed, maybe_new_runtime)
+			.await
+		{
+			tracing::error!(
+				target: LOG_TARGET,
+				?primary_info,
+				error = ?err,
+				"Error at processing bundles.",
+			);
+		}
+	}
+}
+
+/// Result of comparing an execution receipt received from the network with the local one.
+#[doc(hidden)]
+pub enum ReceiptComparison<Receipt> {
+	/// The receipt is not compared, e.g., it has been pruned or its primary block is no longer
+	/// canonical.
+	Skipped,
+	/// The receipt matches the local one.
+	Matched,
+	/// The receipt mismatches the local one, starting from the trace root at `local_trace_idx`.
+	Mismatched { local_trace_idx: usize, local_receipt: Receipt },
+	/// The receipt points to a future block from the local view, the local receipt to compare
+	/// with is not produced yet.
+	LocalReceiptPending { receipt_hash: H256 },
+}
+
+/// What the executor would do with an execution receipt received from the network.
+#[derive(Debug)]
+pub enum ExecutionReceiptDecision {
+	/// The receipt matches the local one and would be rebroadcast.
+	WouldRebroadcast,
+	/// The receipt mismatches the local one and the fraud proof would be submitted.
+	WouldSubmitFraudProof(FraudProof),
+	/// The receipt would be ignored.
+	Ignored,
+	/// The local receipt to compare with is not produced yet, the receipt would be compared in
+	/// the background once it is.
+	LocalReceiptPending,
+}
+
+/// What the executor would do with a bundle received from the network.
+#[derive(Debug)]
+pub enum BundleOutcome {
+	/// The bundle is an equivocation of its author, the proof would be submitted and the bundle
+	/// wouldn't be processed further under [`EquivocationPolicy::ReportAndAbort`].
+	Equivocation(BundleEquivocationProof),
+	/// The bundle is not signed by the expected executor.
+	InvalidAuthor { got: ExecutorId, expected: ExecutorId },
+	/// The bundle signature is invalid.
+	BadSignature,
+	/// The bundle has been received before.
+	AlreadyKnown,
+	/// The bundle would be ignored, e.g., it's for another domain, its primary block is unknown or
+	/// its author exceeded the bundle rate limit.
+	Ignored,
+	/// The bundle is accepted, the invalid transaction proofs of its illegal extrinsics would be
+	/// submitted.
+	///
+	/// `equivocation_proof` is set for an equivocation accepted under
+	/// [`EquivocationPolicy::ReportAndContinue`], which would be submitted as well.
+	Accepted {
+		rebroadcast: bool,
+		invalid_transaction_proofs: Vec<InvalidTransactionProof>,
+		equivocation_proof: Option<BundleEquivocationProof>,
+	},
+}
+
+/// Error of validating a bundle received from the network.
+#[derive(Debug, thiserror::Error)]
+pub enum BundleValidationError {
 	#[error("Bundle equivocation error")]
-	BundleEquivocation,
+	Equivocation(BundleEquivocationProof),
+	#[error("The signature of bundle is invalid")]
+	BadSignature,
+	#[error("Invalid bundle author, got: {got}, expected: {expected}")]
+	InvalidAuthor { got: ExecutorId, expected: ExecutorId },
+	#[error("No executor is configured on the primary chain, check the executor genesis config")]
+	NoExecutorConfigured,
+	#[error("Bundle primary hash does not decode to the primary block hash type")]
+	MalformedPrimaryHash,
+	#[error(transparent)]
+	RuntimeApi(#[from] sp_api::ApiError),
+}
+
+impl PeerReputation for BundleValidationError {
+	fn reputation_change(&self) -> Option<ReputationChange> {
+		match self {
+			Self::BadSignature => Some(cost::BAD_SIGNATURE),
+			Self::MalformedPrimaryHash => Some(cost::UNDECODABLE_MESSAGE),
+			// The peer may have only relayed the bundle of the misbehaving author, which is
+			// handled by submitting the equivocation proof against the author instead.
+			Self::Equivocation(_) | Self::InvalidAuthor { .. } => None,
+			// Local failures the peer is not to blame for.
+			Self::NoExecutorConfigured | Self::RuntimeApi(_) => None,
+		}
+	}
+}
+
+impl BundleValidationError {
+	/// Returns the stable kind of this error.
+	pub fn kind(&self) -> GossipErrorKind {
+		match self {
+			Self::Equivocation(_) => GossipErrorKind::Equivocation,
+			Self::BadSignature => GossipErrorKind::BadSignature,
+			Self::InvalidAuthor { .. } => GossipErrorKind::InvalidAuthor,
+			Self::MalformedPrimaryHash => GossipErrorKind::Malformed,
+			Self::NoExecutorConfigured | Self::RuntimeApi(_) => GossipErrorKind::Runtime,
+		}
+	}
+}
+
+/// Stable classification of [`GossipMessageError`].
+///
+/// Downstream code should branch on the kind instead of matching the error variants, which may
+/// change between releases.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum GossipErrorKind {
+	/// The message is signed with an invalid signature.
+	BadSignature,
+	/// The message is not authored by the expected executor.
+	InvalidAuthor,
+	/// The message proves an equivocation of its author.
+	Equivocation,
+	/// The message is malformed or inconsistent with the local view of the chain.
+	Malformed,
+	/// The local client failed to serve the data required to handle the message.
+	Client,
+	/// The runtime failed to serve the data required to handle the message.
+	Runtime,
+	/// Handling the message timed out.
+	Timeout,
+	/// Internal failure of the executor.
+	Internal,
+}
+
+/// Error type for cirrus gossip handling.
+#[derive(Debug, thiserror::Error)]
+pub enum GossipMessageError {
+	#[error(transparent)]
+	BundleValidation(#[from] BundleValidationError),
+	#[error("Bundle slot {slot} is in the future, latest primary slot: {latest}")]
+	FutureBundleSlot { slot: u64, latest: u64 },
+	#[error(
+		"Bundle exceeds the limits, extrinsics: {extrinsics} (max: {max_extrinsics}), \
+		size: {size} bytes (max: {max_size})"
+	)]
+	OversizedBundle { extrinsics: usize, max_extrinsics: usize, size: usize, max_size: usize },
 	#[error("State root not using H256")]
 	InvalidStateRootType,
 	#[error("Invalid extrinsic index for creating the execution proof, got: {index}, max: {max}")]
 	InvalidExtrinsicIndex { index: usize, max: usize },
 	#[error(transparent)]
 	Client(Box<sp_blockchain::Error>),
+	#[error("Failed to look up the block header: {0}")]
+	HeaderLookup(Box<sp_blockchain::Error>),
+	#[error("Failed to get the block body: {0}")]
+	BlockBodyMissing(Box<sp_blockchain::Error>),
+	#[error("Failed to load the local execution receipt: {0}")]
+	ReceiptLoad(Box<sp_blockchain::Error>),
 	#[error(transparent)]
 	RuntimeApi(#[from] sp_api::ApiError),
-	#[error(transparent)]
-	RecvError(#[from] crossbeam::channel::RecvError),
-	#[error("Failed to send local receipt result because the channel is disconnected")]
-	SendError,
-	#[error("The signature of bundle is invalid")]
-	BadBundleSignature,
-	#[error("Invalid bundle author, got: {got}, expected: {expected}")]
-	InvalidBundleAuthor { got: ExecutorId, expected: ExecutorId },
 	#[error("The signature of execution receipt is invalid")]
 	BadExecutionReceiptSignature,
 	#[error("Invalid execution receipt author, got: {got}, expected: {expected}")]
 	InvalidExecutionReceiptAuthor { got: ExecutorId, expected: ExecutorId },
+	#[error("No executor is configured on the primary chain, check the executor genesis config")]
+	NoExecutorConfigured,
+	#[error("Malformed execution receipt with an empty trace")]
+	EmptyTrace,
+	#[error("Execution receipt trace is too long, got: {len}, max: {max}")]
+	TraceTooLong { len: usize, max: usize },
+	#[error(
+		"Inconsistent primary block reference, stated number: {stated}, local number: {local:?}"
+	)]
+	InconsistentPrimaryReference { stated: u64, local: Option<u64> },
+	#[error("Block number does not fit into the block number type of the executor")]
+	BlockNumberOverflow,
+	#[error("Extrinsic {extrinsic_index} traps during the execution: {error}")]
+	ExecutionTrap { extrinsic_index: usize, error: Box<sc_executor::error::Error> },
+	#[error("Parent state is unavailable for constructing the proof, block status: {status:?}")]
+	ParentStateUnavailable { status: BlockStatus },
+	#[error("The awaited secondary block has been orphaned by a reorg")]
+	AwaitedBlockOrphaned,
+	#[error("Too many receipts are waiting for the local receipts, max: {max}")]
+	TooManyPendingReceipts { max: usize },
 }
 
 impl From<sp_blockchain::Error> for GossipMessageError {
@@ -551,6 +2413,65 @@ impl From<sp_blockchain::Error> for GossipMessageError {
 	}
 }
 
+impl PeerReputation for GossipMessageError {
+	fn reputation_change(&self) -> Option<ReputationChange> {
+		match self {
+			Self::BundleValidation(error) => error.reputation_change(),
+			Self::BadExecutionReceiptSignature => Some(cost::BAD_SIGNATURE),
+			// Only the undecodable and the badly signed messages are penalized, the others may
+			// have been relayed in good faith by the peer.
+			Self::InvalidExecutionReceiptAuthor { .. } |
+			Self::FutureBundleSlot { .. } |
+			Self::OversizedBundle { .. } |
+			Self::EmptyTrace |
+			Self::TraceTooLong { .. } |
+			Self::InconsistentPrimaryReference { .. } => None,
+			// Local failures the peer is not to blame for.
+			Self::NoExecutorConfigured |
+			Self::InvalidStateRootType |
+			Self::InvalidExtrinsicIndex { .. } |
+			Self::Client(_) |
+			Self::HeaderLookup(_) |
+			Self::BlockBodyMissing(_) |
+			Self::ReceiptLoad(_) |
+			Self::RuntimeApi(_) |
+			Self::BlockNumberOverflow |
+			Self::ExecutionTrap { .. } |
+			Self::ParentStateUnavailable { .. } |
+			Self::AwaitedBlockOrphaned |
+			Self::TooManyPendingReceipts { .. } => None,
+		}
+	}
+}
+
+impl GossipMessageError {
+	/// Returns the stable kind of this error.
+	pub fn kind(&self) -> GossipErrorKind {
+		match self {
+			Self::BundleValidation(error) => error.kind(),
+			Self::BadExecutionReceiptSignature => GossipErrorKind::BadSignature,
+			Self::InvalidExecutionReceiptAuthor { .. } => GossipErrorKind::InvalidAuthor,
+			Self::FutureBundleSlot { .. } |
+			Self::OversizedBundle { .. } |
+			Self::EmptyTrace |
+			Self::TraceTooLong { .. } |
+			Self::InconsistentPrimaryReference { .. } => GossipErrorKind::Malformed,
+			Self::Client(_) |
+			Self::HeaderLookup(_) |
+			Self::BlockBodyMissing(_) |
+			Self::ReceiptLoad(_) |
+			Self::ParentStateUnavailable { .. } |
+			Self::AwaitedBlockOrphaned => GossipErrorKind::Client,
+			Self::NoExecutorConfigured | Self::RuntimeApi(_) | Self::ExecutionTrap { .. } =>
+				GossipErrorKind::Runtime,
+			Self::InvalidStateRootType |
+			Self::InvalidExtrinsicIndex { .. } |
+			Self::BlockNumberOverflow |
+			Self::TooManyPendingReceipts { .. } => GossipErrorKind::Internal,
+		}
+	}
+}
+
 impl<Block, PBlock, Client, PClient, TransactionPool, Backend, E>
 	GossipMessageHandler<PBlock, Block>
 	for Executor<Block, PBlock, Client, PClient, TransactionPool, Backend, E>
@@ -561,11 +2482,14 @@ where
 		+ BlockBackend<Block>
 		+ ProvideRuntimeApi<Block>
 		+ AuxStore
+		+ BlockchainEvents<Block>
 		+ Send
 		+ Sync
 		+ 'static,
 	Client::Api: SecondaryApi<Block, AccountId>
 		+ sp_block_builder::BlockBuilder<Block>
+		+ TaggedTransactionQueue<Block>
+		+ TransactionPaymentApi<Block, Balance>
 		+ sp_api::ApiExt<
 			Block,
 			StateBackend = sc_client_api::backend::StateBackendFor<Backend, Block>,
@@ -585,74 +2509,52 @@ where
 	Backend: sc_client_api::Backend<Block> + Send + Sync + 'static,
 	TransactionFor<Backend, Block>: sp_trie::HashDBT<HashFor<Block>, sp_trie::DBValue>,
 	TransactionPool: sc_transaction_pool_api::TransactionPool<Block = Block> + 'static,
-	E: CodeExecutor,
+	E: CodeExecutor<Error = sc_executor::error::Error>,
 {
 	type Error = GossipMessageError;
 
 	fn on_bundle(
 		&self,
-		SignedBundle { bundle, signature, signer }: &SignedBundle<Block::Extrinsic>,
+		signed_bundle: &SignedBundle<Block::Extrinsic>,
 	) -> Result<Action, Self::Error> {
-		let check_equivocation = |_bundle: &Bundle<Block::Extrinsic>| {
-			// TODO: check bundle equivocation
-			let bundle_is_an_equivocation = false;
-			if bundle_is_an_equivocation {
-				Some(BundleEquivocationProof::dummy_at(bundle.header.slot_number))
-			} else {
-				None
-			}
-		};
-
-		// A bundle equivocation occurs.
-		if let Some(equivocation_proof) = check_equivocation(bundle) {
-			self.submit_bundle_equivocation_proof(equivocation_proof);
-			return Err(GossipMessageError::BundleEquivocation)
-		}
-
-		let bundle_exists = false;
-
-		if bundle_exists {
-			Ok(Action::Empty)
-		} else {
-			let primary_hash =
-				PBlock::Hash::decode(&mut bundle.header.primary_hash.encode().as_slice())
-					.expect("Hash type must be correct");
+		let _span = self.span.enter();
 
-			if !signer.verify(&bundle.hash(), signature) {
-				return Err(Self::Error::BadBundleSignature)
-			}
+		match self.evaluate_bundle(signed_bundle)? {
+			BundleOutcome::Ignored | BundleOutcome::AlreadyKnown => Ok(Action::Empty),
+			BundleOutcome::Equivocation(equivocation_proof) => {
+				self.submit_bundle_equivocation_proof(equivocation_proof.clone(), None);
+				Err(BundleValidationError::Equivocation(equivocation_proof).into())
+			},
+			BundleOutcome::BadSignature => Err(BundleValidationError::BadSignature.into()),
+			BundleOutcome::InvalidAuthor { got, expected } =>
+				Err(BundleValidationError::InvalidAuthor { got, expected }.into()),
+			BundleOutcome::Accepted {
+				rebroadcast,
+				invalid_transaction_proofs,
+				equivocation_proof,
+			} => {
+				if let Some(equivocation_proof) = equivocation_proof {
+					self.submit_bundle_equivocation_proof(equivocation_proof, None);
+				}
 
-			let expected_executor_id = self
-				.primary_chain_client
-				.runtime_api()
-				.executor_id(&BlockId::Hash(primary_hash))?;
-			if *signer != expected_executor_id {
-				// TODO: handle the misbehavior.
+				let primary_hash =
+					decode_primary_hash::<PBlock::Hash>(signed_bundle.bundle.header.primary_hash)?;
 
-				return Err(Self::Error::InvalidBundleAuthor {
-					got: signer.clone(),
-					expected: expected_executor_id,
-				})
-			}
+				for invalid_transaction_proof in invalid_transaction_proofs {
+					self.submit_invalid_transaction_proof(
+						invalid_transaction_proof,
+						Some(BlockId::Hash(primary_hash)),
+					);
+				}
 
-			for extrinsic in bundle.extrinsics.iter() {
-				let tx_hash = self.transaction_pool.hash_of(extrinsic);
+				// TODO: all checks pass, add to the bundle pool
 
-				if self.transaction_pool.ready_transaction(&tx_hash).is_some() {
-					// TODO: Set the status of each tx in the bundle to seen
+				if rebroadcast {
+					Ok(Action::RebroadcastBundle)
 				} else {
-					// TODO: check the legality
-					//
-					// if illegal => illegal tx proof
-					let invalid_transaction_proof = InvalidTransactionProof;
-
-					self.submit_invalid_transaction_proof(invalid_transaction_proof);
+					Ok(Action::Empty)
 				}
-			}
-
-			// TODO: all checks pass, add to the bundle pool
-
-			Ok(Action::RebroadcastBundle)
+			},
 		}
 	}
 
@@ -661,206 +2563,509 @@ where
 		&self,
 		signed_execution_receipt: &SignedExecutionReceiptFor<PBlock, Block::Hash>,
 	) -> Result<Action, Self::Error> {
-		let SignedExecutionReceipt { execution_receipt, signature, signer } =
-			signed_execution_receipt;
+		let _span = self.span.enter();
 
-		if !signer.verify(&execution_receipt.hash(), signature) {
-			return Err(Self::Error::BadExecutionReceiptSignature)
+		// The receipts of the blocks not executed locally yet would only pile up the tasks waiting
+		// for the local receipts until the sync completes.
+		if (self.is_major_syncing)() {
+			tracing::trace!(
+				target: LOG_TARGET,
+				"Ignoring the execution receipt as the primary node is major syncing"
+			);
+			return Ok(Action::Empty)
 		}
 
-		let expected_executor_id = self
-			.primary_chain_client
-			.runtime_api()
-			.executor_id(&BlockId::Hash(execution_receipt.primary_hash))?;
-		if *signer != expected_executor_id {
-			// TODO: handle the misbehavior.
-
-			return Err(Self::Error::InvalidExecutionReceiptAuthor {
-				got: signer.clone(),
-				expected: expected_executor_id,
-			})
+		match self.compare_execution_receipt(signed_execution_receipt)? {
+			ReceiptComparison::Skipped => Ok(Action::Empty),
+			ReceiptComparison::Matched => {
+				if self
+					.rebroadcast_window
+					.try_rebroadcast(signed_execution_receipt.hash(), std::time::Instant::now())
+				{
+					Ok(Action::RebroadcastExecutionReceipt)
+				} else {
+					Ok(Action::Empty)
+				}
+			},
+			ReceiptComparison::Mismatched { local_trace_idx, local_receipt } => {
+				self.spawn_fraud_proof_submission(
+					local_trace_idx,
+					local_receipt,
+					signed_execution_receipt.execution_receipt.clone(),
+				);
+				Ok(Action::Empty)
+			},
+			ReceiptComparison::LocalReceiptPending { receipt_hash } => {
+				// Waiting for the local receipt would block the gossip message handling, the
+				// receipt is discarded and compared in the background instead.
+				self.compare_once_local_receipt_ready(
+					signed_execution_receipt.execution_receipt.clone(),
+					receipt_hash,
+				)?;
+				Ok(Action::Empty)
+			},
 		}
+	}
+}
 
-		let primary_number = execution_receipt.primary_number;
-		let best_execution_chain_number = self
-			.primary_chain_client
-			.runtime_api()
-			.best_execution_chain_number(&BlockId::Hash(
-				self.primary_chain_client.info().best_hash,
-			))?;
+/// Returns the digest identifying `bundle`, which is what the bundle signature is made over and
+/// what the received bundles are deduplicated by.
+pub fn bundle_digest<Extrinsic>(bundle: &Bundle<Extrinsic>) -> H256 {
+	bundle.hash()
+}
 
-		// Just ignore it if the receipt is too old and has been pruned.
-		if aux_schema::target_receipt_is_pruned(
-			best_execution_chain_number.saturated_into(),
-			primary_number.saturated_into(),
-		) {
-			return Ok(Action::Empty)
-		}
+/// Decodes the primary hash of a bundle received from the network into the primary hash type.
+fn decode_primary_hash<PHash: Decode>(
+	primary_hash: impl Encode,
+) -> Result<PHash, BundleValidationError> {
+	PHash::decode(&mut primary_hash.encode().as_slice())
+		.map_err(|_| BundleValidationError::MalformedPrimaryHash)
+}
 
-		let block_hash = execution_receipt.secondary_hash;
-		let block_number = <NumberFor<Block>>::decode(&mut primary_number.encode().as_slice())
-			.expect("Primary number and secondary number must use the same type; qed");
+/// Checks `signature` is the signature of `bundle` by `signer`.
+fn check_bundle_signature<Extrinsic>(
+	bundle: &Bundle<Extrinsic>,
+	signature: &ExecutorSignature,
+	signer: &ExecutorId,
+) -> Result<(), BundleValidationError> {
+	if signer.verify(&bundle_digest(bundle), signature) {
+		Ok(())
+	} else {
+		Err(BundleValidationError::BadSignature)
+	}
+}
 
-		// TODO: more efficient execution receipt checking strategy?
-		let local_receipt = if let Some(local_receipt) =
-			aux_schema::load_execution_receipt(&*self.client, block_hash)?
-		{
-			local_receipt
-		} else {
-			// Wait for the local execution receipt until it's ready.
-			let (tx, rx) = crossbeam::channel::bounded::<
-				sp_blockchain::Result<ExecutionReceiptFor<PBlock, Block::Hash>>,
-			>(1);
-			let executor = self.clone();
-			self.spawner.spawn(
-				"wait-for-local-execution-receipt",
-				None,
-				async move {
-					if let Err(err) =
-						executor.wait_for_local_future_receipt(block_hash, block_number, tx).await
-					{
-						tracing::error!(
-							target: LOG_TARGET,
-							?err,
-							"Error occurred while waiting for the local receipt"
-						);
-					}
-				}
-				.boxed(),
-			);
-			rx.recv()??
+/// Converts the state root or block hash of the secondary chain to [`H256`] used by the fraud
+/// proof.
+// TODO: avoid the encode & decode?
+fn as_h256<Hash: Encode>(hash: &Hash) -> Result<H256, GossipMessageError> {
+	H256::decode(&mut hash.encode().as_slice())
+		.map_err(|_| GossipMessageError::InvalidStateRootType)
+}
+
+/// Ensures every root of the execution `trace` can be converted to [`H256`].
+fn ensure_h256_trace<Hash: Encode>(trace: &[Hash]) -> Result<(), GossipMessageError> {
+	trace.iter().try_for_each(|root| as_h256(root).map(|_| ()))
+}
+
+/// Signs `payload` with the keystore key of `executor_id`, the executor registered on the primary
+/// chain at the block the payload is built on, returns `None` if the key isn't in the keystore.
+///
+/// The keystore may hold several executor keys, e.g., both the old and the new one while the
+/// executor key is being rotated. Selecting the key by the on-chain executor id at the same
+/// primary block the verifier checks against makes the payloads built before the rotation be
+/// signed with the old key and the ones built after with the new key, so both keys are usable
+/// during the transition without any extra bookkeeping.
+fn sign_as_executor(
+	keystore: &SyncCryptoStorePtr,
+	executor_id: &ExecutorId,
+	payload: &[u8],
+) -> Result<Option<ExecutorSignature>, sp_blockchain::Error> {
+	if !SyncCryptoStore::has_keys(
+		&**keystore,
+		&[(ByteArray::to_raw_vec(executor_id), ExecutorId::ID)],
+	) {
+		return Ok(None)
+	}
+
+	match SyncCryptoStore::sign_with(
+		&**keystore,
+		ExecutorId::ID,
+		&executor_id.clone().into(),
+		payload,
+	) {
+		Ok(Some(signature)) =>
+			ExecutorSignature::decode(&mut signature.as_slice()).map(Some).map_err(|err| {
+				sp_blockchain::Error::Application(Box::from(format!(
+					"Failed to decode the executor signature: {err}"
+				)))
+			}),
+		Ok(None) => Err(sp_blockchain::Error::Application(Box::from(
+			"This should not happen as the existence of key was just checked",
+		))),
+		Err(error) => Err(sp_blockchain::Error::Application(Box::from(format!(
+			"Error occurred when signing with the executor key: {error}"
+		)))),
+	}
+}
+
+/// Returns the span identifying the executor instance of `domain_id`.
+///
+/// The target of the logs is static, the instances running in the same process are told apart by
+/// the `domain_id` field of this span instead, e.g., `[executor{domain_id=DomainId(1)}]`.
+fn executor_span(domain_id: DomainId) -> tracing::Span {
+	tracing::info_span!(target: LOG_TARGET, "executor", ?domain_id)
+}
+
+/// Logs `proof` at the info level instead of submitting it as the proof submission is disabled,
+/// so that the misbehaviors detected in the detection-only mode are still visible.
+fn log_unsubmitted_proof(proof_kind: &str, proof: &impl fmt::Debug) {
+	tracing::info!(
+		target: LOG_TARGET,
+		?proof,
+		"Proof submission is disabled, skipping the submission of {proof_kind}"
+	);
+}
+
+/// Ensures the primary runtime exposes a supported `ExecutorApi`, `version` is the version the
+/// primary runtime reports, `None` if it doesn't expose the API at all.
+fn ensure_executor_api_version(version: Option<u32>) -> Result<(), sp_consensus::Error> {
+	match version {
+		Some(version) if version >= MIN_EXECUTOR_API_VERSION => Ok(()),
+		Some(version) => Err(sp_consensus::Error::Other(Box::from(format!(
+			"Primary runtime exposes ExecutorApi v{version}, the minimum supported version is \
+				v{MIN_EXECUTOR_API_VERSION}, please upgrade the primary node"
+		)))),
+		None => Err(sp_consensus::Error::Other(Box::from(
+			"Primary runtime doesn't expose ExecutorApi, is the executor connected to a Subspace \
+				primary node?",
+		))),
+	}
+}
+
+/// Returns `executor_id` unless it's the all-zero key, which is what the runtime returns when no
+/// executor is configured on the primary chain.
+fn configured_executor_id(executor_id: ExecutorId) -> Option<ExecutorId> {
+	if AsRef::<[u8]>::as_ref(&executor_id).iter().all(|byte| *byte == 0) {
+		None
+	} else {
+		Some(executor_id)
+	}
+}
+
+/// Logs the blocks lacking a stored execution receipt between the oldest kept receipt and the
+/// best block, which stall the receipt matching, e.g., due to the blocks missed by a restart.
+fn log_receipt_gaps<Block, Client>(client: &Client) -> Result<(), sp_blockchain::Error>
+where
+	Block: BlockT,
+	Client: HeaderBackend<Block> + AuxStore,
+{
+	let oldest_receipt_number =
+		match aux_schema::oldest_receipt_number::<_, NumberFor<Block>>(client)? {
+			Some(oldest_receipt_number) => oldest_receipt_number,
+			None => return Ok(()),
 		};
+	let best_number = client.info().best_number;
+
+	let gaps =
+		aux_schema::detect_receipt_gaps::<_, Block>(client, oldest_receipt_number, best_number)?;
+	if !gaps.is_empty() {
+		tracing::warn!(
+			target: LOG_TARGET,
+			?gaps,
+			"Execution receipts are missing for some blocks between #{oldest_receipt_number} and #{best_number}"
+		);
+	}
 
-		// TODO: What happens for this obvious error?
-		if local_receipt.trace.len() != execution_receipt.trace.len() {}
+	Ok(())
+}
 
-		if let Some((local_trace_idx, local_root)) = local_receipt
-			.trace
-			.iter()
-			.enumerate()
-			.zip(execution_receipt.trace.iter().enumerate())
-			.find_map(|((local_idx, local_root), (_, external_root))| {
-				if local_root != external_root {
-					Some((local_idx, local_root))
-				} else {
-					None
-				}
-			}) {
-			let header = self.header(execution_receipt.secondary_hash)?;
-			let parent_header = self.header(*header.parent_hash())?;
-
-			// TODO: avoid the encode & decode?
-			let as_h256 = |state_root: &Block::Hash| {
-				H256::decode(&mut state_root.encode().as_slice())
-					.map_err(|_| Self::Error::InvalidStateRootType)
-			};
+/// Converts the secondary block `number` into the block number used by the fraud proof.
+fn to_block_number<Number: TryInto<BlockNumber>>(
+	number: Number,
+) -> Result<BlockNumber, GossipMessageError> {
+	number.try_into().map_err(|_| GossipMessageError::BlockNumberOverflow)
+}
 
-			let prover = subspace_fraud_proof::ExecutionProver::new(
-				self.backend.clone(),
-				self.code_executor.clone(),
-				self.spawner.clone() as Box<dyn SpawnNamed>,
-			);
+/// Converts the primary block number into the number of the secondary block derived from it,
+/// which fails if the secondary chain uses a narrower number type.
+fn to_secondary_number<Number: Decode>(
+	primary_number: impl Encode,
+) -> Result<Number, GossipMessageError> {
+	Number::decode_all(&mut primary_number.encode().as_slice())
+		.map_err(|_| GossipMessageError::BlockNumberOverflow)
+}
 
-			let parent_number = TryInto::<BlockNumber>::try_into(*parent_header.number())
-				.unwrap_or_else(|_| panic!("Parent number must fit into u32; qed"));
+/// Returns `true` if the receipt of block `block_number` must be verified root by root, i.e., the
+/// block is a checkpoint or the final state root of the receipt diverges from the local one.
+fn requires_full_verification<Number, Hash>(
+	block_number: Number,
+	checkpoint_interval: u32,
+	local_final_root: Option<&Hash>,
+	final_root: Option<&Hash>,
+) -> bool
+where
+	Number: AtLeast32BitUnsigned,
+	Hash: PartialEq,
+{
+	let is_checkpoint = (block_number % checkpoint_interval.max(1).into()).is_zero();
+	is_checkpoint || local_final_root != final_root
+}
 
-			// TODO: abstract the execution proof impl to be reusable in the test.
-			let fraud_proof = if local_trace_idx == 0 {
-				// `initialize_block` execution proof.
-				let pre_state_root = as_h256(parent_header.state_root())?;
-				let post_state_root = as_h256(local_root)?;
+/// Returns `true` if the receipt of block `block_number` is to be compared with the local one,
+/// the blocks are sampled pseudo-randomly at `sampling_rate`, deterministically for `seed`.
+fn is_sampled_for_verification(seed: u64, block_number: u64, sampling_rate: Perbill) -> bool {
+	if sampling_rate == Perbill::one() {
+		return true
+	}
+	let digest = BlakeTwo256::hash_of(&(seed, block_number));
+	let draw = u32::from_le_bytes(
+		digest.as_ref()[..4].try_into().expect("Hash is longer than 4 bytes; qed"),
+	) % Perbill::ACCURACY;
+	draw < sampling_rate.deconstruct()
+}
 
-				let new_header = Block::Header::new(
-					block_number,
-					Default::default(),
-					Default::default(),
-					parent_header.hash(),
-					Default::default(),
-				);
-				let execution_phase =
-					ExecutionPhase::InitializeBlock { call_data: new_header.encode() };
+/// Returns `true` if the bundle `slot` is beyond the `tolerance` of the latest primary slot seen
+/// locally, no slot is considered in the future until the first primary slot is seen.
+fn is_future_slot(slot: u64, latest_slot: Option<Slot>, tolerance: u64) -> bool {
+	latest_slot.map_or(false, |latest_slot| slot > u64::from(latest_slot).saturating_add(tolerance))
+}
 
-				let proof = prover.prove_execution::<TransactionFor<Backend, Block>>(
-					BlockId::Hash(parent_header.hash()),
-					&execution_phase,
-					None,
-				)?;
+/// Runs `prove` once a permit of `provers` is available.
+async fn run_prover<R>(provers: &Semaphore, prove: impl FnOnce() -> R) -> R {
+	let _permit = provers.acquire().await.expect("Provers semaphore is never closed; qed");
+	prove()
+}
 
-				FraudProof {
-					parent_number,
-					parent_hash: as_h256(&parent_header.hash())?,
-					pre_state_root,
-					post_state_root,
-					proof,
-					execution_phase,
-				}
-			} else if local_trace_idx == local_receipt.trace.len() - 1 {
-				// `finalize_block` execution proof.
-				let pre_state_root = as_h256(&execution_receipt.trace[local_trace_idx - 1])?;
-				let post_state_root = as_h256(local_root)?;
-				let execution_phase = ExecutionPhase::FinalizeBlock;
+/// Waiters for the local receipts of the future blocks, at most `max_pending` at a time.
+#[derive(Clone)]
+struct FutureReceiptWaiters {
+	waiters: Arc<Semaphore>,
+	max_pending: usize,
+}
 
-				let block_builder = BlockBuilder::new(
-					&*self.client,
-					parent_header.hash(),
-					*parent_header.number(),
-					RecordProof::No,
-					Default::default(),
-					&*self.backend,
-					self.block_body(execution_receipt.secondary_hash)?,
-				)?;
-				let storage_changes =
-					block_builder.prepare_storage_changes_before_finalize_block()?;
+impl FutureReceiptWaiters {
+	fn new(max_pending: usize) -> Self {
+		Self { waiters: Arc::new(Semaphore::new(max_pending)), max_pending }
+	}
 
-				let delta = storage_changes.transaction;
-				let post_delta_root = storage_changes.transaction_storage_root;
+	/// Reserves one of the slots for waiting for a local future receipt, the slot is released
+	/// once the returned permit is dropped.
+	fn reserve(&self) -> Result<OwnedSemaphorePermit, GossipMessageError> {
+		self.waiters
+			.clone()
+			.try_acquire_owned()
+			.map_err(|_| GossipMessageError::TooManyPendingReceipts { max: self.max_pending })
+	}
+}
 
-				let proof = prover.prove_execution(
-					BlockId::Hash(parent_header.hash()),
-					&execution_phase,
-					Some((delta, post_delta_root)),
-				)?;
+/// Checks once whether the local receipt to compare with the external receipt of the future block
+/// `(secondary_block_hash, secondary_block_number)` is ready, `None` if it's still pending.
+fn poll_local_future_receipt<Block, PBlock, Client>(
+	client: &Client,
+	secondary_block_hash: Block::Hash,
+	secondary_block_number: NumberFor<Block>,
+) -> Option<Result<ExecutionReceiptFor<PBlock, Block::Hash>, GossipMessageError>>
+where
+	Block: BlockT,
+	PBlock: BlockT,
+	Client: HeaderBackend<Block> + AuxStore,
+{
+	let receipt_load_error = |error| GossipMessageError::ReceiptLoad(Box::new(error));
+
+	// The awaited block has been imported locally but a reorg made it orphaned, the local
+	// receipt at the same height is built on another fork and must not be compared with it.
+	match is_block_orphaned(client, secondary_block_hash) {
+		Ok(true) => return Some(Err(GossipMessageError::AwaitedBlockOrphaned)),
+		Ok(false) => {},
+		Err(error) => return Some(Err(receipt_load_error(error))),
+	}
 
-				FraudProof {
-					parent_number,
-					parent_hash: as_h256(&parent_header.hash())?,
-					pre_state_root,
-					post_state_root,
-					proof,
-					execution_phase,
-				}
+	match aux_schema::load_execution_receipt(client, secondary_block_hash) {
+		Ok(Some(local_receipt)) => Some(Ok(local_receipt)),
+		Ok(None) => {
+			// The primary forks are handled by the caller, a receipt whose primary block
+			// has been reorged out is never disputed, see `on_execution_receipt`.
+			//
+			// Whether or not the best execution chain number on primary chain has been
+			// updated, the local client has proceeded to a higher block, that means the receipt
+			// of `block_hash` received from the network does not match the local one,
+			// we should just send back the local receipt at the same height.
+			if client.info().best_number >= secondary_block_number {
+				Some(
+					load_local_receipt_at::<Block, PBlock, _>(client, secondary_block_number)
+						.map_err(receipt_load_error),
+				)
 			} else {
-				// Regular extrinsic execution proof.
-				let pre_state_root = as_h256(&execution_receipt.trace[local_trace_idx - 1])?;
-				let post_state_root = as_h256(local_root)?;
-
-				let (proof, execution_phase) = self.create_extrinsic_execution_proof(
-					local_trace_idx - 1,
-					&parent_header,
-					execution_receipt.secondary_hash,
-					&prover,
-				)?;
+				None
+			}
+		},
+		Err(error) => Some(Err(receipt_load_error(error))),
+	}
+}
 
-				// TODO: proof should be a CompactProof.
-				FraudProof {
-					parent_number,
-					parent_hash: as_h256(&parent_header.hash())?,
-					pre_state_root,
-					post_state_root,
-					proof,
-					execution_phase,
-				}
-			};
+fn load_local_receipt_at<Block, PBlock, Client>(
+	client: &Client,
+	secondary_block_number: NumberFor<Block>,
+) -> sp_blockchain::Result<ExecutionReceiptFor<PBlock, Block::Hash>>
+where
+	Block: BlockT,
+	PBlock: BlockT,
+	Client: HeaderBackend<Block> + AuxStore,
+{
+	let local_block_hash =
+		client.expect_block_hash_from_id(&BlockId::Number(secondary_block_number))?;
+	aux_schema::load_execution_receipt(client, local_block_hash)?.ok_or_else(|| {
+		sp_blockchain::Error::Backend(format!(
+			"Execution receipt not found for {:?}",
+			local_block_hash
+		))
+	})
+}
 
-			self.submit_fraud_proof(fraud_proof);
+/// Calls `poll` every `interval` until it returns `Some`.
+async fn poll_until_ready<T>(interval: Duration, mut poll: impl FnMut() -> Option<T>) -> T {
+	loop {
+		if let Some(ready) = poll() {
+			return ready
+		}
+		tokio::time::sleep(interval).await;
+	}
+}
 
-			Ok(Action::Empty)
-		} else {
-			Ok(Action::RebroadcastExecutionReceipt)
+/// Waits until the block is no longer queued for import, for at most `timeout`.
+///
+/// The block status is rechecked on each notification from `import_notifications`.
+async fn wait_for_queued_block<S>(
+	block_status: impl Fn() -> sp_blockchain::Result<BlockStatus>,
+	mut import_notifications: S,
+	timeout: Duration,
+) where
+	S: Stream + Unpin,
+{
+	let imported = async {
+		while matches!(block_status(), Ok(BlockStatus::Queued)) {
+			if import_notifications.next().await.is_none() {
+				break
+			}
 		}
+	};
+
+	futures::select! {
+		_ = Box::pin(imported.fuse()) => {},
+		_ = futures_timer::Delay::new(timeout).fuse() => {},
+	}
+}
+
+/// Returns the extrinsic at `index` of a block body.
+fn extrinsic_at<Extrinsic>(
+	extrinsics: &[Extrinsic],
+	index: usize,
+) -> Result<&Extrinsic, GossipMessageError> {
+	extrinsics.get(index).ok_or(GossipMessageError::InvalidExtrinsicIndex {
+		index,
+		max: extrinsics.len().saturating_sub(1),
+	})
+}
+
+/// Returns an error unless the block with `block_status` has its state available, e.g., the state
+/// has not been pruned.
+fn ensure_state_available(
+	block_status: sp_blockchain::Result<BlockStatus>,
+) -> Result<(), GossipMessageError> {
+	match block_status? {
+		BlockStatus::InChainWithState => Ok(()),
+		status => Err(GossipMessageError::ParentStateUnavailable { status }),
+	}
+}
+
+/// Returns the hash of the bundle including the extrinsic encoded as `encoded_extrinsic` and the
+/// index of the extrinsic in it.
+///
+/// An opaque extrinsic encodes to the same bytes as the extrinsic it wraps, the encodings are
+/// compared to not decode the extrinsic.
+fn locate_in_bundles(bundles: &[OpaqueBundle], encoded_extrinsic: &[u8]) -> Option<(H256, u32)> {
+	bundles.iter().find_map(|bundle| {
+		bundle
+			.opaque_extrinsics
+			.iter()
+			.position(|xt| xt.encode() == encoded_extrinsic)
+			.map(|index| (bundle.hash(), index as u32))
+	})
+}
+
+/// Returns the length of the trace of a block with `extrinsics` extrinsics, which has a root per
+/// extrinsic plus the roots of initializing and finalizing the block.
+fn expected_trace_len(extrinsics: usize) -> usize {
+	extrinsics + 2
+}
+
+/// Rejects the trace of `len` roots if it's longer than `max`.
+fn ensure_trace_len(len: usize, max: usize) -> Result<(), GossipMessageError> {
+	if len > max {
+		return Err(GossipMessageError::TraceTooLong { len, max })
+	}
+	Ok(())
+}
+
+/// Returns the execution phase producing the trace root `local_trace_idx` of a trace of
+/// `trace_len` roots.
+fn fraud_proof_phase(local_trace_idx: usize, trace_len: usize) -> FraudProofPhase {
+	if local_trace_idx == 0 {
+		FraudProofPhase::InitializeBlock
+	} else if local_trace_idx == trace_len - 1 {
+		FraudProofPhase::FinalizeBlock
+	} else {
+		FraudProofPhase::ApplyExtrinsic(local_trace_idx - 1)
+	}
+}
+
+/// Logs where the external receipt diverges from the local one before the fraud proof is
+/// constructed, which is the context needed for the post-mortem analysis of the dispute.
+fn log_trace_divergence<Number: fmt::Debug, PHash: fmt::Debug, Hash: fmt::Debug>(
+	execution_receipt: &ExecutionReceipt<Number, PHash, Hash>,
+	local_trace_idx: usize,
+	phase: FraudProofPhase,
+	local_root: &Hash,
+) {
+	tracing::info!(
+		target: LOG_TARGET,
+		local_trace_idx,
+		?phase,
+		?local_root,
+		external_root = ?execution_receipt.trace.get(local_trace_idx),
+		block_number = ?execution_receipt.primary_number,
+		primary_hash = ?execution_receipt.primary_hash,
+		"Execution receipt diverges from the local one",
+	);
+}
+
+/// Returns `true` if the block `(number, hash)` is on the current canonical chain of `client`.
+fn is_canonical<Block, Client>(
+	client: &Client,
+	number: NumberFor<Block>,
+	hash: Block::Hash,
+) -> Result<bool, sp_blockchain::Error>
+where
+	Block: BlockT,
+	Client: HeaderBackend<Block>,
+{
+	Ok(client.hash(number)? == Some(hash))
+}
+
+/// Returns `true` if the block `hash` is known and on the current canonical chain of `client`.
+fn is_block_canonical<Block, Client>(
+	client: &Client,
+	hash: Block::Hash,
+) -> Result<bool, sp_blockchain::Error>
+where
+	Block: BlockT,
+	Client: HeaderBackend<Block>,
+{
+	match client.number(hash)? {
+		Some(number) => is_canonical(client, number, hash),
+		None => Ok(false),
 	}
 }
 
+/// Returns `true` if the block `hash` is known but no longer on the canonical chain of `client`.
+fn is_block_orphaned<Block, Client>(
+	client: &Client,
+	hash: Block::Hash,
+) -> Result<bool, sp_blockchain::Error>
+where
+	Block: BlockT,
+	Client: HeaderBackend<Block>,
+{
+	Ok(client.number(hash)?.is_some() && !is_block_canonical(client, hash)?)
+}
+
+/// Sorts the leaves in ascending order of the block number, the leaves at the same height are
+/// ordered by the block hash so that the order is reproducible for the same set of leaves.
+fn sort_leaves<PBlock: BlockT>(leaves: &mut [BlockInfo<PBlock>]) {
+	leaves.sort_by(|a, b| a.number.cmp(&b.number).then_with(|| a.hash.cmp(&b.hash)));
+}
+
 /// Returns the active leaves the overseer should start with.
 async fn active_leaves<PBlock, PClient, SC>(
 	client: &PClient,
@@ -899,7 +3104,7 @@ where
 		.collect::<Vec<_>>();
 
 	// Sort by block number and get the maximum number of leaves
-	leaves.sort_by_key(|b| b.number);
+	sort_leaves(&mut leaves);
 
 	leaves.push(BlockInfo {
 		hash: best_block.hash(),
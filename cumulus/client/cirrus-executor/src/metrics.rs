@@ -0,0 +1,53 @@
+//! Prometheus metrics of the executor.
+
+use sp_executor::ExecutionPhase;
+use std::time::Duration;
+use substrate_prometheus_endpoint::{
+	register, HistogramOpts, HistogramVec, PrometheusError, Registry,
+};
+
+/// Returns the label of `execution_phase` in the metrics.
+pub(crate) fn phase_label(execution_phase: &ExecutionPhase) -> &'static str {
+	match execution_phase {
+		ExecutionPhase::InitializeBlock { .. } => "initialize_block",
+		ExecutionPhase::ApplyExtrinsic { .. } => "apply_extrinsic",
+		ExecutionPhase::FinalizeBlock => "finalize_block",
+	}
+}
+
+/// Histograms of the time spent on constructing the execution proofs.
+#[derive(Clone)]
+pub(crate) struct ProvingMetrics {
+	proving_time: HistogramVec,
+}
+
+impl ProvingMetrics {
+	pub(crate) fn register(registry: &Registry) -> Result<Self, PrometheusError> {
+		Ok(Self {
+			proving_time: register(
+				HistogramVec::new(
+					HistogramOpts::new(
+						"cirrus_execution_proof_time",
+						"Time taken to construct an execution proof, in seconds",
+					),
+					&["phase"],
+				)?,
+				registry,
+			)?,
+		})
+	}
+
+	/// Records that proving `execution_phase` took `elapsed`.
+	pub(crate) fn observe(&self, execution_phase: &ExecutionPhase, elapsed: Duration) {
+		self.proving_time
+			.with_label_values(&[phase_label(execution_phase)])
+			.observe(elapsed.as_secs_f64());
+	}
+
+	#[cfg(test)]
+	pub(crate) fn observations(&self, execution_phase: &ExecutionPhase) -> u64 {
+		self.proving_time
+			.with_label_values(&[phase_label(execution_phase)])
+			.get_sample_count()
+	}
+}
@@ -0,0 +1,225 @@
+//! Submission of the proofs produced by executor to the primary chain.
+
+use codec::{Decode, Encode};
+use sp_api::{ApiError, ProvideRuntimeApi};
+use sp_executor::{BundleEquivocationProof, ExecutorApi, FraudProof, InvalidTransactionProof};
+use sp_runtime::{generic::BlockId, traits::Block as BlockT};
+use std::{marker::PhantomData, sync::Arc, time::Duration};
+
+/// Transport of the proofs produced by executor to the primary chain.
+///
+/// Each submission is made at the primary block `at`, the failed submissions are retried by the
+/// caller according to the [`SubmissionRetry`] policy.
+pub trait ProofSubmitter<PBlock: BlockT>: Send + Sync {
+	/// Submits the fraud proof.
+	fn submit_fraud_proof(
+		&self,
+		at: &BlockId<PBlock>,
+		fraud_proof: FraudProof,
+	) -> Result<(), ApiError>;
+
+	/// Submits the bundle equivocation proof.
+	fn submit_bundle_equivocation_proof(
+		&self,
+		at: &BlockId<PBlock>,
+		bundle_equivocation_proof: BundleEquivocationProof,
+	) -> Result<(), ApiError>;
+
+	/// Submits the invalid transaction proof.
+	fn submit_invalid_transaction_proof(
+		&self,
+		at: &BlockId<PBlock>,
+		invalid_transaction_proof: InvalidTransactionProof,
+	) -> Result<(), ApiError>;
+}
+
+/// [`ProofSubmitter`] submitting the proofs as the unsigned extrinsics via the [`ExecutorApi`] of
+/// the local primary client, `SecondaryHash` is the block hash type of the secondary chain.
+pub struct PrimaryChainProofSubmitter<PClient, SecondaryHash> {
+	primary_chain_client: Arc<PClient>,
+	_phantom: PhantomData<fn() -> SecondaryHash>,
+}
+
+impl<PClient, SecondaryHash> PrimaryChainProofSubmitter<PClient, SecondaryHash> {
+	/// Creates a submitter using `primary_chain_client`.
+	pub fn new(primary_chain_client: Arc<PClient>) -> Self {
+		Self { primary_chain_client, _phantom: PhantomData }
+	}
+}
+
+impl<PBlock, PClient, SecondaryHash> ProofSubmitter<PBlock>
+	for PrimaryChainProofSubmitter<PClient, SecondaryHash>
+where
+	PBlock: BlockT,
+	PClient: ProvideRuntimeApi<PBlock> + Send + Sync,
+	PClient::Api: ExecutorApi<PBlock, SecondaryHash>,
+	SecondaryHash: Encode + Decode,
+{
+	fn submit_fraud_proof(
+		&self,
+		at: &BlockId<PBlock>,
+		fraud_proof: FraudProof,
+	) -> Result<(), ApiError> {
+		self.primary_chain_client
+			.runtime_api()
+			.submit_fraud_proof_unsigned(at, fraud_proof)
+	}
+
+	fn submit_bundle_equivocation_proof(
+		&self,
+		at: &BlockId<PBlock>,
+		bundle_equivocation_proof: BundleEquivocationProof,
+	) -> Result<(), ApiError> {
+		self.primary_chain_client
+			.runtime_api()
+			.submit_bundle_equivocation_proof_unsigned(at, bundle_equivocation_proof)
+	}
+
+	fn submit_invalid_transaction_proof(
+		&self,
+		at: &BlockId<PBlock>,
+		invalid_transaction_proof: InvalidTransactionProof,
+	) -> Result<(), ApiError> {
+		self.primary_chain_client
+			.runtime_api()
+			.submit_invalid_transaction_proof_unsigned(at, invalid_transaction_proof)
+	}
+}
+
+/// Retry policy for submitting the proofs to the primary chain.
+#[derive(Debug, Clone, Copy)]
+pub struct SubmissionRetry {
+	/// Maximum number of attempts, including the first one.
+	pub max_attempts: u32,
+	/// Delay before the first retry, doubled on each subsequent retry.
+	pub base_delay: Duration,
+}
+
+impl Default for SubmissionRetry {
+	fn default() -> Self {
+		Self { max_attempts: 3, base_delay: Duration::from_millis(500) }
+	}
+}
+
+/// Error of submitting a proof to the primary chain.
+#[derive(Debug, thiserror::Error)]
+pub enum SubmitError {
+	/// The proof submission is disabled in the executor config.
+	#[error("Proof submission is disabled")]
+	Disabled,
+	/// The runtime call submitting the proof failed.
+	#[error("Failed to submit the proof: {0}")]
+	RuntimeApi(ApiError),
+}
+
+/// Returns `true` if the runtime call can never succeed, no matter how many times it's retried.
+fn is_permanent_error(error: &ApiError) -> bool {
+	matches!(
+		error,
+		ApiError::FailedToDecodeReturnValue { .. } |
+			ApiError::FailedToConvertReturnValue { .. } |
+			ApiError::FailedToConvertParameter { .. }
+	)
+}
+
+/// Returns the block at which the proof is submitted, `pinned` if any, otherwise the current best
+/// block returned by `best_hash`.
+pub(crate) fn submission_at<Block: BlockT>(
+	pinned: Option<BlockId<Block>>,
+	best_hash: impl FnOnce() -> Block::Hash,
+) -> BlockId<Block> {
+	pinned.unwrap_or_else(|| BlockId::Hash(best_hash()))
+}
+
+/// Calls `submit` until it succeeds, retrying on the transient errors with an exponential backoff.
+pub(crate) async fn submit_with_retry<F>(retry: SubmissionRetry, submit: F) -> Result<(), ApiError>
+where
+	F: Fn() -> Result<(), ApiError>,
+{
+	let mut attempt = 1;
+	loop {
+		match submit() {
+			Ok(()) => return Ok(()),
+			Err(error) if is_permanent_error(&error) || attempt >= retry.max_attempts =>
+				return Err(error),
+			Err(error) => {
+				let delay = retry.base_delay.saturating_mul(1 << (attempt - 1).min(16));
+				tracing::debug!(
+					target: crate::LOG_TARGET,
+					?error,
+					attempt,
+					?delay,
+					"Failed to submit the proof, retrying"
+				);
+				futures_timer::Delay::new(delay).await;
+				attempt += 1;
+			},
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::sync::atomic::{AtomicU32, Ordering};
+	use substrate_test_runtime_client::runtime::{Block, Hash};
+
+	const RETRY: SubmissionRetry =
+		SubmissionRetry { max_attempts: 3, base_delay: Duration::from_millis(1) };
+
+	fn transient_error() -> ApiError {
+		ApiError::Application(Box::from("State not available"))
+	}
+
+	#[test]
+	fn submission_should_succeed_after_transient_failures() {
+		let attempts = AtomicU32::new(0);
+		let result = futures::executor::block_on(submit_with_retry(RETRY, || {
+			if attempts.fetch_add(1, Ordering::SeqCst) < 2 {
+				Err(transient_error())
+			} else {
+				Ok(())
+			}
+		}));
+		assert!(result.is_ok());
+		assert_eq!(attempts.load(Ordering::SeqCst), 3);
+	}
+
+	#[test]
+	fn submission_should_give_up_after_max_attempts() {
+		let attempts = AtomicU32::new(0);
+		let result = futures::executor::block_on(submit_with_retry(RETRY, || {
+			attempts.fetch_add(1, Ordering::SeqCst);
+			Err(transient_error())
+		}));
+		assert!(result.is_err());
+		assert_eq!(attempts.load(Ordering::SeqCst), RETRY.max_attempts);
+	}
+
+	#[test]
+	fn permanent_error_should_not_be_retried() {
+		let attempts = AtomicU32::new(0);
+		let result = futures::executor::block_on(submit_with_retry(RETRY, || {
+			attempts.fetch_add(1, Ordering::SeqCst);
+			Err(ApiError::FailedToConvertParameter {
+				function: "submit_fraud_proof_unsigned",
+				parameter: "fraud_proof",
+				error: "Invalid".into(),
+			})
+		}));
+		assert!(result.is_err());
+		assert_eq!(attempts.load(Ordering::SeqCst), 1);
+	}
+
+	#[test]
+	fn pinned_submission_block_should_be_used() {
+		let pinned = Hash::repeat_byte(1);
+		let at = submission_at::<Block>(Some(BlockId::Hash(pinned)), || {
+			panic!("Best hash must not be queried for a pinned submission")
+		});
+		assert_eq!(at, BlockId::Hash(pinned));
+
+		let best = Hash::repeat_byte(2);
+		assert_eq!(submission_at::<Block>(None, || best), BlockId::Hash(best));
+	}
+}
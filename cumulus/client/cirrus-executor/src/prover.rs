@@ -0,0 +1,80 @@
+//! Abstraction of the execution prover used for constructing the fraud proofs.
+
+use sc_executor::error::Error as ExecutorError;
+use sp_core::traits::CodeExecutor;
+use sp_executor::ExecutionPhase;
+use sp_runtime::{
+	generic::BlockId,
+	traits::{Block as BlockT, HashFor},
+};
+use sp_trie::{DBValue, HashDBT, StorageProof};
+use subspace_fraud_proof::ExecutionProver;
+
+/// Error of proving an execution phase.
+#[derive(Debug, thiserror::Error)]
+pub(crate) enum ProvingError {
+	/// The runtime traps or panics while executing the phase.
+	#[error("Execution traps: {0}")]
+	ExecutionTrap(ExecutorError),
+	/// The phase can't be proven, e.g., the state is unavailable.
+	#[error(transparent)]
+	Client(#[from] sp_blockchain::Error),
+}
+
+impl ProvingError {
+	/// Converts into the client error, a trap outside of an extrinsic, e.g., in
+	/// `initialize_block`, isn't the evidence of any invalid transaction.
+	pub(crate) fn into_client_error(self) -> sp_blockchain::Error {
+		match self {
+			Self::ExecutionTrap(error) => sp_blockchain::Error::Execution(Box::new(error)),
+			Self::Client(error) => error,
+		}
+	}
+}
+
+/// Creates the storage proof of an execution phase, `DB` is the type of the storage changes
+/// applied on top of the state at `at` before the execution.
+pub(crate) trait Prover<Block: BlockT, DB> {
+	/// Returns the storage proof of executing `execution_phase` on top of the state at `at`.
+	fn prove_execution(
+		&self,
+		at: BlockId<Block>,
+		execution_phase: &ExecutionPhase,
+		delta_changes: Option<(DB, Block::Hash)>,
+	) -> Result<StorageProof, ProvingError>;
+}
+
+impl<Block, B, Exec, DB> Prover<Block, DB> for ExecutionProver<Block, B, Exec>
+where
+	Block: BlockT,
+	B: sc_client_api::Backend<Block>,
+	Exec: CodeExecutor<Error = ExecutorError> + 'static,
+	DB: HashDBT<HashFor<Block>, DBValue>,
+{
+	fn prove_execution(
+		&self,
+		at: BlockId<Block>,
+		execution_phase: &ExecutionPhase,
+		delta_changes: Option<(DB, Block::Hash)>,
+	) -> Result<StorageProof, ProvingError> {
+		let (result, proof) =
+			ExecutionProver::prove_execution_recording(self, at, execution_phase, delta_changes)?;
+
+		match result {
+			Ok(()) => Ok(proof),
+			Err(error) if is_execution_trap(&error) => Err(ProvingError::ExecutionTrap(error)),
+			Err(error) => Err(ProvingError::Client(sp_blockchain::Error::Execution(Box::new(error)))),
+		}
+	}
+}
+
+/// Returns `true` if the executor `error` is caused by the runtime trapping or panicking while
+/// executing the call, rather than by an infrastructure failure, e.g., a missing runtime code.
+pub(crate) fn is_execution_trap(error: &ExecutorError) -> bool {
+	matches!(
+		error,
+		ExecutorError::AbortedDueToTrap(_) |
+			ExecutorError::AbortedDueToPanic(_) |
+			ExecutorError::RuntimePanicked(_)
+	)
+}
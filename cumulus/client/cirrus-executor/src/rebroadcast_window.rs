@@ -0,0 +1,70 @@
+//! Suppression of the repeated rebroadcasts of the same gossip message within a time window.
+
+use parking_lot::Mutex;
+use sp_core::H256;
+use std::{
+	collections::{hash_map::Entry, HashMap},
+	time::{Duration, Instant},
+};
+
+/// Time-keyed set of the recently rebroadcast messages.
+pub(crate) struct RebroadcastWindow {
+	window: Duration,
+	rebroadcast_at: Mutex<HashMap<H256, Instant>>,
+}
+
+impl RebroadcastWindow {
+	pub(crate) fn new(window: Duration) -> Self {
+		Self { window, rebroadcast_at: Mutex::new(HashMap::new()) }
+	}
+
+	/// Returns `true` if the message `hash` received at `now` is to be rebroadcast, i.e., it
+	/// hasn't been rebroadcast within the window.
+	pub(crate) fn try_rebroadcast(&self, hash: H256, now: Instant) -> bool {
+		let mut rebroadcast_at = self.rebroadcast_at.lock();
+
+		// Forget the messages whose window has passed, which keeps the set small.
+		rebroadcast_at.retain(|_, at| now.saturating_duration_since(*at) < self.window);
+
+		match rebroadcast_at.entry(hash) {
+			Entry::Occupied(_) => false,
+			Entry::Vacant(entry) => {
+				entry.insert(now);
+				true
+			},
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const WINDOW: Duration = Duration::from_secs(2);
+
+	#[test]
+	fn rebroadcast_within_the_window_should_be_suppressed() {
+		let rebroadcast_window = RebroadcastWindow::new(WINDOW);
+		let now = Instant::now();
+		let hash = H256::repeat_byte(1);
+
+		assert!(rebroadcast_window.try_rebroadcast(hash, now));
+		assert!(!rebroadcast_window.try_rebroadcast(hash, now + Duration::from_secs(1)));
+
+		// Other messages are not affected.
+		assert!(rebroadcast_window.try_rebroadcast(H256::repeat_byte(2), now));
+
+		// The message is rebroadcast again once the window has passed.
+		assert!(rebroadcast_window.try_rebroadcast(hash, now + WINDOW));
+	}
+
+	#[test]
+	fn zero_window_should_not_suppress_any_rebroadcast() {
+		let rebroadcast_window = RebroadcastWindow::new(Duration::ZERO);
+		let now = Instant::now();
+		let hash = H256::repeat_byte(1);
+
+		assert!(rebroadcast_window.try_rebroadcast(hash, now));
+		assert!(rebroadcast_window.try_rebroadcast(hash, now));
+	}
+}
@@ -0,0 +1,111 @@
+//! Comparison of the execution traces of two receipts of the same block.
+
+use sp_executor::ExecutionReceipt;
+
+/// Difference between the execution traces of a local and a remote receipt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReceiptDiff<Hash> {
+	/// Index of the first root differing between the traces, `None` if the roots both traces
+	/// have match.
+	pub first_divergent_index: Option<usize>,
+	/// Whether the traces are of the different lengths.
+	pub lengths_differ: bool,
+	/// Local and remote roots at `first_divergent_index`.
+	pub divergent_roots: Option<(Hash, Hash)>,
+}
+
+impl<Hash> ReceiptDiff<Hash> {
+	/// Returns `true` if the traces are identical.
+	pub fn is_identical(&self) -> bool {
+		self.first_divergent_index.is_none() && !self.lengths_differ
+	}
+}
+
+/// Compares the execution trace of the `local` receipt with the one of the `remote` receipt.
+///
+/// It only inspects the traces, nothing is loaded or proved, which suits the tooling inspecting a
+/// dispute.
+pub fn diff_receipts<Number, PHash, Hash: Clone + PartialEq>(
+	local: &ExecutionReceipt<Number, PHash, Hash>,
+	remote: &ExecutionReceipt<Number, PHash, Hash>,
+) -> ReceiptDiff<Hash> {
+	let first_divergent_index = local
+		.trace
+		.iter()
+		.zip(remote.trace.iter())
+		.position(|(local_root, remote_root)| local_root != remote_root);
+	ReceiptDiff {
+		first_divergent_index,
+		lengths_differ: local.trace.len() != remote.trace.len(),
+		divergent_roots: first_divergent_index
+			.map(|idx| (local.trace[idx].clone(), remote.trace[idx].clone())),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use sp_core::H256;
+
+	fn receipt(trace: Vec<H256>) -> ExecutionReceipt<u32, H256, H256> {
+		ExecutionReceipt {
+			primary_number: 1,
+			primary_hash: H256::repeat_byte(1),
+			secondary_hash: H256::repeat_byte(2),
+			trace,
+			trace_root: Default::default(),
+		}
+	}
+
+	fn roots(bytes: &[u8]) -> Vec<H256> {
+		bytes.iter().copied().map(H256::repeat_byte).collect()
+	}
+
+	#[test]
+	fn matching_receipts_should_be_identical() {
+		let local = receipt(roots(&[1, 2, 3]));
+
+		let diff = diff_receipts(&local, &local.clone());
+		assert_eq!(
+			diff,
+			ReceiptDiff {
+				first_divergent_index: None,
+				lengths_differ: false,
+				divergent_roots: None
+			}
+		);
+		assert!(diff.is_identical());
+	}
+
+	#[test]
+	fn length_mismatch_should_be_reported() {
+		let diff = diff_receipts(&receipt(roots(&[1, 2, 3])), &receipt(roots(&[1, 2])));
+		assert_eq!(
+			diff,
+			ReceiptDiff {
+				first_divergent_index: None,
+				lengths_differ: true,
+				divergent_roots: None
+			}
+		);
+		assert!(!diff.is_identical());
+
+		let diff = diff_receipts(&receipt(roots(&[1, 2, 3])), &receipt(roots(&[1, 4])));
+		assert_eq!(diff.first_divergent_index, Some(1));
+		assert!(diff.lengths_differ);
+	}
+
+	#[test]
+	fn single_root_divergence_should_be_located() {
+		let diff = diff_receipts(&receipt(roots(&[1, 2, 3, 4])), &receipt(roots(&[1, 2, 5, 4])));
+		assert_eq!(
+			diff,
+			ReceiptDiff {
+				first_divergent_index: Some(2),
+				lengths_differ: false,
+				divergent_roots: Some((H256::repeat_byte(3), H256::repeat_byte(5))),
+			}
+		);
+		assert!(!diff.is_identical());
+	}
+}
@@ -0,0 +1,70 @@
+//! JSON representation of the execution receipts for RPC.
+
+use sp_core::bytes::to_hex;
+use sp_executor::SignedExecutionReceipt;
+use sp_runtime::traits::UniqueSaturatedInto;
+
+/// Converts a signed execution receipt into its stable JSON representation.
+///
+/// The block number is a JSON number, the hashes, the trace roots, the signer and the signature
+/// are `0x`-prefixed hex strings.
+pub fn receipt_to_json<Number, Hash, SecondaryHash>(
+	signed_execution_receipt: &SignedExecutionReceipt<Number, Hash, SecondaryHash>,
+) -> serde_json::Value
+where
+	Number: UniqueSaturatedInto<u64> + Copy,
+	Hash: AsRef<[u8]>,
+	SecondaryHash: AsRef<[u8]>,
+{
+	let SignedExecutionReceipt { execution_receipt, signature, signer } = signed_execution_receipt;
+	let primary_number: u64 = execution_receipt.primary_number.unique_saturated_into();
+
+	serde_json::json!({
+		"primaryNumber": primary_number,
+		"primaryHash": to_hex(execution_receipt.primary_hash.as_ref(), false),
+		"secondaryHash": to_hex(execution_receipt.secondary_hash.as_ref(), false),
+		"trace": execution_receipt
+			.trace
+			.iter()
+			.map(|root| to_hex(root.as_ref(), false))
+			.collect::<Vec<_>>(),
+		"traceRoot": to_hex(&execution_receipt.trace_root, false),
+		"signer": to_hex(signer.as_ref(), false),
+		"signature": to_hex(signature.as_ref(), false),
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use sp_core::{Pair, H256};
+	use sp_executor::{ExecutionReceipt, ExecutorPair};
+
+	#[test]
+	fn receipt_should_be_converted_to_json() {
+		let pair = ExecutorPair::from_string("//Alice", None).unwrap();
+		let execution_receipt = ExecutionReceipt {
+			primary_number: 7u32,
+			primary_hash: H256::repeat_byte(0x01),
+			secondary_hash: H256::repeat_byte(0x02),
+			trace: vec![H256::repeat_byte(0xab), H256::zero()],
+			trace_root: [0x03; 32],
+		};
+		let signed_execution_receipt = SignedExecutionReceipt {
+			signature: pair.sign(execution_receipt.hash().as_ref()),
+			signer: pair.public(),
+			execution_receipt,
+		};
+
+		let hex_of = |byte: &str| format!("0x{}", byte.repeat(32));
+		let json = receipt_to_json(&signed_execution_receipt);
+		assert_eq!(json["primaryNumber"], 7);
+		assert_eq!(json["primaryHash"], hex_of("01"));
+		assert_eq!(json["secondaryHash"], hex_of("02"));
+		assert_eq!(json["trace"], serde_json::json!([hex_of("ab"), hex_of("00")]));
+		assert_eq!(json["traceRoot"], hex_of("03"));
+		assert_eq!(json["signer"], to_hex(signed_execution_receipt.signer.as_ref(), false));
+		assert_eq!(json["signature"].as_str().unwrap().len(), 2 + 64 * 2);
+		assert_eq!(json.as_object().unwrap().len(), 7);
+	}
+}
@@ -1,25 +1,95 @@
 use cirrus_block_builder::{BlockBuilder, RecordProof};
+use cirrus_client_executor_gossip::{GossipMessageHandler, PeerReputation};
 use cirrus_primitives::{BlockNumber, Hash, SecondaryApi};
 use cirrus_test_service::{
 	run_primary_chain_validator_node,
 	runtime::Header,
-	Keyring::{Alice, Bob, Charlie, Dave, Ferdie},
+	Keyring::{Alice, Bob, Charlie, Dave, Ferdie, One},
 };
 use codec::Encode;
+use futures::StreamExt;
+use pallet_transaction_payment_rpc_runtime_api::TransactionPaymentApi;
 use sc_client_api::{Backend, HeaderBackend, StateBackend, StorageProof};
 use sc_service::Role;
 use sc_transaction_pool_api::TransactionSource;
 use sp_api::ProvideRuntimeApi;
+use sp_consensus::BlockStatus;
 use sp_core::{traits::FetchRuntimeCode, Pair};
 use sp_executor::{
-	BundleHeader, ExecutionPhase, ExecutorPair, FraudProof, OpaqueBundle, SignedExecutionReceipt,
+	Bundle, BundleEquivocationProof, BundleHeader, DomainId, ExecutionPhase, ExecutionReceipt,
+	ExecutorPair, FraudProof, InvalidTransactionProof, OpaqueBundle, SignedBundle,
+	SignedExecutionReceipt,
 };
 use sp_runtime::{
 	generic::{BlockId, DigestItem},
 	traits::{BlakeTwo256, Hash as HashT, Header as HeaderT},
 	OpaqueExtrinsic,
 };
-use std::collections::HashSet;
+use std::{collections::HashSet, time::Duration};
+use subspace_test_service::PrimaryTestNode;
+
+/// Starts Ferdie, the primary chain validator, and Alice, a secondary chain authority node
+/// connected to Ferdie and running with `executor_config`.
+async fn setup_ferdie_and_alice(
+	executor_config: crate::ExecutorConfig,
+) -> (PrimaryTestNode, cirrus_test_service::TestNode) {
+	let mut builder = sc_cli::LoggerBuilder::new("");
+	builder.with_colors(false);
+	let _ = builder.init();
+
+	let tokio_handle = tokio::runtime::Handle::current();
+
+	// Start Ferdie
+	let (ferdie, ferdie_network_starter) =
+		run_primary_chain_validator_node(tokio_handle.clone(), Ferdie, vec![]);
+	ferdie_network_starter.start_network();
+
+	// Run Alice (a secondary chain authority node)
+	let alice = cirrus_test_service::TestNodeBuilder::new(tokio_handle, Alice)
+		.connect_to_primary_chain_node(&ferdie)
+		.executor_config(executor_config)
+		.build(Role::Authority)
+		.await;
+
+	(ferdie, alice)
+}
+
+/// Submitter recording the submitted proofs instead of submitting them.
+#[derive(Default)]
+struct RecordingSubmitter {
+	fraud_proofs: parking_lot::Mutex<Vec<FraudProof>>,
+	bundle_equivocation_proofs: parking_lot::Mutex<Vec<BundleEquivocationProof>>,
+	invalid_transaction_proofs: parking_lot::Mutex<Vec<InvalidTransactionProof>>,
+}
+
+impl crate::ProofSubmitter<subspace_runtime_primitives::opaque::Block> for RecordingSubmitter {
+	fn submit_fraud_proof(
+		&self,
+		_at: &BlockId<subspace_runtime_primitives::opaque::Block>,
+		fraud_proof: FraudProof,
+	) -> Result<(), sp_api::ApiError> {
+		self.fraud_proofs.lock().push(fraud_proof);
+		Ok(())
+	}
+
+	fn submit_bundle_equivocation_proof(
+		&self,
+		_at: &BlockId<subspace_runtime_primitives::opaque::Block>,
+		bundle_equivocation_proof: BundleEquivocationProof,
+	) -> Result<(), sp_api::ApiError> {
+		self.bundle_equivocation_proofs.lock().push(bundle_equivocation_proof);
+		Ok(())
+	}
+
+	fn submit_invalid_transaction_proof(
+		&self,
+		_at: &BlockId<subspace_runtime_primitives::opaque::Block>,
+		invalid_transaction_proof: InvalidTransactionProof,
+	) -> Result<(), sp_api::ApiError> {
+		self.invalid_transaction_proofs.lock().push(invalid_transaction_proof);
+		Ok(())
+	}
+}
 
 #[substrate_test_utils::test(flavor = "multi_thread")]
 async fn test_executor_full_node_catching_up() {
@@ -141,6 +211,7 @@ async fn execution_proof_creation_and_verification_should_work() {
 
 	let bundles = vec![OpaqueBundle {
 		header: BundleHeader {
+			domain_id: DomainId::SYSTEM,
 			primary_hash: ferdie.client.info().best_hash,
 			slot_number: Default::default(),
 			extrinsics_root: Default::default(),
@@ -691,3 +762,3525 @@ async fn pallet_executor_unsigned_extrinsics_should_work() {
 	alice.wait_for_blocks(5).await;
 	assert!(ready_txs().is_empty());
 }
+
+#[substrate_test_utils::test(flavor = "multi_thread")]
+async fn bundle_extrinsic_legality_check_should_work() {
+	let (ferdie, alice) = setup_ferdie_and_alice(Default::default()).await;
+
+	alice.wait_for_blocks(3).await;
+
+	let primary_hash = ferdie.client.info().best_hash;
+	let bundle_hash = Hash::random();
+
+	// A legal transaction which has never been seen by the local transaction pool.
+	let legal_tx = cirrus_test_service::construct_extrinsic(
+		&alice.client,
+		pallet_balances::Call::transfer {
+			dest: cirrus_test_service::runtime::Address::Id(Charlie.public().into()),
+			value: 8,
+		},
+		Alice,
+		false,
+		0,
+	);
+	assert!(alice
+		.executor
+		.check_bundle_extrinsic(primary_hash, bundle_hash, 0, &legal_tx)
+		.unwrap()
+		.is_none());
+
+	// The sender has no funds to pay the transaction fee.
+	let illegal_tx = cirrus_test_service::construct_extrinsic(
+		&alice.client,
+		pallet_balances::Call::transfer {
+			dest: cirrus_test_service::runtime::Address::Id(Charlie.public().into()),
+			value: 8,
+		},
+		One,
+		false,
+		0,
+	);
+	let invalid_transaction_proof = alice
+		.executor
+		.check_bundle_extrinsic(primary_hash, bundle_hash, 1, &illegal_tx)
+		.unwrap()
+		.expect("Transaction from an account without funds must be illegal");
+	assert_eq!(invalid_transaction_proof.bundle_hash, bundle_hash);
+	assert_eq!(invalid_transaction_proof.extrinsic_index, 1);
+	assert_eq!(invalid_transaction_proof.extrinsic, illegal_tx.encode());
+}
+
+#[substrate_test_utils::test(flavor = "multi_thread")]
+async fn evaluate_execution_receipt_should_work() {
+	let (_ferdie, alice) = setup_ferdie_and_alice(Default::default()).await;
+
+	alice.wait_for_blocks(3).await;
+
+	let sign_execution_receipt = |execution_receipt: ExecutionReceipt<_, _, _>| {
+		let pair = ExecutorPair::from_string("//Alice", None).unwrap();
+		let signature = pair.sign(execution_receipt.hash().as_ref());
+		SignedExecutionReceipt { execution_receipt, signature, signer: pair.public() }
+	};
+
+	let execution_receipt = crate::aux_schema::load_execution_receipt(
+		&*alice.backend,
+		alice.client.hash(2).unwrap().unwrap(),
+	)
+	.expect("Failed to load execution receipt from the local aux_db")
+	.expect("The requested execution receipt must exist");
+
+	let matched_receipt = sign_execution_receipt(execution_receipt.clone());
+	assert!(matches!(
+		alice.executor.evaluate_execution_receipt(&matched_receipt).unwrap(),
+		crate::ExecutionReceiptDecision::WouldRebroadcast
+	));
+
+	let mut bad_execution_receipt = execution_receipt.clone();
+	let mismatch_idx = bad_execution_receipt.trace.len() - 1;
+	bad_execution_receipt.trace[mismatch_idx] = Hash::random();
+	let mismatched_receipt = sign_execution_receipt(bad_execution_receipt);
+	match alice.executor.evaluate_execution_receipt(&mismatched_receipt).unwrap() {
+		crate::ExecutionReceiptDecision::WouldSubmitFraudProof(fraud_proof) => {
+			assert_eq!(fraud_proof.parent_number, 1);
+			assert_eq!(fraud_proof.post_state_root, execution_receipt.trace[mismatch_idx]);
+		},
+		decision => panic!("Unexpected decision for a mismatched receipt: {decision:?}"),
+	}
+
+	// The roots of the truncated trace all match, it's neither rebroadcast nor disputed.
+	let mut truncated_receipt = execution_receipt.clone();
+	truncated_receipt.trace.pop();
+	assert!(matches!(
+		alice
+			.executor
+			.evaluate_execution_receipt(&sign_execution_receipt(truncated_receipt))
+			.unwrap(),
+		crate::ExecutionReceiptDecision::Ignored
+	));
+
+	let mut inconsistent_receipt = execution_receipt.clone();
+	inconsistent_receipt.primary_number += 1;
+	assert!(matches!(
+		alice
+			.executor
+			.evaluate_execution_receipt(&sign_execution_receipt(inconsistent_receipt)),
+		Err(crate::GossipMessageError::InconsistentPrimaryReference { stated: 3, local: Some(2) })
+	));
+
+	let mut empty_trace_receipt = execution_receipt;
+	empty_trace_receipt.trace.clear();
+	assert!(matches!(
+		alice
+			.executor
+			.evaluate_execution_receipt(&sign_execution_receipt(empty_trace_receipt)),
+		Err(crate::GossipMessageError::EmptyTrace)
+	));
+}
+
+#[substrate_test_utils::test(flavor = "multi_thread")]
+async fn receipt_with_deferred_verification_should_not_be_rebroadcast() {
+	let (_ferdie, alice) = setup_ferdie_and_alice(crate::ExecutorConfig {
+		receipt_checkpoint_interval: 4,
+		..Default::default()
+	})
+	.await;
+
+	alice.wait_for_blocks(3).await;
+
+	// Block #2 is not a checkpoint, the receipt diverging only before its final state root is not
+	// verified root by root.
+	let mut execution_receipt = crate::aux_schema::load_execution_receipt(
+		&*alice.backend,
+		alice.client.hash(2).unwrap().unwrap(),
+	)
+	.expect("Failed to load execution receipt from the local aux_db")
+	.expect("The requested execution receipt must exist");
+	execution_receipt.trace[0] = Hash::random();
+
+	let pair = ExecutorPair::from_string("//Alice", None).unwrap();
+	let signature = pair.sign(execution_receipt.hash().as_ref());
+	let unverified_receipt =
+		SignedExecutionReceipt { execution_receipt, signature, signer: pair.public() };
+
+	assert!(matches!(
+		alice.executor.evaluate_execution_receipt(&unverified_receipt).unwrap(),
+		crate::ExecutionReceiptDecision::Ignored
+	));
+	assert!(matches!(
+		alice.executor.on_execution_receipt(&unverified_receipt).unwrap(),
+		cirrus_client_executor_gossip::Action::Empty
+	));
+}
+
+#[substrate_test_utils::test(flavor = "multi_thread")]
+async fn missing_block_body_should_surface_block_body_missing_error() {
+	let (_ferdie, alice) = setup_ferdie_and_alice(Default::default()).await;
+
+	alice.wait_for_blocks(1).await;
+
+	let unknown_hash = Hash::random();
+	assert!(matches!(
+		alice.executor.block_body(unknown_hash),
+		Err(crate::GossipMessageError::BlockBodyMissing(_))
+	));
+	assert!(matches!(
+		alice.executor.header(unknown_hash),
+		Err(crate::GossipMessageError::HeaderLookup(_))
+	));
+}
+
+#[substrate_test_utils::test(flavor = "multi_thread")]
+async fn minimum_bundle_tx_fee_should_work() {
+	let (_ferdie, alice) = setup_ferdie_and_alice(Default::default()).await;
+
+	alice.wait_for_blocks(1).await;
+
+	let best_id = BlockId::Hash(alice.client.info().best_hash);
+	let minimum_bundle_tx_fee = alice
+		.executor
+		.minimum_bundle_tx_fee()
+		.unwrap()
+		.expect("The test runtime exposes SecondaryApi v2");
+	assert_eq!(minimum_bundle_tx_fee, cirrus_test_service::runtime::MinimumBundleTxFee::get());
+	assert_eq!(
+		minimum_bundle_tx_fee,
+		alice.client.runtime_api().minimum_bundle_tx_fee(&best_id).unwrap()
+	);
+
+	// A regular transfer pays more than the minimum fee and therefore can be included in a bundle.
+	let transfer_to_charlie = cirrus_test_service::construct_extrinsic(
+		&alice.client,
+		pallet_balances::Call::transfer {
+			dest: cirrus_test_service::runtime::Address::Id(Charlie.public().into()),
+			value: 8,
+		},
+		Alice,
+		false,
+		0,
+	);
+	let tx_fee = alice
+		.client
+		.runtime_api()
+		.query_info(
+			&best_id,
+			transfer_to_charlie.clone(),
+			transfer_to_charlie.encoded_size() as u32,
+		)
+		.unwrap()
+		.partial_fee;
+	assert!(tx_fee >= minimum_bundle_tx_fee);
+}
+
+/// Sends a transfer without a tip from Alice and a transfer tipping a quarter of its fee from Bob
+/// to Alice requiring the bundle fee premium `bundle_fee_multiplier`, returns whether each of them
+/// is selected for her next bundle.
+async fn transfers_selected_under(bundle_fee_multiplier: sp_runtime::FixedU128) -> (bool, bool) {
+	use sc_transaction_pool_api::TransactionPool;
+
+	let (_ferdie, alice) = setup_ferdie_and_alice(crate::ExecutorConfig {
+		bundle_fee_multiplier,
+		..Default::default()
+	})
+	.await;
+
+	alice.wait_for_blocks(1).await;
+
+	let transfer_from = |caller, tip| {
+		cirrus_test_service::construct_extrinsic_with_tip(
+			&alice.client,
+			pallet_balances::Call::transfer {
+				dest: cirrus_test_service::runtime::Address::Id(Charlie.public().into()),
+				value: 8,
+			},
+			caller,
+			false,
+			0,
+			tip,
+		)
+	};
+	let best_id = BlockId::Hash(alice.client.info().best_hash);
+	let untipped = transfer_from(Alice, 0);
+	let tx_fee = alice
+		.client
+		.runtime_api()
+		.query_info(&best_id, untipped.clone(), untipped.encoded_size() as u32)
+		.unwrap()
+		.partial_fee;
+
+	let tip = tx_fee / 4;
+	let tipped = transfer_from(Bob, tip);
+	assert_eq!(alice.client.runtime_api().extrinsic_tip(&best_id, tipped.clone()).unwrap(), tip);
+
+	let transaction_pool = alice.executor.transaction_pool.clone();
+	let untipped_hash = transaction_pool.hash_of(&untipped.clone().into());
+	let tipped_hash = transaction_pool.hash_of(&tipped.clone().into());
+	for tx in [untipped, tipped] {
+		alice.send_extrinsic(tx).await.expect("Failed to send extrinsic");
+	}
+	assert!(transaction_pool.ready_transaction(&untipped_hash).is_some());
+	assert!(transaction_pool.ready_transaction(&tipped_hash).is_some());
+
+	let selected = alice
+		.executor
+		.pending_extrinsics_for_next_bundle()
+		.await
+		.unwrap()
+		.into_iter()
+		.map(|xt| transaction_pool.hash_of(&xt))
+		.collect::<Vec<_>>();
+	(selected.contains(&untipped_hash), selected.contains(&tipped_hash))
+}
+
+#[substrate_test_utils::test(flavor = "multi_thread")]
+async fn transaction_without_the_premium_should_be_excluded() {
+	use sp_runtime::{FixedPointNumber, FixedU128};
+
+	// Only the tip pays the 20% premium, the fee charged for the transfer itself doesn't.
+	let (untipped_selected, tipped_selected) =
+		transfers_selected_under(FixedU128::saturating_from_rational(6, 5)).await;
+	assert!(!untipped_selected);
+	assert!(tipped_selected);
+}
+
+#[substrate_test_utils::test(flavor = "multi_thread")]
+async fn transaction_paying_its_fee_should_be_included_without_a_premium() {
+	use sp_runtime::{traits::One, FixedU128};
+
+	let (untipped_selected, tipped_selected) = transfers_selected_under(FixedU128::one()).await;
+	assert!(untipped_selected);
+	assert!(tipped_selected);
+}
+
+#[substrate_test_utils::test(flavor = "multi_thread")]
+async fn pending_extrinsics_for_next_bundle_should_match_the_produced_bundle() {
+	let (ferdie, alice) = setup_ferdie_and_alice(Default::default()).await;
+
+	alice.wait_for_blocks(1).await;
+
+	let transfer_to = |dest: cirrus_test_service::Keyring, nonce| {
+		cirrus_test_service::construct_extrinsic(
+			&alice.client,
+			pallet_balances::Call::transfer {
+				dest: cirrus_test_service::runtime::Address::Id(dest.public().into()),
+				value: 8,
+			},
+			Alice,
+			false,
+			nonce,
+		)
+	};
+	for tx in [transfer_to(Bob, 0), transfer_to(Charlie, 1), transfer_to(Dave, 2)] {
+		alice.send_extrinsic(tx).await.expect("Failed to send extrinsic");
+	}
+
+	let pending_extrinsics = alice.executor.pending_extrinsics_for_next_bundle().await.unwrap();
+	assert_eq!(pending_extrinsics.len(), 3);
+
+	let slot_info = crate::worker::ExecutorSlotInfo {
+		slot: sp_consensus_slots::Slot::from(u64::MAX),
+		global_challenge: Default::default(),
+	};
+	let signed_bundle = alice
+		.executor
+		.bundle_producer
+		.clone()
+		.produce_bundle(ferdie.client.info().best_hash, slot_info)
+		.await
+		.unwrap()
+		.expect("Alice is an authority and must produce the bundle");
+
+	assert_eq!(
+		pending_extrinsics.into_iter().map(Into::into).collect::<Vec<_>>(),
+		signed_bundle.bundle.extrinsics
+	);
+}
+
+#[test]
+fn receipt_on_abandoned_primary_fork_should_not_be_canonical() {
+	use sc_block_builder::BlockBuilderProvider;
+	use sp_consensus::BlockOrigin;
+	use substrate_test_runtime_client::{prelude::*, runtime::Header as PHeader};
+
+	let import_block_at =
+		|client: &mut TestClient, parent_hash, fork_marker: Option<u8>| -> PHeader {
+			let mut builder = client
+				.new_block_at(&BlockId::Hash(parent_hash), Default::default(), false)
+				.unwrap();
+			// Distinguish the blocks at the same height on different forks.
+			if let Some(marker) = fork_marker {
+				builder.push_storage_change(vec![marker], Some(vec![marker])).unwrap();
+			}
+			let block = builder.build().unwrap().block;
+			futures::executor::block_on(client.import(BlockOrigin::Own, block.clone())).unwrap();
+			block.header
+		};
+
+	let mut client = substrate_test_runtime_client::new();
+	let genesis_hash = client.info().genesis_hash;
+
+	// G -> A1
+	let a1 = import_block_at(&mut client, genesis_hash, None);
+	assert!(crate::is_canonical(&client, 1, a1.hash()).unwrap());
+
+	// G -> B1 -> B2, the primary chain reorgs to the longer fork B.
+	let b1 = import_block_at(&mut client, genesis_hash, Some(1));
+	let b2 = import_block_at(&mut client, b1.hash(), None);
+
+	assert_eq!(client.info().best_hash, b2.hash());
+	assert!(!crate::is_canonical(&client, 1, a1.hash()).unwrap());
+	assert!(crate::is_canonical(&client, 1, b1.hash()).unwrap());
+	assert!(crate::is_canonical(&client, 2, b2.hash()).unwrap());
+}
+
+#[test]
+fn receipt_of_orphaned_block_should_not_be_canonical() {
+	use sc_block_builder::BlockBuilderProvider;
+	use sp_consensus::BlockOrigin;
+	use substrate_test_runtime_client::{prelude::*, runtime::Header};
+
+	let import_block_at =
+		|client: &mut TestClient, parent_hash, fork_marker: Option<u8>| -> Header {
+			let mut builder = client
+				.new_block_at(&BlockId::Hash(parent_hash), Default::default(), false)
+				.unwrap();
+			// Distinguish the blocks at the same height on different forks.
+			if let Some(marker) = fork_marker {
+				builder.push_storage_change(vec![marker], Some(vec![marker])).unwrap();
+			}
+			let block = builder.build().unwrap().block;
+			futures::executor::block_on(client.import(BlockOrigin::Own, block.clone())).unwrap();
+			block.header
+		};
+
+	let mut client = substrate_test_runtime_client::new();
+	let genesis_hash = client.info().genesis_hash;
+
+	// G -> A1
+	let a1 = import_block_at(&mut client, genesis_hash, None);
+	assert!(crate::is_block_canonical(&client, a1.hash()).unwrap());
+
+	// G -> B1 -> B2, the chain reorgs to the longer fork B and A1 is orphaned.
+	let b1 = import_block_at(&mut client, genesis_hash, Some(1));
+	let b2 = import_block_at(&mut client, b1.hash(), None);
+
+	assert!(!crate::is_block_canonical(&client, a1.hash()).unwrap());
+	assert!(crate::is_block_canonical(&client, b1.hash()).unwrap());
+	assert!(crate::is_block_canonical(&client, b2.hash()).unwrap());
+
+	// Unknown block.
+	assert!(!crate::is_block_canonical::<substrate_test_runtime_client::runtime::Block, _>(
+		&client,
+		Hash::random()
+	)
+	.unwrap());
+}
+
+#[test]
+fn orphaned_block_should_abandon_the_local_receipt_wait() {
+	use sc_block_builder::BlockBuilderProvider;
+	use sp_consensus::BlockOrigin;
+	use substrate_test_runtime_client::{
+		prelude::*,
+		runtime::{Block, Header},
+	};
+
+	let import_block_at =
+		|client: &mut TestClient, parent_hash, fork_marker: Option<u8>| -> Header {
+			let mut builder = client
+				.new_block_at(&BlockId::Hash(parent_hash), Default::default(), false)
+				.unwrap();
+			// Distinguish the blocks at the same height on different forks.
+			if let Some(marker) = fork_marker {
+				builder.push_storage_change(vec![marker], Some(vec![marker])).unwrap();
+			}
+			let block = builder.build().unwrap().block;
+			futures::executor::block_on(client.import(BlockOrigin::Own, block.clone())).unwrap();
+			block.header
+		};
+
+	let write_receipt_of = |client: &TestClient, header: &Header| {
+		let receipt = ExecutionReceipt {
+			primary_number: *header.number(),
+			primary_hash: Hash::random(),
+			secondary_hash: header.hash(),
+			trace: vec![*header.state_root()],
+			trace_root: Default::default(),
+		};
+		crate::aux_schema::write_execution_receipt::<_, Block, Block>(
+			client,
+			(header.hash(), *header.number()),
+			0,
+			0,
+			&receipt,
+		)
+		.unwrap();
+		receipt
+	};
+
+	let poll = |client: &TestClient, header: &Header| {
+		crate::poll_local_future_receipt::<Block, Block, _>(client, header.hash(), *header.number())
+	};
+
+	let mut client = substrate_test_runtime_client::new();
+	let genesis_hash = client.info().genesis_hash;
+
+	// G -> A1, an external receipt of A2 is awaited.
+	let a1 = import_block_at(&mut client, genesis_hash, None);
+	write_receipt_of(&client, &a1);
+	let a2 = client
+		.new_block_at(&BlockId::Hash(a1.hash()), Default::default(), false)
+		.unwrap()
+		.build()
+		.unwrap()
+		.block;
+	assert!(poll(&client, &a2.header).is_none());
+
+	// A2 is imported, its receipt is not written until the block is processed.
+	futures::executor::block_on(client.import(BlockOrigin::Own, a2.clone())).unwrap();
+	let a2 = a2.header;
+
+	// G -> B1 -> B2 -> B3, the chain reorgs to the longer fork B while waiting.
+	let b1 = import_block_at(&mut client, genesis_hash, Some(1));
+	write_receipt_of(&client, &b1);
+	let b2 = import_block_at(&mut client, b1.hash(), None);
+	let b2_receipt = write_receipt_of(&client, &b2);
+	import_block_at(&mut client, b2.hash(), None);
+
+	// The orphaned A2 is reported instead of sending back the local receipt of B2.
+	assert!(matches!(
+		poll(&client, &a2),
+		Some(Err(crate::GossipMessageError::AwaitedBlockOrphaned))
+	));
+
+	// An unknown block at #2 is still compared with the local receipt at the same height.
+	let unknown = Header { state_root: Hash::random(), ..a2 };
+	assert_eq!(poll(&client, &unknown).unwrap().unwrap(), b2_receipt);
+}
+
+#[test]
+fn active_leaves_should_track_the_best_block_and_its_uncles() {
+	use sc_block_builder::BlockBuilderProvider;
+	use sp_consensus::BlockOrigin;
+	use substrate_test_runtime_client::{prelude::*, runtime::Header as PHeader};
+
+	let import_block_at =
+		|client: &mut TestClient, parent_hash, fork_marker: Option<u8>| -> PHeader {
+			let mut builder = client
+				.new_block_at(&BlockId::Hash(parent_hash), Default::default(), false)
+				.unwrap();
+			// Distinguish the blocks at the same height on different forks.
+			if let Some(marker) = fork_marker {
+				builder.push_storage_change(vec![marker], Some(vec![marker])).unwrap();
+			}
+			let block = builder.build().unwrap().block;
+			futures::executor::block_on(client.import(BlockOrigin::Own, block.clone())).unwrap();
+			block.header
+		};
+
+	let (mut client, select_chain) = TestClientBuilder::new().build_with_longest_chain();
+	let genesis_hash = client.info().genesis_hash;
+
+	let active_leaves = |client: &TestClient| {
+		futures::executor::block_on(crate::active_leaves(client, &select_chain))
+			.unwrap()
+			.into_iter()
+			.map(|leaf| (leaf.number, leaf.hash, leaf.parent_hash))
+			.collect::<Vec<_>>()
+	};
+
+	// No leaves when starting from the genesis.
+	assert!(active_leaves(&client).is_empty());
+
+	// G -> A1 -> A2 -> A3
+	//  \-> B1
+	//       \-> C2
+	let a1 = import_block_at(&mut client, genesis_hash, None);
+	let a2 = import_block_at(&mut client, a1.hash(), None);
+	let b1 = import_block_at(&mut client, genesis_hash, Some(1));
+	let c2 = import_block_at(&mut client, b1.hash(), Some(2));
+	let a3 = import_block_at(&mut client, a2.hash(), None);
+
+	// The best block comes first, followed by the leaves that are at most an uncle of it.
+	assert_eq!(active_leaves(&client), vec![(3, a3.hash(), a2.hash()), (2, c2.hash(), b1.hash())]);
+}
+
+#[test]
+fn leaves_at_the_same_height_should_be_sorted_by_hash() {
+	use substrate_test_runtime_client::runtime::Block as PBlock;
+
+	let leaf = |number: u64, hash_byte: u8| crate::BlockInfo::<PBlock> {
+		hash: Hash::repeat_byte(hash_byte),
+		parent_hash: Default::default(),
+		number,
+	};
+
+	let sorted = |mut leaves: Vec<crate::BlockInfo<PBlock>>| {
+		crate::sort_leaves(&mut leaves);
+		leaves.into_iter().map(|leaf| (leaf.number, leaf.hash)).collect::<Vec<_>>()
+	};
+
+	let expected = vec![
+		(1, Hash::repeat_byte(2)),
+		(2, Hash::repeat_byte(1)),
+		(2, Hash::repeat_byte(3)),
+		(2, Hash::repeat_byte(7)),
+	];
+
+	// The same set of leaves in any order results in the same order.
+	assert_eq!(sorted(vec![leaf(2, 7), leaf(2, 1), leaf(1, 2), leaf(2, 3)]), expected);
+	assert_eq!(sorted(vec![leaf(2, 3), leaf(1, 2), leaf(2, 7), leaf(2, 1)]), expected);
+}
+
+#[test]
+fn diff_receipts_should_pick_the_first_divergence_in_large_trace() {
+	let local_receipt = ExecutionReceipt {
+		primary_number: 1u32,
+		primary_hash: Hash::random(),
+		secondary_hash: Hash::random(),
+		trace: (0..100_000u32).map(|i| BlakeTwo256::hash_of(&i)).collect::<Vec<_>>(),
+		trace_root: Default::default(),
+	};
+	let first_divergent_index =
+		|external_receipt| crate::diff_receipts(&local_receipt, external_receipt).first_divergent_index;
+
+	assert_eq!(first_divergent_index(&local_receipt), None);
+
+	let mut external_receipt = local_receipt.clone();
+	external_receipt.trace[99_999] = Hash::random();
+	external_receipt.trace[77_777] = Hash::random();
+	external_receipt.trace[55_555] = Hash::random();
+	assert_eq!(first_divergent_index(&external_receipt), Some(55_555));
+
+	external_receipt.trace[0] = Hash::random();
+	assert_eq!(first_divergent_index(&external_receipt), Some(0));
+}
+
+#[test]
+fn wait_for_queued_block_should_return_once_block_is_imported() {
+	let block_status = parking_lot::Mutex::new(BlockStatus::Queued);
+	let (import_notification_sender, import_notifications) = futures::channel::mpsc::unbounded();
+
+	let wait = crate::wait_for_queued_block(
+		|| Ok(*block_status.lock()),
+		import_notifications,
+		Duration::from_secs(60),
+	);
+
+	// The block becomes in chain while the executor is waiting for it.
+	let import = async {
+		*block_status.lock() = BlockStatus::InChainWithState;
+		import_notification_sender.unbounded_send(()).unwrap();
+	};
+
+	let now = std::time::Instant::now();
+	futures::executor::block_on(futures::future::join(wait, import));
+	assert!(now.elapsed() < Duration::from_secs(60));
+	assert_eq!(*block_status.lock(), BlockStatus::InChainWithState);
+}
+
+#[test]
+fn wait_for_queued_block_should_give_up_after_timeout() {
+	let (_import_notification_sender, import_notifications) =
+		futures::channel::mpsc::unbounded::<()>();
+
+	futures::executor::block_on(crate::wait_for_queued_block(
+		|| Ok(BlockStatus::Queued),
+		import_notifications,
+		Duration::from_millis(10),
+	));
+}
+
+#[substrate_test_utils::test]
+async fn shorter_receipt_poll_interval_should_check_more_frequently() {
+	// The paused clock auto-advances to the next timer, the polls are counted deterministically.
+	tokio::time::pause();
+
+	let count_polls_within = |interval| async move {
+		let mut polls = 0;
+		let _ = tokio::time::timeout(
+			Duration::from_millis(212),
+			crate::poll_until_ready(interval, || {
+				polls += 1;
+				None::<()>
+			}),
+		)
+		.await;
+		polls
+	};
+
+	let short_interval_polls = count_polls_within(Duration::from_millis(5)).await;
+	let default_interval_polls = count_polls_within(Duration::from_millis(100)).await;
+
+	// Polled at 0, 5, ..., 210ms and at 0, 100, 200ms respectively.
+	assert_eq!(short_interval_polls, 43);
+	assert_eq!(default_interval_polls, 3);
+}
+
+#[substrate_test_utils::test(flavor = "multi_thread")]
+async fn executor_should_be_constructed_with_custom_config() {
+	let executor_config = crate::ExecutorConfig {
+		submission_retry: crate::SubmissionRetry {
+			max_attempts: 1,
+			base_delay: Duration::from_millis(10),
+		},
+		queued_block_wait: Some(Duration::from_secs(1)),
+		receipt_poll_interval: Duration::from_millis(10),
+		..Default::default()
+	};
+	let (_ferdie, alice) = setup_ferdie_and_alice(executor_config).await;
+
+	alice.wait_for_blocks(3).await;
+
+	assert_eq!(alice.executor.submission_retry.max_attempts, 1);
+	assert_eq!(alice.executor.receipt_poll_interval, Duration::from_millis(10));
+}
+
+#[substrate_test_utils::test(flavor = "multi_thread")]
+async fn can_prove_extrinsic_should_check_the_extrinsic_index() {
+	let (ferdie, alice) = setup_ferdie_and_alice(Default::default()).await;
+
+	alice.wait_for_blocks(3).await;
+
+	let transfer_to_charlie = cirrus_test_service::construct_extrinsic(
+		&alice.client,
+		pallet_balances::Call::transfer {
+			dest: cirrus_test_service::runtime::Address::Id(Charlie.public().into()),
+			value: 8,
+		},
+		Alice,
+		false,
+		0,
+	);
+
+	let bundles = vec![OpaqueBundle {
+		header: BundleHeader {
+			domain_id: DomainId::SYSTEM,
+			primary_hash: ferdie.client.info().best_hash,
+			slot_number: Default::default(),
+			extrinsics_root: Default::default(),
+		},
+		opaque_extrinsics: vec![OpaqueExtrinsic::from_bytes(&transfer_to_charlie.encode()).unwrap()],
+	}];
+
+	alice
+		.executor
+		.clone()
+		.process_bundles(
+			(ferdie.client.info().best_hash, ferdie.client.info().best_number),
+			bundles,
+			BlakeTwo256::hash_of(&[1u8; 64]).into(),
+			None,
+		)
+		.await;
+
+	let best_hash = alice.client.info().best_hash;
+	assert!(alice.executor.can_prove_extrinsic(best_hash, 0).unwrap());
+	assert!(!alice.executor.can_prove_extrinsic(best_hash, 1).unwrap());
+	assert!(matches!(
+		alice.executor.can_prove_extrinsic(Hash::random(), 0),
+		Err(crate::GossipMessageError::BlockBodyMissing(_))
+	));
+}
+
+#[substrate_test_utils::test(flavor = "multi_thread")]
+async fn bundles_should_not_be_processed_without_the_parent_block() {
+	let (ferdie, alice) = setup_ferdie_and_alice(Default::default()).await;
+
+	alice.wait_for_blocks(3).await;
+
+	let process_bundles_at = |primary_number| {
+		alice.executor.bundle_processor.clone().process_bundles(
+			(ferdie.client.info().best_hash, primary_number),
+			Vec::new(),
+			BlakeTwo256::hash_of(&[1u8; 64]).into(),
+			None,
+		)
+	};
+
+	// The parent of the block driven by a primary block far ahead is not imported.
+	let primary_number = ferdie.client.info().best_number + 1000;
+	assert!(process_bundles_at(primary_number).await.is_err());
+	assert_eq!(alice.client.hash(primary_number).unwrap(), None);
+
+	// The primary genesis block drives no secondary block.
+	assert!(process_bundles_at(0).await.is_err());
+}
+
+#[substrate_test_utils::test(flavor = "multi_thread")]
+async fn bundles_should_wait_for_the_parent_block_under_import() {
+	let (ferdie, alice) = setup_ferdie_and_alice(crate::ExecutorConfig {
+		queued_block_wait: Some(Duration::from_secs(60)),
+		..Default::default()
+	})
+	.await;
+
+	alice.wait_for_blocks(3).await;
+
+	// The parent of the block driven by the primary block after next is not imported yet, it's
+	// imported once the next primary block is processed.
+	let parent_number = alice.client.info().best_number + 1;
+	assert_eq!(alice.client.hash(parent_number).unwrap(), None);
+
+	let now = std::time::Instant::now();
+	alice
+		.executor
+		.bundle_processor
+		.clone()
+		.process_bundles(
+			(ferdie.client.info().best_hash, parent_number + 1),
+			Vec::new(),
+			BlakeTwo256::hash_of(&[1u8; 64]).into(),
+			None,
+		)
+		.await
+		.unwrap();
+	assert!(now.elapsed() < Duration::from_secs(60));
+	assert!(alice.client.hash(parent_number).unwrap().is_some());
+}
+
+#[substrate_test_utils::test(flavor = "multi_thread")]
+async fn disabled_proof_submission_should_skip_the_runtime_call() {
+	// Run Alice (a secondary chain authority node) in the detection-only mode.
+	let (_ferdie, alice) = setup_ferdie_and_alice(crate::ExecutorConfig {
+		proof_submission_enabled: false,
+		..Default::default()
+	})
+	.await;
+
+	alice.wait_for_blocks(1).await;
+
+	let submitted = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+	let failed = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+	alice.executor.submit_proof_in_background(
+		"test-submit-proof",
+		"test proof",
+		None,
+		(),
+		{
+			let submitted = submitted.clone();
+			move |_primary_chain_client, _at, ()| {
+				submitted.store(true, std::sync::atomic::Ordering::SeqCst);
+				Ok(())
+			}
+		},
+		{
+			let failed = failed.clone();
+			move || failed.store(true, std::sync::atomic::Ordering::SeqCst)
+		},
+	);
+
+	tokio::time::sleep(Duration::from_secs(1)).await;
+	assert!(!submitted.load(std::sync::atomic::Ordering::SeqCst));
+	// Skipping the disabled submission is not a submission failure.
+	assert!(!failed.load(std::sync::atomic::Ordering::SeqCst));
+}
+
+#[substrate_test_utils::test(flavor = "multi_thread")]
+async fn submit_fraud_proof_awaitable_should_return_the_submission_error() {
+	let (_ferdie, alice) = setup_ferdie_and_alice(crate::ExecutorConfig {
+		submission_retry: crate::SubmissionRetry {
+			max_attempts: 1,
+			base_delay: Duration::from_millis(10),
+		},
+		..Default::default()
+	})
+	.await;
+
+	alice.wait_for_blocks(1).await;
+
+	let fraud_proof = FraudProof {
+		parent_number: 0,
+		parent_hash: Hash::random(),
+		pre_state_root: Hash::random(),
+		post_state_root: Hash::random(),
+		proof: StorageProof::empty(),
+		execution_phase: ExecutionPhase::FinalizeBlock,
+	};
+
+	// The runtime call at an unknown primary block must fail and the error must reach the caller.
+	let result = alice
+		.executor
+		.submit_fraud_proof_awaitable(fraud_proof, Some(BlockId::Hash(Hash::random())))
+		.await;
+	assert!(matches!(result, Err(crate::SubmitError::RuntimeApi(_))));
+}
+
+#[substrate_test_utils::test(flavor = "multi_thread")]
+async fn bad_bundle_signature_should_be_penalized() {
+	let (ferdie, alice) = setup_ferdie_and_alice(Default::default()).await;
+
+	alice.wait_for_blocks(1).await;
+
+	let pair = ExecutorPair::from_string("//Alice", None).unwrap();
+	let bundle = Bundle {
+		header: BundleHeader {
+			domain_id: DomainId::SYSTEM,
+			primary_hash: ferdie.client.info().best_hash,
+			slot_number: Default::default(),
+			extrinsics_root: Default::default(),
+		},
+		extrinsics: Vec::new(),
+	};
+	let signed_bundle = SignedBundle {
+		signature: pair.sign(b"not the bundle hash"),
+		signer: pair.public(),
+		bundle,
+	};
+
+	let err = alice.executor.on_bundle(&signed_bundle).unwrap_err();
+	assert!(matches!(
+		err,
+		crate::GossipMessageError::BundleValidation(crate::BundleValidationError::BadSignature)
+	));
+	assert_eq!(err.reputation_change(), Some(cirrus_client_executor_gossip::cost::BAD_SIGNATURE));
+}
+
+#[test]
+fn fraud_proof_should_be_decoded_from_the_envelope_bytes() {
+	let call_data = vec![1u8, 2, 3];
+	let node = vec![7u8; 5];
+
+	let mut envelope = b"SFPE".to_vec();
+	envelope.push(1);
+	envelope.extend_from_slice(&100u32.to_le_bytes());
+	envelope.extend_from_slice(&[1u8; 32]);
+	envelope.extend_from_slice(&[2u8; 32]);
+	envelope.extend_from_slice(&[3u8; 32]);
+	envelope.push(1);
+	envelope.extend_from_slice(&(call_data.len() as u32).to_le_bytes());
+	envelope.extend_from_slice(&call_data);
+	envelope.extend_from_slice(&1u32.to_le_bytes());
+	envelope.extend_from_slice(&(node.len() as u32).to_le_bytes());
+	envelope.extend_from_slice(&node);
+
+	let fraud_proof = FraudProof {
+		parent_number: 100,
+		parent_hash: Hash::repeat_byte(1),
+		pre_state_root: Hash::repeat_byte(2),
+		post_state_root: Hash::repeat_byte(3),
+		proof: StorageProof::new(vec![node]),
+		execution_phase: ExecutionPhase::ApplyExtrinsic { call_data },
+	};
+	assert_eq!(FraudProof::from_envelope(&envelope).unwrap(), fraud_proof);
+	assert_eq!(fraud_proof.to_envelope(), envelope);
+
+	let mut unsupported_version = envelope.clone();
+	unsupported_version[4] = 2;
+	assert_eq!(
+		FraudProof::from_envelope(&unsupported_version),
+		Err(sp_executor::EnvelopeError::UnsupportedVersion(2))
+	);
+	assert_eq!(
+		FraudProof::from_envelope(&envelope[..envelope.len() - 1]),
+		Err(sp_executor::EnvelopeError::Truncated)
+	);
+}
+
+#[test]
+fn finalize_block_envelope_with_call_data_should_be_rejected() {
+	let fraud_proof = FraudProof {
+		parent_number: 100,
+		parent_hash: Hash::repeat_byte(1),
+		pre_state_root: Hash::repeat_byte(2),
+		post_state_root: Hash::repeat_byte(3),
+		proof: StorageProof::empty(),
+		execution_phase: ExecutionPhase::FinalizeBlock,
+	};
+	let envelope = fraud_proof.to_envelope();
+	assert_eq!(FraudProof::from_envelope(&envelope).unwrap(), fraud_proof);
+
+	// The call data follows the phase tag at offset 105 and its length.
+	let mut with_call_data = envelope[..106].to_vec();
+	with_call_data.extend_from_slice(&1u32.to_le_bytes());
+	with_call_data.push(7);
+	with_call_data.extend_from_slice(&envelope[110..]);
+	assert_eq!(
+		FraudProof::from_envelope(&with_call_data),
+		Err(sp_executor::EnvelopeError::UnexpectedCallData)
+	);
+}
+
+#[test]
+fn default_executor_id_should_not_be_configured() {
+	let unset_executor_id =
+		sp_executor::ExecutorId::from(sp_core::sr25519::Public::from_raw([0u8; 32]));
+	assert_eq!(crate::configured_executor_id(unset_executor_id), None);
+
+	let alice_executor_id = ExecutorPair::from_string("//Alice", None).unwrap().public();
+	assert_eq!(crate::configured_executor_id(alice_executor_id.clone()), Some(alice_executor_id));
+}
+
+#[test]
+fn gossip_message_error_should_be_mapped_to_its_kind() {
+	use crate::{BundleValidationError, GossipErrorKind, GossipMessageError};
+
+	let alice_executor_id = ExecutorPair::from_string("//Alice", None).unwrap().public();
+	let bob_executor_id = ExecutorPair::from_string("//Bob", None).unwrap().public();
+
+	let cases = [
+		(BundleValidationError::BadSignature.into(), GossipErrorKind::BadSignature),
+		(GossipMessageError::BadExecutionReceiptSignature, GossipErrorKind::BadSignature),
+		(
+			BundleValidationError::InvalidAuthor {
+				got: bob_executor_id.clone(),
+				expected: alice_executor_id.clone(),
+			}
+			.into(),
+			GossipErrorKind::InvalidAuthor,
+		),
+		(
+			GossipMessageError::InvalidExecutionReceiptAuthor {
+				got: bob_executor_id,
+				expected: alice_executor_id,
+			},
+			GossipErrorKind::InvalidAuthor,
+		),
+		(GossipMessageError::FutureBundleSlot { slot: 10, latest: 1 }, GossipErrorKind::Malformed),
+		(GossipMessageError::EmptyTrace, GossipErrorKind::Malformed),
+		(GossipMessageError::TraceTooLong { len: 5, max: 4 }, GossipErrorKind::Malformed),
+		(BundleValidationError::MalformedPrimaryHash.into(), GossipErrorKind::Malformed),
+		(GossipMessageError::AwaitedBlockOrphaned, GossipErrorKind::Client),
+		(sp_blockchain::Error::Backend("Database failure".into()).into(), GossipErrorKind::Client),
+		(BundleValidationError::NoExecutorConfigured.into(), GossipErrorKind::Runtime),
+		(GossipMessageError::NoExecutorConfigured, GossipErrorKind::Runtime),
+		(GossipMessageError::InvalidStateRootType, GossipErrorKind::Internal),
+	];
+
+	for (error, kind) in cases {
+		assert_eq!(error.kind(), kind, "Unexpected kind of {error:?}");
+	}
+}
+
+#[test]
+fn block_number_overflow_should_be_an_error() {
+	assert_eq!(crate::to_block_number(u64::from(u32::MAX)).unwrap(), u32::MAX);
+	assert!(matches!(
+		crate::to_block_number(u64::from(u32::MAX) + 1),
+		Err(crate::GossipMessageError::BlockNumberOverflow)
+	));
+
+	assert_eq!(crate::to_secondary_number::<u32>(7u32).unwrap(), 7);
+	assert!(matches!(
+		crate::to_secondary_number::<u32>(u64::MAX),
+		Err(crate::GossipMessageError::BlockNumberOverflow)
+	));
+}
+
+#[test]
+fn full_verification_should_be_triggered_on_final_root_divergence() {
+	let (root, other_root) = (Hash::random(), Hash::random());
+
+	// Each receipt is fully verified by default.
+	assert!(crate::requires_full_verification(5u32, 1, Some(&root), Some(&root)));
+
+	// The checkpoint receipts are always fully verified.
+	assert!(crate::requires_full_verification(8u32, 4, Some(&root), Some(&root)));
+	assert!(crate::requires_full_verification(8u32, 4, Some(&root), Some(&other_root)));
+
+	// The receipts between the checkpoints are only fully verified on final root divergence.
+	assert!(!crate::requires_full_verification(5u32, 4, Some(&root), Some(&root)));
+	assert!(crate::requires_full_verification(5u32, 4, Some(&root), Some(&other_root)));
+}
+
+#[test]
+fn execution_trap_should_be_told_apart_from_infrastructure_errors() {
+	use crate::prover::is_execution_trap;
+	use sc_executor::error::{Error as ExecutorError, MessageWithBacktrace};
+
+	let message =
+		|message: &str| MessageWithBacktrace { message: message.to_string(), backtrace: None };
+
+	assert!(is_execution_trap(&ExecutorError::AbortedDueToTrap(message(
+		"wasm `unreachable` instruction executed"
+	))));
+	assert!(is_execution_trap(&ExecutorError::AbortedDueToPanic(message("Bad input data"))));
+	assert!(is_execution_trap(&ExecutorError::RuntimePanicked("Bad input data".to_string())));
+
+	assert!(!is_execution_trap(&ExecutorError::Other("Unable to generate proof".to_string())));
+	assert!(!is_execution_trap(&ExecutorError::MethodNotFound("apply_extrinsic".to_string())));
+}
+
+#[test]
+fn trapped_extrinsic_should_be_located_in_its_bundle() {
+	let opaque_extrinsic = |byte: u8| OpaqueExtrinsic::from_bytes(&[byte].encode()).unwrap();
+	let bundle = |opaque_extrinsics| OpaqueBundle {
+		header: BundleHeader {
+			domain_id: DomainId::SYSTEM,
+			primary_hash: Hash::random(),
+			slot_number: Default::default(),
+			extrinsics_root: Default::default(),
+		},
+		opaque_extrinsics,
+	};
+	let bundles = vec![
+		bundle(vec![opaque_extrinsic(1), opaque_extrinsic(2)]),
+		bundle(vec![opaque_extrinsic(3), opaque_extrinsic(4)]),
+	];
+
+	assert_eq!(
+		crate::locate_in_bundles(&bundles, &opaque_extrinsic(1).encode()),
+		Some((bundles[0].hash(), 0))
+	);
+	assert_eq!(
+		crate::locate_in_bundles(&bundles, &opaque_extrinsic(4).encode()),
+		Some((bundles[1].hash(), 1))
+	);
+	assert_eq!(crate::locate_in_bundles(&bundles, &opaque_extrinsic(5).encode()), None);
+}
+
+#[test]
+fn duplicated_slot_should_be_processed_once() {
+	use crate::worker::{skip_stale_slots, ExecutorSlotInfo};
+	use sp_consensus_slots::Slot;
+
+	let slots = [1u64, 2, 2, 1, 3, 3].into_iter().map(|slot| ExecutorSlotInfo {
+		slot: Slot::from(slot),
+		global_challenge: Default::default(),
+	});
+	let processed_slots = futures::executor::block_on(
+		skip_stale_slots(futures::stream::iter(slots))
+			.map(|executor_slot_info| u64::from(executor_slot_info.slot))
+			.collect::<Vec<_>>(),
+	);
+	assert_eq!(processed_slots, vec![1, 2, 3]);
+}
+
+#[test]
+fn pruned_parent_state_should_be_unavailable_for_proof_construction() {
+	assert!(crate::ensure_state_available(Ok(BlockStatus::InChainWithState)).is_ok());
+
+	for status in [BlockStatus::InChainPruned, BlockStatus::Queued, BlockStatus::Unknown] {
+		assert!(matches!(
+			crate::ensure_state_available(Ok(status)),
+			Err(crate::GossipMessageError::ParentStateUnavailable { status: got }) if got == status
+		));
+	}
+
+	assert!(matches!(
+		crate::ensure_state_available(Err(sp_blockchain::Error::UnknownBlock("Test".into()))),
+		Err(crate::GossipMessageError::Client(_))
+	));
+}
+
+#[test]
+fn bundle_signature_should_be_verified() {
+	use cirrus_test_service::Executor;
+
+	let pair = ExecutorPair::from_string("//Alice", None).unwrap();
+	let bundle = Bundle {
+		header: BundleHeader {
+			domain_id: DomainId::SYSTEM,
+			primary_hash: Hash::random(),
+			slot_number: 1,
+			extrinsics_root: Default::default(),
+		},
+		extrinsics: Vec::new(),
+	};
+	let signed_bundle = SignedBundle {
+		signature: pair.sign(bundle.hash().as_ref()),
+		signer: pair.public(),
+		bundle,
+	};
+	assert!(Executor::verify_bundle_signature(&signed_bundle).is_ok());
+
+	// The signature no longer matches once the bundle is tampered with.
+	let mut tampered_bundle = signed_bundle;
+	tampered_bundle.bundle.header.slot_number = 2;
+	assert!(matches!(
+		Executor::verify_bundle_signature(&tampered_bundle),
+		Err(crate::GossipMessageError::BundleValidation(
+			crate::BundleValidationError::BadSignature
+		))
+	));
+}
+
+#[test]
+fn receipt_signature_should_be_verified() {
+	use cirrus_test_service::Executor;
+
+	let pair = ExecutorPair::from_string("//Alice", None).unwrap();
+	let execution_receipt = ExecutionReceipt {
+		primary_number: 1,
+		primary_hash: Hash::random(),
+		secondary_hash: Hash::random(),
+		trace: vec![Hash::random(), Hash::random()],
+		trace_root: Default::default(),
+	};
+	let signed_execution_receipt = SignedExecutionReceipt {
+		signature: pair.sign(execution_receipt.hash().as_ref()),
+		signer: pair.public(),
+		execution_receipt,
+	};
+	assert!(Executor::verify_receipt_signature(&signed_execution_receipt).is_ok());
+
+	// The signature no longer matches once the receipt is tampered with.
+	let mut tampered_execution_receipt = signed_execution_receipt;
+	tampered_execution_receipt.execution_receipt.trace[1] = Hash::random();
+	assert!(matches!(
+		Executor::verify_receipt_signature(&tampered_execution_receipt),
+		Err(crate::GossipMessageError::BadExecutionReceiptSignature)
+	));
+}
+
+#[test]
+fn bundle_digest_should_be_stable() {
+	use codec::Decode;
+
+	let extrinsics = vec![
+		OpaqueExtrinsic::from_bytes(&vec![0x01u8; 8].encode()).unwrap(),
+		OpaqueExtrinsic::from_bytes(&vec![0x02u8; 16].encode()).unwrap(),
+	];
+	let bundle = Bundle {
+		header: BundleHeader {
+			domain_id: DomainId::SYSTEM,
+			primary_hash: Hash::random(),
+			slot_number: 10,
+			extrinsics_root: BlakeTwo256::ordered_trie_root(
+				extrinsics.iter().map(|xt| xt.encode()).collect(),
+				sp_core::storage::StateVersion::V1,
+			),
+		},
+		extrinsics: extrinsics.clone(),
+	};
+	let digest = crate::bundle_digest(&bundle);
+
+	let decoded = Bundle::<OpaqueExtrinsic>::decode(&mut bundle.encode().as_slice()).unwrap();
+	assert_eq!(crate::bundle_digest(&decoded), digest);
+
+	let mut over_allocated = Vec::with_capacity(extrinsics.len() * 16);
+	over_allocated.extend(extrinsics);
+	let over_allocated = Bundle { header: bundle.header.clone(), extrinsics: over_allocated };
+	assert_eq!(crate::bundle_digest(&over_allocated), digest);
+
+	let other_slot = Bundle {
+		header: BundleHeader { slot_number: 11, ..bundle.header.clone() },
+		extrinsics: bundle.extrinsics.clone(),
+	};
+	assert_ne!(crate::bundle_digest(&other_slot), digest);
+}
+
+#[test]
+fn panicked_worker_should_be_restarted() {
+	use crate::worker::{supervise, SharedStream};
+
+	let notifications = SharedStream::new(futures::stream::iter(1..=5u32));
+	let handled = parking_lot::Mutex::new(Vec::new());
+	let mut starts = 0;
+
+	futures::executor::block_on(supervise(
+		Duration::from_millis(1),
+		Duration::from_millis(10),
+		|| {
+			starts += 1;
+			let simulate_panic = starts <= 2;
+			let mut notifications = notifications.clone();
+			let handled = &handled;
+			async move {
+				while let Some(notification) = notifications.next().await {
+					handled.lock().push(notification);
+					if simulate_panic {
+						panic!("Simulated worker panic");
+					}
+				}
+			}
+		},
+	));
+
+	// The worker exits normally on the third start, after resuming from where it panicked.
+	assert_eq!(starts, 3);
+	assert_eq!(*handled.lock(), vec![1, 2, 3, 4, 5]);
+}
+
+#[test]
+fn malformed_trace_root_should_be_rejected_up_front() {
+	let well_formed_trace = vec![vec![0x01u8; 32], vec![0x02u8; 32], vec![0x03u8; 32]];
+	assert!(crate::ensure_h256_trace(&well_formed_trace).is_ok());
+
+	// The external trace diverges at #2, but carries a malformed root at #1.
+	let external_trace = vec![vec![0x01u8; 32], vec![0x02u8; 16], vec![0x04u8; 32]];
+	let receipt = |trace| ExecutionReceipt {
+		primary_number: 1u32,
+		primary_hash: Hash::random(),
+		secondary_hash: Vec::new(),
+		trace,
+		trace_root: Default::default(),
+	};
+	let mut local_trace = external_trace.clone();
+	local_trace[2] = vec![0x03u8; 32];
+	assert_eq!(
+		crate::diff_receipts(&receipt(local_trace), &receipt(external_trace.clone()))
+			.first_divergent_index,
+		Some(2)
+	);
+	assert!(crate::as_h256(&external_trace[2]).is_ok());
+
+	assert!(matches!(
+		crate::ensure_h256_trace(&external_trace),
+		Err(crate::GossipMessageError::InvalidStateRootType)
+	));
+}
+
+#[test]
+fn blocks_should_be_sampled_for_verification_at_the_configured_rate() {
+	use sp_runtime::Perbill;
+
+	let sampled_blocks = |seed, sampling_rate| {
+		(1..=10_000u64)
+			.filter(|block_number| {
+				crate::is_sampled_for_verification(seed, *block_number, sampling_rate)
+			})
+			.collect::<Vec<_>>()
+	};
+
+	let sampled = sampled_blocks(7, Perbill::from_percent(25));
+	assert!((2_300..=2_700).contains(&sampled.len()), "Sampled {} blocks", sampled.len());
+
+	// The same seed samples the same blocks, another seed samples different ones.
+	assert_eq!(sampled_blocks(7, Perbill::from_percent(25)), sampled);
+	assert_ne!(sampled_blocks(8, Perbill::from_percent(25)), sampled);
+
+	assert_eq!(sampled_blocks(7, Perbill::one()).len(), 10_000);
+	assert!(sampled_blocks(7, Perbill::zero()).is_empty());
+}
+
+#[test]
+fn executor_key_should_follow_the_rotation_of_the_on_chain_executor_id() {
+	use sp_executor::ExecutorId;
+	use sp_keystore::{testing::KeyStore, SyncCryptoStore, SyncCryptoStorePtr};
+	use sp_runtime::RuntimeAppPublic;
+	use std::sync::Arc;
+
+	let keystore: SyncCryptoStorePtr = Arc::new(KeyStore::new());
+	let generate_key = || -> ExecutorId {
+		SyncCryptoStore::sr25519_generate_new(&*keystore, ExecutorId::ID, None)
+			.expect("Failed to generate the executor key")
+			.into()
+	};
+
+	// The keystore holds both the old and the new key while the executor key is rotated.
+	let old_key = generate_key();
+	let new_key = generate_key();
+	let payload = b"bundle or receipt hash";
+
+	// Before the rotation lands on the primary chain, the old key is registered and used.
+	let signature = crate::sign_as_executor(&keystore, &old_key, payload)
+		.unwrap()
+		.expect("The old key is in the keystore");
+	assert!(old_key.verify(&payload, &signature));
+	assert!(!new_key.verify(&payload, &signature));
+
+	// After the rotation, the new key is registered and used.
+	let signature = crate::sign_as_executor(&keystore, &new_key, payload)
+		.unwrap()
+		.expect("The new key is in the keystore");
+	assert!(new_key.verify(&payload, &signature));
+	assert!(!old_key.verify(&payload, &signature));
+
+	// Nothing is signed once another executor not owning any local key is registered.
+	let other_key = ExecutorPair::from_string("//Charlie", None).unwrap().public();
+	assert!(crate::sign_as_executor(&keystore, &other_key, payload).unwrap().is_none());
+}
+
+type CapturedFields = Vec<(&'static str, String)>;
+
+/// Collects the fields of every event followed by the fields of the spans it's emitted within,
+/// formatted with `Debug`.
+#[derive(Clone, Default)]
+struct EventCapture {
+	events: std::sync::Arc<std::sync::Mutex<Vec<CapturedFields>>>,
+	spans: std::sync::Arc<std::sync::Mutex<Vec<CapturedFields>>>,
+	entered: std::sync::Arc<std::sync::Mutex<Vec<u64>>>,
+}
+
+impl EventCapture {
+	/// Returns the events whose message contains `message`.
+	fn events_with_message(&self, message: &str) -> Vec<CapturedFields> {
+		self.events
+			.lock()
+			.unwrap()
+			.iter()
+			.filter(|fields| {
+				fields.iter().any(|(name, value)| *name == "message" && value.contains(message))
+			})
+			.cloned()
+			.collect()
+	}
+}
+
+struct FieldVisitor(Vec<(&'static str, String)>);
+
+impl tracing::field::Visit for FieldVisitor {
+	fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+		self.0.push((field.name(), format!("{value:?}")));
+	}
+}
+
+impl tracing::Subscriber for EventCapture {
+	fn enabled(&self, _metadata: &tracing::Metadata<'_>) -> bool {
+		true
+	}
+	fn new_span(&self, span: &tracing::span::Attributes<'_>) -> tracing::span::Id {
+		let mut visitor = FieldVisitor(Vec::new());
+		span.record(&mut visitor);
+		let mut spans = self.spans.lock().unwrap();
+		spans.push(visitor.0);
+		tracing::span::Id::from_u64(spans.len() as u64)
+	}
+	fn record(&self, _span: &tracing::span::Id, _values: &tracing::span::Record<'_>) {}
+	fn record_follows_from(&self, _span: &tracing::span::Id, _follows: &tracing::span::Id) {}
+	fn event(&self, event: &tracing::Event<'_>) {
+		let mut visitor = FieldVisitor(Vec::new());
+		event.record(&mut visitor);
+		let spans = self.spans.lock().unwrap();
+		for id in self.entered.lock().unwrap().iter().rev() {
+			visitor.0.extend(spans[*id as usize - 1].iter().cloned());
+		}
+		self.events.lock().unwrap().push(visitor.0);
+	}
+	fn enter(&self, span: &tracing::span::Id) {
+		self.entered.lock().unwrap().push(span.into_u64());
+	}
+	fn exit(&self, _span: &tracing::span::Id) {
+		self.entered.lock().unwrap().pop();
+	}
+}
+
+#[test]
+fn trace_divergence_should_be_logged_with_the_index_and_phase() {
+	let local_trace = (0..5u8).map(Hash::repeat_byte).collect::<Vec<_>>();
+	let mut external_trace = local_trace.clone();
+	external_trace[2] = Hash::repeat_byte(0xee);
+	external_trace[3] = Hash::repeat_byte(0xef);
+
+	let execution_receipt = ExecutionReceipt {
+		primary_number: 7u32,
+		primary_hash: Hash::repeat_byte(0xab),
+		secondary_hash: Hash::repeat_byte(0xcd),
+		trace: external_trace.clone(),
+		trace_root: Default::default(),
+	};
+	let local_receipt = ExecutionReceipt { trace: local_trace.clone(), ..execution_receipt.clone() };
+
+	let local_trace_idx =
+		crate::diff_receipts(&local_receipt, &execution_receipt).first_divergent_index.unwrap();
+	let phase = crate::fraud_proof_phase(local_trace_idx, local_trace.len());
+	assert_eq!(local_trace_idx, 2);
+
+	let capture = EventCapture::default();
+	tracing::subscriber::with_default(capture.clone(), || {
+		crate::log_trace_divergence(
+			&execution_receipt,
+			local_trace_idx,
+			phase,
+			&local_trace[local_trace_idx],
+		)
+	});
+
+	let events = capture.events_with_message("diverges from the local one");
+	let divergence = events.first().expect("Divergence must be logged");
+	let field = |name| {
+		divergence
+			.iter()
+			.find_map(|(field_name, value)| (*field_name == name).then(|| value.clone()))
+			.unwrap_or_else(|| panic!("Field {name} must be logged"))
+	};
+
+	assert_eq!(field("local_trace_idx"), "2");
+	assert_eq!(field("phase"), "ApplyExtrinsic(1)");
+	assert_eq!(field("local_root"), format!("{:?}", local_trace[2]));
+	assert_eq!(field("external_root"), format!("{:?}", Some(external_trace[2])));
+	assert_eq!(field("block_number"), "7");
+	assert_eq!(field("primary_hash"), format!("{:?}", Hash::repeat_byte(0xab)));
+
+	// The first and the last roots are produced by the block hooks.
+	assert!(matches!(crate::fraud_proof_phase(0, 5), crate::FraudProofPhase::InitializeBlock));
+	assert!(matches!(crate::fraud_proof_phase(4, 5), crate::FraudProofPhase::FinalizeBlock));
+}
+
+#[test]
+fn logs_should_carry_the_domain_id_of_the_executor_instance() {
+	let receipt = |primary_number| ExecutionReceipt {
+		primary_number,
+		primary_hash: Hash::repeat_byte(0xab),
+		secondary_hash: Hash::repeat_byte(0xcd),
+		trace: vec![Hash::repeat_byte(1)],
+		trace_root: Default::default(),
+	};
+	let local_root = Hash::repeat_byte(2);
+
+	let capture = EventCapture::default();
+	tracing::subscriber::with_default(capture.clone(), || {
+		let first = crate::executor_span(DomainId::new(1));
+		let second = crate::executor_span(DomainId::new(2));
+		first.in_scope(|| {
+			crate::log_trace_divergence(
+				&receipt(1u32),
+				0,
+				crate::FraudProofPhase::InitializeBlock,
+				&local_root,
+			)
+		});
+		second.in_scope(|| {
+			crate::log_trace_divergence(
+				&receipt(2u32),
+				0,
+				crate::FraudProofPhase::InitializeBlock,
+				&local_root,
+			)
+		});
+	});
+
+	let domain_ids = capture
+		.events_with_message("diverges from the local one")
+		.into_iter()
+		.map(|fields| {
+			let field = |name| {
+				fields
+					.iter()
+					.find_map(|(field_name, value)| (*field_name == name).then(|| value.clone()))
+					.unwrap_or_else(|| panic!("Field {name} must be logged"))
+			};
+			(field("block_number"), field("domain_id"))
+		})
+		.collect::<Vec<_>>();
+	assert_eq!(
+		domain_ids,
+		vec![
+			("1".to_string(), format!("{:?}", DomainId::new(1))),
+			("2".to_string(), format!("{:?}", DomainId::new(2))),
+		]
+	);
+}
+
+#[test]
+fn ended_stream_should_be_resubscribed_with_backoff() {
+	use futures::stream::{self, StreamExt};
+	use std::sync::{
+		atomic::{AtomicUsize, Ordering},
+		Arc,
+	};
+
+	let subscriptions = Arc::new(AtomicUsize::new(0));
+	let subscribe = {
+		let subscriptions = subscriptions.clone();
+		move || match subscriptions.fetch_add(1, Ordering::SeqCst) {
+			0 => stream::iter(vec![1, 2]).boxed(),
+			1 => stream::iter(vec![3]).boxed(),
+			// The upstream is gone, every re-subscribed stream ends right away.
+			_ => stream::empty().boxed(),
+		}
+	};
+
+	let capture = EventCapture::default();
+	let items = tracing::subscriber::with_default(capture.clone(), || {
+		futures::executor::block_on(
+			crate::worker::resubscribing(
+				"test",
+				subscribe,
+				(Duration::from_millis(1), Duration::from_millis(4)),
+				3,
+			)
+			.collect::<Vec<_>>(),
+		)
+	});
+
+	// The items are resumed from the re-subscribed stream.
+	assert_eq!(items, vec![1, 2, 3]);
+	// The initial subscription, the one recovering the first stream and 3 fruitless attempts
+	// after the second stream ends.
+	assert_eq!(subscriptions.load(Ordering::SeqCst), 5);
+	assert_eq!(capture.events_with_message("re-subscribing").len(), 4);
+	assert_eq!(capture.events_with_message("giving up").len(), 1);
+}
+
+#[test]
+fn outdated_executor_api_should_be_rejected() {
+	use sp_api::RuntimeApiInfo;
+	use sp_executor::ExecutorApi;
+
+	let error_message = |version| match crate::ensure_executor_api_version(version) {
+		Err(sp_consensus::Error::Other(error)) => error.to_string(),
+		result => panic!("Unexpected result: {result:?}"),
+	};
+
+	assert!(error_message(None).contains("doesn't expose ExecutorApi"));
+	// A v1 runtime lacks `transaction_bundle_extrinsic` and `extract_fraud_proofs`.
+	assert!(error_message(Some(1))
+		.contains("exposes ExecutorApi v1, the minimum supported version is v2"));
+
+	// The test runtime the executor tests run against is supported.
+	let test_runtime_version = subspace_test_runtime::VERSION
+		.api_version(&<dyn ExecutorApi<subspace_test_runtime::Block, Hash>>::ID);
+	assert_eq!(test_runtime_version, Some(2));
+	assert!(crate::ensure_executor_api_version(test_runtime_version).is_ok());
+
+	assert!(crate::ensure_executor_api_version(Some(crate::MIN_EXECUTOR_API_VERSION)).is_ok());
+	assert!(crate::ensure_executor_api_version(Some(crate::MIN_EXECUTOR_API_VERSION + 1)).is_ok());
+}
+
+#[test]
+fn future_receipt_waiters_should_be_capped() {
+	let waiters = crate::FutureReceiptWaiters::new(2);
+
+	let first = waiters.reserve().unwrap();
+	let _second = waiters.reserve().unwrap();
+	assert!(matches!(
+		waiters.reserve(),
+		Err(crate::GossipMessageError::TooManyPendingReceipts { max: 2 })
+	));
+
+	// The slot is released once the waiter is done.
+	drop(first);
+	assert!(waiters.reserve().is_ok());
+
+	let disabled = crate::FutureReceiptWaiters::new(0);
+	assert!(matches!(
+		disabled.reserve(),
+		Err(crate::GossipMessageError::TooManyPendingReceipts { max: 0 })
+	));
+}
+
+#[test]
+fn malformed_bundle_primary_hash_should_be_an_error() {
+	let primary_hash = Hash::random();
+	assert_eq!(crate::decode_primary_hash::<Hash>(primary_hash).unwrap(), primary_hash);
+
+	// The bytes of a 32-byte hash from a peer don't decode to a wider primary hash type.
+	let error = crate::decode_primary_hash::<sp_core::H512>(primary_hash).unwrap_err();
+	assert!(matches!(error, crate::BundleValidationError::MalformedPrimaryHash));
+	assert_eq!(
+		error.reputation_change(),
+		Some(cirrus_client_executor_gossip::cost::UNDECODABLE_MESSAGE)
+	);
+}
+
+#[test]
+fn trace_longer_than_the_block_should_be_rejected() {
+	use crate::{ensure_trace_len, expected_trace_len, GossipMessageError};
+
+	assert_eq!(expected_trace_len(0), 2);
+	assert_eq!(expected_trace_len(3), 5);
+
+	assert!(ensure_trace_len(5, expected_trace_len(3)).is_ok());
+	assert!(ensure_trace_len(1, expected_trace_len(3)).is_ok());
+	assert!(matches!(
+		ensure_trace_len(6, expected_trace_len(3)),
+		Err(GossipMessageError::TraceTooLong { len: 6, max: 5 })
+	));
+}
+
+#[test]
+fn bundles_should_be_routed_to_the_executor_of_their_domain() {
+	use cirrus_client_executor_gossip::Action;
+	use cirrus_test_service::runtime::Block;
+	use sp_runtime::traits::{Block as BlockT, NumberFor};
+
+	type PBlock = subspace_test_runtime::Block;
+
+	/// Records the domain of each bundle it receives.
+	#[derive(Clone, Default)]
+	struct DomainExecutor {
+		bundle_domains: std::sync::Arc<parking_lot::Mutex<Vec<DomainId>>>,
+		execution_receipts: std::sync::Arc<parking_lot::Mutex<usize>>,
+	}
+
+	impl GossipMessageHandler<PBlock, Block> for DomainExecutor {
+		type Error = crate::GossipMessageError;
+
+		fn on_bundle(
+			&self,
+			bundle: &SignedBundle<<Block as BlockT>::Extrinsic>,
+		) -> Result<Action, Self::Error> {
+			self.bundle_domains.lock().push(bundle.bundle.header.domain_id);
+			Ok(Action::Empty)
+		}
+
+		fn on_execution_receipt(
+			&self,
+			_execution_receipt: &SignedExecutionReceipt<
+				NumberFor<PBlock>,
+				<PBlock as BlockT>::Hash,
+				Hash,
+			>,
+		) -> Result<Action, Self::Error> {
+			*self.execution_receipts.lock() += 1;
+			Ok(Action::Empty)
+		}
+	}
+
+	let system_executor = DomainExecutor::default();
+	let core_executor = DomainExecutor::default();
+	let mut domain_registry = crate::DomainRegistry::new();
+	assert!(domain_registry.register(DomainId::SYSTEM, system_executor.clone()).is_none());
+	assert!(domain_registry.register(DomainId::new(1), core_executor.clone()).is_none());
+	assert_eq!(
+		domain_registry.domains().collect::<Vec<_>>(),
+		vec![DomainId::SYSTEM, DomainId::new(1)]
+	);
+
+	let pair = ExecutorPair::from_string("//Alice", None).unwrap();
+	let signed_bundle = |domain_id| {
+		let bundle = Bundle {
+			header: BundleHeader {
+				domain_id,
+				primary_hash: Hash::random(),
+				slot_number: Default::default(),
+				extrinsics_root: Default::default(),
+			},
+			extrinsics: Vec::new(),
+		};
+		SignedBundle { signature: pair.sign(bundle.hash().as_ref()), signer: pair.public(), bundle }
+	};
+
+	for domain_id in [DomainId::new(1), DomainId::SYSTEM, DomainId::new(1), DomainId::new(2)] {
+		assert!(matches!(
+			GossipMessageHandler::<PBlock, Block>::on_bundle(
+				&domain_registry,
+				&signed_bundle(domain_id)
+			),
+			Ok(Action::Empty)
+		));
+	}
+
+	assert_eq!(*system_executor.bundle_domains.lock(), vec![DomainId::SYSTEM]);
+	assert_eq!(*core_executor.bundle_domains.lock(), vec![DomainId::new(1), DomainId::new(1)]);
+
+	// The execution receipts are only produced for the system domain.
+	let execution_receipt = ExecutionReceipt {
+		primary_number: 1,
+		primary_hash: Hash::random(),
+		secondary_hash: Hash::random(),
+		trace: Vec::new(),
+		trace_root: Default::default(),
+	};
+	let signed_execution_receipt = SignedExecutionReceipt {
+		signature: pair.sign(execution_receipt.hash().as_ref()),
+		signer: pair.public(),
+		execution_receipt,
+	};
+	GossipMessageHandler::<PBlock, Block>::on_execution_receipt(
+		&domain_registry,
+		&signed_execution_receipt,
+	)
+	.unwrap();
+	assert_eq!(*system_executor.execution_receipts.lock(), 1);
+	assert_eq!(*core_executor.execution_receipts.lock(), 0);
+}
+
+#[test]
+fn bundle_slot_beyond_the_tolerance_should_be_in_the_future() {
+	use sp_consensus_slots::Slot;
+
+	let tolerance = crate::BUNDLE_SLOT_TOLERANCE;
+
+	// No primary slot has been seen yet.
+	assert!(!crate::is_future_slot(u64::MAX, None, tolerance));
+
+	let latest_slot = Some(Slot::from(100));
+	assert!(!crate::is_future_slot(99, latest_slot, tolerance));
+	assert!(!crate::is_future_slot(100, latest_slot, tolerance));
+	assert!(!crate::is_future_slot(100 + tolerance, latest_slot, tolerance));
+	assert!(crate::is_future_slot(101 + tolerance, latest_slot, tolerance));
+
+	// No skew is tolerated at all.
+	assert!(!crate::is_future_slot(100, latest_slot, 0));
+	assert!(crate::is_future_slot(101, latest_slot, 0));
+}
+
+#[substrate_test_utils::test(flavor = "multi_thread")]
+async fn future_slot_bundle_should_be_rejected() {
+	let (ferdie, alice) = setup_ferdie_and_alice(Default::default()).await;
+
+	alice.wait_for_blocks(1).await;
+
+	let latest_slot: u64 = alice
+		.executor
+		.latest_slot
+		.lock()
+		.expect("Primary slots must have been seen")
+		.into();
+
+	let pair = ExecutorPair::from_string("//Alice", None).unwrap();
+	let signed_bundle_at = |slot_number| {
+		let bundle = Bundle {
+			header: BundleHeader {
+				domain_id: DomainId::SYSTEM,
+				primary_hash: ferdie.client.info().best_hash,
+				slot_number,
+				extrinsics_root: Default::default(),
+			},
+			extrinsics: Vec::new(),
+		};
+		SignedBundle { signature: pair.sign(bundle.hash().as_ref()), signer: pair.public(), bundle }
+	};
+
+	assert!(alice.executor.on_bundle(&signed_bundle_at(latest_slot)).is_ok());
+
+	let future_slot = latest_slot + 1_000;
+	assert!(matches!(
+		alice.executor.on_bundle(&signed_bundle_at(future_slot)),
+		Err(crate::GossipMessageError::FutureBundleSlot { slot, .. }) if slot == future_slot
+	));
+}
+
+#[substrate_test_utils::test(flavor = "multi_thread")]
+async fn bundle_slot_should_be_checked_against_the_configured_skew_tolerance() {
+	let slot_skew_tolerance = 100;
+	let (ferdie, alice) =
+		setup_ferdie_and_alice(crate::ExecutorConfig { slot_skew_tolerance, ..Default::default() })
+			.await;
+
+	alice.wait_for_blocks(1).await;
+
+	let latest_slot: u64 = alice
+		.executor
+		.latest_slot
+		.lock()
+		.expect("Primary slots must have been seen")
+		.into();
+
+	let pair = ExecutorPair::from_string("//Alice", None).unwrap();
+	let signed_bundle_at = |slot_number| {
+		let bundle = Bundle {
+			header: BundleHeader {
+				domain_id: DomainId::SYSTEM,
+				primary_hash: ferdie.client.info().best_hash,
+				slot_number,
+				extrinsics_root: Default::default(),
+			},
+			extrinsics: Vec::new(),
+		};
+		SignedBundle { signature: pair.sign(bundle.hash().as_ref()), signer: pair.public(), bundle }
+	};
+
+	// Within the configured tolerance, although beyond the default one.
+	assert!(alice
+		.executor
+		.on_bundle(&signed_bundle_at(latest_slot + slot_skew_tolerance))
+		.is_ok());
+
+	// The latest slot only moves forward, the margin keeps the bundle beyond the tolerance.
+	let future_slot = latest_slot + slot_skew_tolerance + 1_000;
+	assert!(matches!(
+		alice.executor.on_bundle(&signed_bundle_at(future_slot)),
+		Err(crate::GossipMessageError::FutureBundleSlot { slot, .. }) if slot == future_slot
+	));
+}
+
+#[substrate_test_utils::test(flavor = "multi_thread")]
+async fn validate_bundle_should_reject_invalid_bundles() {
+	let (ferdie, alice) = setup_ferdie_and_alice(Default::default()).await;
+
+	alice.wait_for_blocks(1).await;
+
+	let bundle_at = |primary_hash| Bundle {
+		header: BundleHeader {
+			domain_id: DomainId::SYSTEM,
+			primary_hash,
+			slot_number: Default::default(),
+			extrinsics_root: Default::default(),
+		},
+		extrinsics: Vec::new(),
+	};
+	let sign_bundle = |pair: &ExecutorPair, bundle: Bundle<_>| SignedBundle {
+		signature: pair.sign(bundle.hash().as_ref()),
+		signer: pair.public(),
+		bundle,
+	};
+
+	let alice_pair = ExecutorPair::from_string("//Alice", None).unwrap();
+	let bob_pair = ExecutorPair::from_string("//Bob", None).unwrap();
+	let primary_hash = ferdie.client.info().best_hash;
+
+	assert!(alice
+		.executor
+		.validate_bundle(&sign_bundle(&alice_pair, bundle_at(primary_hash)))
+		.is_ok());
+
+	let mut bad_signature_bundle = sign_bundle(&alice_pair, bundle_at(primary_hash));
+	bad_signature_bundle.signature = alice_pair.sign(b"not the bundle hash");
+	assert!(matches!(
+		alice.executor.validate_bundle(&bad_signature_bundle),
+		Err(crate::BundleValidationError::BadSignature)
+	));
+
+	match alice.executor.validate_bundle(&sign_bundle(&bob_pair, bundle_at(primary_hash))) {
+		Err(crate::BundleValidationError::InvalidAuthor { got, expected }) => {
+			assert_eq!(got, bob_pair.public());
+			assert_eq!(expected, alice_pair.public());
+		},
+		result => panic!("Unexpected result for a bundle of an invalid author: {result:?}"),
+	}
+
+	// The executor of an unknown primary block can not be retrieved.
+	assert!(matches!(
+		alice
+			.executor
+			.validate_bundle(&sign_bundle(&alice_pair, bundle_at(Hash::random()))),
+		Err(crate::BundleValidationError::RuntimeApi(_))
+	));
+
+	// TODO: cover `BundleValidationError::Equivocation` once the bundle equivocation check is
+	// implemented.
+}
+
+#[substrate_test_utils::test(flavor = "multi_thread")]
+async fn execution_receipt_stream_should_notify_all_subscribers() {
+	let (_ferdie, alice) = setup_ferdie_and_alice(Default::default()).await;
+
+	let mut subscriber1 = alice.executor.execution_receipt_stream().subscribe();
+	let mut subscriber2 = alice.executor.execution_receipt_stream().subscribe();
+
+	let receipt1 = subscriber1.next().await.expect("Execution receipt stream must not end");
+	let receipt2 = subscriber2.next().await.expect("Execution receipt stream must not end");
+	assert_eq!(receipt1, receipt2);
+	assert_eq!(receipt1.signer, ExecutorPair::from_string("//Alice", None).unwrap().public());
+}
+
+#[substrate_test_utils::test(flavor = "multi_thread")]
+async fn execution_receipt_should_be_sent_once_confirmed() {
+	let (_ferdie, alice) = setup_ferdie_and_alice(crate::ExecutorConfig {
+		receipt_confirmation_depth: 2,
+		..Default::default()
+	})
+	.await;
+
+	let mut receipts = alice.executor.execution_receipt_stream().subscribe();
+
+	// The receipt of block #1 is only sent once block #3 is imported.
+	let receipt = receipts.next().await.expect("Execution receipt stream must not end");
+	let receipt_number = alice
+		.client
+		.number(receipt.execution_receipt.secondary_hash)
+		.unwrap()
+		.expect("Block of the sent receipt must have been imported");
+	assert_eq!(receipt_number, 1);
+	assert!(alice.client.info().best_number >= receipt_number + 2);
+
+	// The receipts of the blocks below the confirmation depth are never sent.
+	let receipt = receipts.next().await.expect("Execution receipt stream must not end");
+	let receipt_number = alice
+		.client
+		.number(receipt.execution_receipt.secondary_hash)
+		.unwrap()
+		.expect("Block of the sent receipt must have been imported");
+	assert!(alice.client.info().best_number >= receipt_number + 2);
+}
+
+#[substrate_test_utils::test(flavor = "multi_thread")]
+async fn concurrent_provers_should_be_capped() {
+	use std::sync::{
+		atomic::{AtomicUsize, Ordering},
+		Arc,
+	};
+
+	const MAX_CONCURRENT_PROVERS: usize = 2;
+
+	let provers = Arc::new(tokio::sync::Semaphore::new(MAX_CONCURRENT_PROVERS));
+	let running = Arc::new(AtomicUsize::new(0));
+	let max_running = Arc::new(AtomicUsize::new(0));
+
+	let burst = (0..10)
+		.map(|_| {
+			let provers = provers.clone();
+			let running = running.clone();
+			let max_running = max_running.clone();
+			tokio::task::spawn_blocking(move || {
+				futures::executor::block_on(crate::run_prover(&provers, || {
+					let now_running = running.fetch_add(1, Ordering::SeqCst) + 1;
+					max_running.fetch_max(now_running, Ordering::SeqCst);
+					std::thread::sleep(Duration::from_millis(50));
+					running.fetch_sub(1, Ordering::SeqCst);
+				}))
+			})
+		})
+		.collect::<Vec<_>>();
+
+	for prover in burst {
+		prover.await.unwrap();
+	}
+
+	assert!(max_running.load(Ordering::SeqCst) <= MAX_CONCURRENT_PROVERS);
+}
+
+#[substrate_test_utils::test(flavor = "multi_thread")]
+async fn build_fraud_proof_should_work_for_each_phase() {
+	let (ferdie, alice) = setup_ferdie_and_alice(Default::default()).await;
+
+	alice.wait_for_blocks(3).await;
+
+	let transfer_to_charlie = cirrus_test_service::construct_extrinsic(
+		&alice.client,
+		pallet_balances::Call::transfer {
+			dest: cirrus_test_service::runtime::Address::Id(Charlie.public().into()),
+			value: 8,
+		},
+		Alice,
+		false,
+		0,
+	);
+
+	let bundles = vec![OpaqueBundle {
+		header: BundleHeader {
+			domain_id: DomainId::SYSTEM,
+			primary_hash: ferdie.client.info().best_hash,
+			slot_number: Default::default(),
+			extrinsics_root: Default::default(),
+		},
+		opaque_extrinsics: vec![OpaqueExtrinsic::from_bytes(&transfer_to_charlie.encode()).unwrap()],
+	}];
+
+	alice
+		.executor
+		.clone()
+		.process_bundles(
+			(ferdie.client.info().best_hash, ferdie.client.info().best_number),
+			bundles,
+			BlakeTwo256::hash_of(&[1u8; 64]).into(),
+			None,
+		)
+		.await;
+
+	let best_hash = alice.client.info().best_hash;
+	let header = alice.client.header(&BlockId::Hash(best_hash)).unwrap().unwrap();
+	let parent_header =
+		alice.client.header(&BlockId::Hash(*header.parent_hash())).unwrap().unwrap();
+
+	let intermediate_roots: Vec<Hash> = alice
+		.client
+		.runtime_api()
+		.intermediate_roots(&BlockId::Hash(best_hash))
+		.expect("Get intermediate roots")
+		.into_iter()
+		.map(Into::into)
+		.collect();
+	assert_eq!(intermediate_roots.len(), 2);
+
+	let prover = subspace_fraud_proof::ExecutionProver::new(
+		alice.backend.clone(),
+		alice.code_executor.clone(),
+		Box::new(alice.task_manager.spawn_handle()),
+	);
+
+	let phases = [
+		(
+			crate::FraudProofPhase::InitializeBlock,
+			*parent_header.state_root(),
+			intermediate_roots[0],
+		),
+		(crate::FraudProofPhase::ApplyExtrinsic(0), intermediate_roots[0], intermediate_roots[1]),
+		(crate::FraudProofPhase::FinalizeBlock, intermediate_roots[1], *header.state_root()),
+	];
+
+	for (phase_kind, pre_state_root, post_state_root) in phases {
+		let fraud_proof = alice
+			.executor
+			.build_fraud_proof(
+				&parent_header,
+				best_hash,
+				phase_kind,
+				(pre_state_root, post_state_root),
+			)
+			.unwrap_or_else(|err| panic!("Failed to build fraud proof for {phase_kind:?}: {err}"));
+
+		assert_eq!(fraud_proof.parent_number, *parent_header.number());
+		assert_eq!(fraud_proof.parent_hash, parent_header.hash());
+		assert_eq!(fraud_proof.pre_state_root, pre_state_root);
+		assert_eq!(fraud_proof.post_state_root, post_state_root);
+		match (phase_kind, &fraud_proof.execution_phase) {
+			(crate::FraudProofPhase::InitializeBlock, ExecutionPhase::InitializeBlock { .. }) |
+			(crate::FraudProofPhase::FinalizeBlock, ExecutionPhase::FinalizeBlock) => {},
+			(
+				crate::FraudProofPhase::ApplyExtrinsic(_),
+				ExecutionPhase::ApplyExtrinsic { call_data },
+			) => assert_eq!(*call_data, transfer_to_charlie.encode()),
+			(phase_kind, execution_phase) =>
+				panic!("Unexpected execution phase {execution_phase:?} for {phase_kind:?}"),
+		}
+
+		let execution_result = prover
+			.check_execution_proof(
+				BlockId::Hash(parent_header.hash()),
+				&fraud_proof.execution_phase,
+				pre_state_root,
+				fraud_proof.proof.clone(),
+			)
+			.unwrap_or_else(|err| panic!("Failed to check the proof for {phase_kind:?}: {err}"));
+		let post_execution_root = fraud_proof
+			.execution_phase
+			.decode_execution_result::<Header>(execution_result)
+			.unwrap();
+		assert_eq!(post_execution_root, post_state_root);
+	}
+}
+
+#[substrate_test_utils::test(flavor = "multi_thread")]
+async fn bundle_production_should_be_toggleable() {
+	use sc_client_api::BlockBackend;
+	use sp_executor::ExecutorApi;
+
+	let (ferdie, alice) = setup_ferdie_and_alice(Default::default()).await;
+
+	let bundles_at = |number: BlockNumber| {
+		let block_id = BlockId::Number(number);
+		let extrinsics = ferdie
+			.client
+			.block_body(&block_id)
+			.unwrap()
+			.expect("Primary block body must exist");
+		ferdie
+			.client
+			.runtime_api()
+			.extract_bundles(
+				&block_id,
+				extrinsics
+					.into_iter()
+					.map(|xt| OpaqueExtrinsic::from_bytes(&xt.encode()).unwrap())
+					.collect(),
+			)
+			.unwrap()
+			.len()
+	};
+
+	alice.wait_for_blocks(3).await;
+	assert!(alice.executor.is_producing());
+
+	alice.executor.set_producing(false);
+	assert!(!alice.executor.is_producing());
+
+	// Let the bundles submitted before halting the production be included.
+	alice.wait_for_blocks(2).await;
+	let halted_from = ferdie.client.info().best_number + 1;
+	alice.wait_for_blocks(3).await;
+	let halted_to = ferdie.client.info().best_number;
+	assert!((halted_from..=halted_to).all(|number| bundles_at(number) == 0));
+
+	alice.executor.set_producing(true);
+	assert!(alice.executor.is_producing());
+
+	let resumed_from = ferdie.client.info().best_number + 1;
+	alice.wait_for_blocks(3).await;
+	let resumed_to = ferdie.client.info().best_number;
+	assert!((resumed_from..=resumed_to).any(|number| bundles_at(number) > 0));
+}
+
+#[substrate_test_utils::test(flavor = "multi_thread")]
+async fn missing_bundle_should_be_recovered_from_the_primary_block() {
+	let (ferdie, alice) = setup_ferdie_and_alice(Default::default()).await;
+
+	let transfer_to_charlie = cirrus_test_service::construct_extrinsic(
+		&alice.client,
+		pallet_balances::Call::transfer {
+			dest: cirrus_test_service::runtime::Address::Id(Charlie.public().into()),
+			value: 8,
+		},
+		Alice,
+		false,
+		0,
+	);
+	alice
+		.send_extrinsic(transfer_to_charlie.clone())
+		.await
+		.expect("Failed to send extrinsic");
+
+	alice.wait_for_blocks(3).await;
+
+	let transfer_to_charlie = OpaqueExtrinsic::from_bytes(&transfer_to_charlie.encode()).unwrap();
+
+	// No bundle is stored locally, the bundle of the transfer can only be recovered from the
+	// primary block that includes it.
+	assert!((1..=ferdie.client.info().best_number)
+		.flat_map(|number| {
+			let primary_hash = ferdie.client.hash(number).unwrap().unwrap();
+			alice.executor.recover_bundles(primary_hash).unwrap()
+		})
+		.any(|bundle| bundle.opaque_extrinsics.contains(&transfer_to_charlie)));
+
+	let unknown_primary_hash = Hash::random();
+	assert!(matches!(
+		alice.executor.recover_bundles(unknown_primary_hash),
+		Err(crate::GossipMessageError::BlockBodyMissing(_))
+	));
+}
+
+#[substrate_test_utils::test(flavor = "multi_thread")]
+async fn block_rebuilt_from_the_primary_block_should_match_the_local_receipt() {
+	let (_ferdie, alice) = setup_ferdie_and_alice(Default::default()).await;
+
+	let transfer_to_charlie = cirrus_test_service::construct_extrinsic(
+		&alice.client,
+		pallet_balances::Call::transfer {
+			dest: cirrus_test_service::runtime::Address::Id(Charlie.public().into()),
+			value: 8,
+		},
+		Alice,
+		false,
+		0,
+	);
+	alice
+		.send_extrinsic(transfer_to_charlie)
+		.await
+		.expect("Failed to send extrinsic");
+
+	alice.wait_for_blocks(3).await;
+
+	for block_number in 1..=alice.client.info().best_number {
+		let block_hash = alice.client.hash(block_number).unwrap().unwrap();
+		let parent_hash =
+			*alice.client.header(BlockId::Hash(block_hash)).unwrap().unwrap().parent_hash();
+		let execution_receipt =
+			crate::aux_schema::load_execution_receipt::<_, _, BlockNumber, Hash>(
+				&*alice.backend,
+				block_hash,
+			)
+			.unwrap()
+			.expect("The receipt of the local block must exist");
+
+		assert_eq!(
+			alice
+				.executor
+				.rebuild_trace(parent_hash, execution_receipt.primary_hash)
+				.unwrap(),
+			execution_receipt.trace
+		);
+	}
+}
+
+#[substrate_test_utils::test(flavor = "multi_thread")]
+async fn receipt_of_missed_primary_block_should_be_verified_against_the_rebuilt_block() {
+	use cirrus_test_service::runtime::opaque::Block;
+	use subspace_runtime_primitives::opaque::Block as PBlock;
+
+	let (_ferdie, alice) = setup_ferdie_and_alice(Default::default()).await;
+
+	alice.wait_for_blocks(3).await;
+
+	let block_hash = alice.client.hash(2).unwrap().unwrap();
+	let execution_receipt = crate::aux_schema::load_execution_receipt::<_, _, BlockNumber, Hash>(
+		&*alice.backend,
+		block_hash,
+	)
+	.unwrap()
+	.expect("The receipt of the local block must exist");
+
+	// The local block #2 is built from another primary block as if the local node missed the
+	// primary block of the receipt.
+	let missed_primary_receipt = ExecutionReceipt {
+		primary_hash: Hash::random(),
+		trace: vec![Hash::random(); execution_receipt.trace.len()],
+		..execution_receipt.clone()
+	};
+	crate::aux_schema::write_execution_receipt::<_, Block, PBlock>(
+		&*alice.backend,
+		(block_hash, 2),
+		0,
+		0,
+		&missed_primary_receipt,
+	)
+	.unwrap();
+
+	// The secondary block of the receipt is unknown locally.
+	let pair = ExecutorPair::from_string("//Alice", None).unwrap();
+	let sign = |execution_receipt: ExecutionReceipt<BlockNumber, Hash, Hash>| {
+		SignedExecutionReceipt {
+			signature: pair.sign(execution_receipt.hash().as_ref()),
+			signer: pair.public(),
+			execution_receipt,
+		}
+	};
+	let receipt_of_unknown_block =
+		ExecutionReceipt { secondary_hash: Hash::random(), ..execution_receipt.clone() };
+	assert!(matches!(
+		alice.executor.on_execution_receipt(&sign(receipt_of_unknown_block.clone())).unwrap(),
+		cirrus_client_executor_gossip::Action::RebroadcastExecutionReceipt
+	));
+
+	// The receipt mismatching the rebuilt block is not disputed as there is no local state to
+	// prove against.
+	let mut bad_receipt = receipt_of_unknown_block;
+	*bad_receipt.trace.last_mut().unwrap() = Hash::random();
+	assert!(matches!(
+		alice.executor.on_execution_receipt(&sign(bad_receipt)).unwrap(),
+		cirrus_client_executor_gossip::Action::Empty
+	));
+}
+
+#[substrate_test_utils::test(flavor = "multi_thread")]
+async fn bundles_should_be_extracted_from_the_primary_block_body() {
+	let mut builder = sc_cli::LoggerBuilder::new("");
+	builder.with_colors(false);
+	let _ = builder.init();
+
+	let tokio_handle = tokio::runtime::Handle::current();
+
+	// Start Ferdie
+	let (ferdie, ferdie_network_starter) =
+		run_primary_chain_validator_node(tokio_handle, Ferdie, vec![]);
+	ferdie_network_starter.start_network();
+
+	let pair = ExecutorPair::from_string("//Alice", None).unwrap();
+	let primary_hash = ferdie.client.info().best_hash;
+	let signed_opaque_bundle = |slot_number: u64| {
+		let opaque_bundle = OpaqueBundle {
+			header: BundleHeader {
+				domain_id: DomainId::SYSTEM,
+				primary_hash,
+				slot_number,
+				extrinsics_root: Default::default(),
+			},
+			opaque_extrinsics: vec![OpaqueExtrinsic::from_bytes(&[1u8, 2, 3].encode()).unwrap()],
+		};
+		sp_executor::SignedOpaqueBundle {
+			signature: pair.sign(opaque_bundle.hash().as_ref()),
+			signer: pair.public(),
+			opaque_bundle,
+		}
+	};
+	let signed_opaque_bundles = vec![signed_opaque_bundle(1), signed_opaque_bundle(2)];
+
+	let primary_block_body = signed_opaque_bundles
+		.iter()
+		.map(|signed_opaque_bundle| {
+			subspace_test_runtime::UncheckedExtrinsic::new_unsigned(
+				pallet_executor::Call::submit_transaction_bundle {
+					signed_opaque_bundle: signed_opaque_bundle.clone(),
+				}
+				.into(),
+			)
+		})
+		.collect();
+
+	let extracted_bundles = crate::extract_bundles::<subspace_test_runtime::Block, _, Hash>(
+		&*ferdie.client,
+		primary_hash,
+		primary_block_body,
+	)
+	.unwrap();
+	assert_eq!(
+		extracted_bundles,
+		signed_opaque_bundles
+			.into_iter()
+			.map(|signed_opaque_bundle| signed_opaque_bundle.opaque_bundle)
+			.collect::<Vec<_>>()
+	);
+
+	assert!(crate::extract_bundles::<subspace_test_runtime::Block, _, Hash>(
+		&*ferdie.client,
+		primary_hash,
+		vec![]
+	)
+	.unwrap()
+	.is_empty());
+}
+
+#[substrate_test_utils::test(flavor = "multi_thread")]
+async fn receipt_at_should_follow_the_canonical_primary_block() {
+	let (ferdie, alice) = setup_ferdie_and_alice(Default::default()).await;
+
+	alice.wait_for_blocks(3).await;
+
+	let primary_number = 2;
+	let receipt = alice
+		.executor
+		.receipt_at(primary_number)
+		.unwrap()
+		.expect("Receipt of the processed primary block must exist");
+	assert_eq!(receipt.primary_number, primary_number);
+	assert_eq!(receipt.primary_hash, ferdie.client.hash(primary_number).unwrap().unwrap());
+	assert_eq!(receipt.secondary_hash, alice.client.hash(primary_number).unwrap().unwrap());
+
+	// The primary block hasn't been produced yet.
+	assert!(alice.executor.receipt_at(primary_number + 100).unwrap().is_none());
+}
+
+#[substrate_test_utils::test(flavor = "multi_thread")]
+async fn prefetched_block_body_should_be_served_from_cache() {
+	use sc_client_api::BlockBackend;
+
+	let (_ferdie, alice) = setup_ferdie_and_alice(crate::ExecutorConfig {
+		block_body_prefetch: Some(2),
+		..Default::default()
+	})
+	.await;
+
+	alice.wait_for_blocks(3).await;
+
+	let best_hash = alice.client.info().best_hash;
+	alice.executor.prefetch_block_body(best_hash);
+	let block_body_cache = alice.executor.block_body_cache.clone().unwrap();
+	assert!(block_body_cache.lock().contains(&best_hash));
+	assert_eq!(
+		alice.executor.block_body(best_hash).unwrap(),
+		alice.client.block_body(&BlockId::Hash(best_hash)).unwrap().unwrap()
+	);
+
+	// A cached body is served without touching the client, which doesn't know the block.
+	let unknown_hash = Hash::random();
+	assert!(matches!(
+		alice.executor.block_body(unknown_hash),
+		Err(crate::GossipMessageError::BlockBodyMissing(_))
+	));
+	block_body_cache.lock().put(unknown_hash, Vec::new());
+	assert_eq!(alice.executor.block_body(unknown_hash).unwrap(), Vec::new());
+
+	// The cache is bounded.
+	for _ in 0..3 {
+		block_body_cache.lock().put(Hash::random(), Vec::new());
+	}
+	assert_eq!(block_body_cache.lock().len(), 2);
+}
+
+#[substrate_test_utils::test(flavor = "multi_thread")]
+async fn ready_transactions_should_be_grouped_by_sender() {
+	let (_ferdie, alice) = setup_ferdie_and_alice(Default::default()).await;
+
+	alice.wait_for_blocks(1).await;
+
+	// Keep the transactions in the pool.
+	alice.executor.set_producing(false);
+
+	let transfer = |sender: cirrus_test_service::Keyring, nonce| {
+		cirrus_test_service::construct_extrinsic(
+			&alice.client,
+			pallet_balances::Call::transfer {
+				dest: cirrus_test_service::runtime::Address::Id(Dave.public().into()),
+				value: 8,
+			},
+			Alice,
+			false,
+			nonce,
+		)
+	};
+	let alice_txs = vec![transfer(Alice, 0), transfer(Alice, 1)];
+	let bob_txs = vec![transfer(Bob, 0)];
+	for tx in [alice_txs[0].clone(), bob_txs[0].clone(), alice_txs[1].clone()] {
+		alice.send_extrinsic(tx).await.expect("Failed to send extrinsic");
+	}
+
+	let groups = alice.executor.ready_grouped_by_sender().unwrap();
+	assert_eq!(groups.len(), 2);
+	assert_eq!(groups[&Some(Alice.to_account_id())], alice_txs);
+	assert_eq!(groups[&Some(Bob.to_account_id())], bob_txs);
+}
+
+#[substrate_test_utils::test(flavor = "multi_thread")]
+async fn receipt_rebroadcast_within_the_window_should_be_suppressed() {
+	use cirrus_client_executor_gossip::Action;
+
+	let (_ferdie, alice) = setup_ferdie_and_alice(crate::ExecutorConfig {
+		rebroadcast_suppression_window: Duration::from_secs(60),
+		..Default::default()
+	})
+	.await;
+
+	alice.wait_for_blocks(3).await;
+
+	let execution_receipt = crate::aux_schema::load_execution_receipt(
+		&*alice.backend,
+		alice.client.hash(2).unwrap().unwrap(),
+	)
+	.expect("Failed to load execution receipt from the local aux_db")
+	.expect("The requested execution receipt must exist");
+	let pair = ExecutorPair::from_string("//Alice", None).unwrap();
+	let signed_execution_receipt = SignedExecutionReceipt {
+		signature: pair.sign(execution_receipt.hash().as_ref()),
+		signer: pair.public(),
+		execution_receipt,
+	};
+
+	assert!(matches!(
+		alice.executor.on_execution_receipt(&signed_execution_receipt).unwrap(),
+		Action::RebroadcastExecutionReceipt
+	));
+	assert!(matches!(
+		alice.executor.on_execution_receipt(&signed_execution_receipt).unwrap(),
+		Action::Empty
+	));
+}
+
+#[substrate_test_utils::test(flavor = "multi_thread")]
+async fn fraud_proof_should_be_built_with_the_injected_prover() {
+	use cirrus_test_service::runtime::opaque::Block;
+
+	/// Prover recording the arguments it's invoked with instead of proving anything.
+	#[derive(Default)]
+	struct MockProver {
+		calls: parking_lot::Mutex<Vec<(BlockId<Block>, ExecutionPhase, bool)>>,
+	}
+
+	impl<DB> crate::prover::Prover<Block, DB> for MockProver {
+		fn prove_execution(
+			&self,
+			at: BlockId<Block>,
+			execution_phase: &ExecutionPhase,
+			delta_changes: Option<(DB, Hash)>,
+		) -> Result<StorageProof, crate::prover::ProvingError> {
+			self.calls.lock().push((at, execution_phase.clone(), delta_changes.is_some()));
+			Ok(StorageProof::empty())
+		}
+	}
+
+	let (ferdie, alice) = setup_ferdie_and_alice(Default::default()).await;
+
+	alice.wait_for_blocks(3).await;
+
+	let transfer_to_charlie = cirrus_test_service::construct_extrinsic(
+		&alice.client,
+		pallet_balances::Call::transfer {
+			dest: cirrus_test_service::runtime::Address::Id(Charlie.public().into()),
+			value: 8,
+		},
+		Alice,
+		false,
+		0,
+	);
+
+	let bundles = vec![OpaqueBundle {
+		header: BundleHeader {
+			domain_id: DomainId::SYSTEM,
+			primary_hash: ferdie.client.info().best_hash,
+			slot_number: Default::default(),
+			extrinsics_root: Default::default(),
+		},
+		opaque_extrinsics: vec![OpaqueExtrinsic::from_bytes(&transfer_to_charlie.encode()).unwrap()],
+	}];
+
+	alice
+		.executor
+		.clone()
+		.process_bundles(
+			(ferdie.client.info().best_hash, ferdie.client.info().best_number),
+			bundles,
+			BlakeTwo256::hash_of(&[1u8; 64]).into(),
+			None,
+		)
+		.await;
+
+	let best_hash = alice.client.info().best_hash;
+	let header = alice.client.header(&BlockId::Hash(best_hash)).unwrap().unwrap();
+	let parent_header =
+		alice.client.header(&BlockId::Hash(*header.parent_hash())).unwrap().unwrap();
+	let parent_id = BlockId::Hash(parent_header.hash());
+
+	let expected_initialize_block = ExecutionPhase::InitializeBlock {
+		call_data: Header::new(
+			*parent_header.number() + 1,
+			Default::default(),
+			Default::default(),
+			parent_header.hash(),
+			Default::default(),
+		)
+		.encode(),
+	};
+	let phases = [
+		(crate::FraudProofPhase::InitializeBlock, expected_initialize_block, false),
+		(
+			crate::FraudProofPhase::ApplyExtrinsic(0),
+			ExecutionPhase::ApplyExtrinsic { call_data: transfer_to_charlie.encode() },
+			true,
+		),
+		(crate::FraudProofPhase::FinalizeBlock, ExecutionPhase::FinalizeBlock, true),
+	];
+
+	for (phase_kind, expected_execution_phase, expected_delta_changes) in phases {
+		let prover = MockProver::default();
+
+		let fraud_proof = alice
+			.executor
+			.build_fraud_proof_with(
+				&prover,
+				&parent_header,
+				best_hash,
+				phase_kind,
+				(Default::default(), Default::default()),
+			)
+			.unwrap_or_else(|err| panic!("Failed to build fraud proof for {phase_kind:?}: {err}"));
+
+		assert_eq!(fraud_proof.execution_phase, expected_execution_phase);
+		assert_eq!(
+			prover.calls.into_inner(),
+			vec![(parent_id, expected_execution_phase, expected_delta_changes)],
+			"Unexpected prover invocation for {phase_kind:?}",
+		);
+	}
+}
+
+#[substrate_test_utils::test(flavor = "multi_thread")]
+async fn oversized_bundle_should_be_rejected_early() {
+	let (ferdie, alice) = setup_ferdie_and_alice(crate::ExecutorConfig {
+		max_received_bundle_extrinsics: 1,
+		..Default::default()
+	})
+	.await;
+
+	alice.wait_for_blocks(1).await;
+
+	let transfer_to_charlie = |nonce| {
+		let extrinsic = cirrus_test_service::construct_extrinsic(
+			&alice.client,
+			pallet_balances::Call::transfer {
+				dest: cirrus_test_service::runtime::Address::Id(Charlie.public().into()),
+				value: 8,
+			},
+			Alice,
+			false,
+			nonce,
+		);
+		OpaqueExtrinsic::from_bytes(&extrinsic.encode()).unwrap()
+	};
+
+	let pair = ExecutorPair::from_string("//Alice", None).unwrap();
+	let bundle = Bundle {
+		header: BundleHeader {
+			domain_id: DomainId::SYSTEM,
+			primary_hash: ferdie.client.info().best_hash,
+			slot_number: Default::default(),
+			extrinsics_root: Default::default(),
+		},
+		extrinsics: vec![transfer_to_charlie(0), transfer_to_charlie(1)],
+	};
+	// The bad signature is never checked as the bundle is rejected before the validation.
+	let signed_bundle = SignedBundle {
+		signature: pair.sign(b"not the bundle hash"),
+		signer: pair.public(),
+		bundle,
+	};
+
+	let err = alice.executor.on_bundle(&signed_bundle).unwrap_err();
+	assert!(matches!(
+		err,
+		crate::GossipMessageError::OversizedBundle { extrinsics: 2, max_extrinsics: 1, .. }
+	));
+	// The peer may have relayed the oversized bundle in good faith.
+	assert!(err.reputation_change().is_none());
+}
+
+#[test]
+fn relayers_of_misbehaving_bundles_should_not_be_penalized() {
+	use crate::BundleValidationError;
+
+	let equivocation_error =
+		BundleValidationError::Equivocation(sp_executor::BundleEquivocationProof::dummy_at(1));
+	assert!(equivocation_error.reputation_change().is_none());
+
+	let invalid_author_error = BundleValidationError::InvalidAuthor {
+		got: ExecutorPair::from_string("//Bob", None).unwrap().public(),
+		expected: ExecutorPair::from_string("//Alice", None).unwrap().public(),
+	};
+	assert!(invalid_author_error.reputation_change().is_none());
+
+	assert_eq!(
+		BundleValidationError::BadSignature.reputation_change(),
+		Some(cirrus_client_executor_gossip::cost::BAD_SIGNATURE)
+	);
+}
+
+#[substrate_test_utils::test(flavor = "multi_thread")]
+async fn replayed_trace_should_match_the_stored_receipt() {
+	let (ferdie, alice) = setup_ferdie_and_alice(Default::default()).await;
+
+	alice.wait_for_blocks(3).await;
+
+	let transfer_to_charlie = cirrus_test_service::construct_extrinsic(
+		&alice.client,
+		pallet_balances::Call::transfer {
+			dest: cirrus_test_service::runtime::Address::Id(Charlie.public().into()),
+			value: 8,
+		},
+		Alice,
+		false,
+		0,
+	);
+
+	let bundles = vec![OpaqueBundle {
+		header: BundleHeader {
+			domain_id: DomainId::SYSTEM,
+			primary_hash: ferdie.client.info().best_hash,
+			slot_number: Default::default(),
+			extrinsics_root: Default::default(),
+		},
+		opaque_extrinsics: vec![OpaqueExtrinsic::from_bytes(&transfer_to_charlie.encode()).unwrap()],
+	}];
+
+	alice
+		.executor
+		.clone()
+		.process_bundles(
+			(ferdie.client.info().best_hash, ferdie.client.info().best_number),
+			bundles,
+			BlakeTwo256::hash_of(&[1u8; 64]).into(),
+			None,
+		)
+		.await;
+
+	let best_hash = alice.client.info().best_hash;
+	let execution_receipt = crate::aux_schema::load_execution_receipt(&*alice.backend, best_hash)
+		.expect("Failed to load execution receipt from the local aux_db")
+		.expect("The requested execution receipt must exist");
+	assert_eq!(execution_receipt.trace.len(), 3);
+
+	let replayed_trace = crate::replay_block(&*alice.client, &*alice.backend, best_hash).unwrap();
+	assert_eq!(replayed_trace, execution_receipt.trace);
+}
+
+#[substrate_test_utils::test(flavor = "multi_thread")]
+async fn proving_time_should_be_observed_per_phase() {
+	let (ferdie, alice) = setup_ferdie_and_alice(Default::default()).await;
+
+	alice.wait_for_blocks(3).await;
+
+	let transfer_to_charlie = cirrus_test_service::construct_extrinsic(
+		&alice.client,
+		pallet_balances::Call::transfer {
+			dest: cirrus_test_service::runtime::Address::Id(Charlie.public().into()),
+			value: 8,
+		},
+		Alice,
+		false,
+		0,
+	);
+
+	let bundles = vec![OpaqueBundle {
+		header: BundleHeader {
+			domain_id: DomainId::SYSTEM,
+			primary_hash: ferdie.client.info().best_hash,
+			slot_number: Default::default(),
+			extrinsics_root: Default::default(),
+		},
+		opaque_extrinsics: vec![OpaqueExtrinsic::from_bytes(&transfer_to_charlie.encode()).unwrap()],
+	}];
+
+	alice
+		.executor
+		.clone()
+		.process_bundles(
+			(ferdie.client.info().best_hash, ferdie.client.info().best_number),
+			bundles,
+			BlakeTwo256::hash_of(&[1u8; 64]).into(),
+			None,
+		)
+		.await;
+
+	let best_hash = alice.client.info().best_hash;
+	let header = alice.client.header(&BlockId::Hash(best_hash)).unwrap().unwrap();
+	let parent_header =
+		alice.client.header(&BlockId::Hash(*header.parent_hash())).unwrap().unwrap();
+
+	let proving_metrics =
+		crate::metrics::ProvingMetrics::register(&substrate_prometheus_endpoint::Registry::new())
+			.unwrap();
+	let mut executor = alice.executor.clone();
+	executor.proving_metrics = Some(proving_metrics.clone());
+
+	let apply_extrinsic = ExecutionPhase::ApplyExtrinsic { call_data: Vec::new() };
+	assert_eq!(proving_metrics.observations(&apply_extrinsic), 0);
+
+	executor
+		.build_fraud_proof(
+			&parent_header,
+			best_hash,
+			crate::FraudProofPhase::ApplyExtrinsic(0),
+			(Default::default(), Default::default()),
+		)
+		.unwrap();
+
+	assert_eq!(proving_metrics.observations(&apply_extrinsic), 1);
+	assert_eq!(proving_metrics.observations(&ExecutionPhase::FinalizeBlock), 0);
+}
+
+#[substrate_test_utils::test(flavor = "multi_thread")]
+async fn primary_best_block_should_follow_the_primary_client() {
+	let (ferdie, alice) = setup_ferdie_and_alice(Default::default()).await;
+
+	alice.wait_for_blocks(3).await;
+
+	// Ferdie is the only block author, the primary chain tip of Alice is always known by Ferdie.
+	let primary_best_hash = alice.executor.primary_best_hash();
+	let primary_best_number = ferdie
+		.client
+		.number(primary_best_hash)
+		.unwrap()
+		.expect("Primary best block of Alice must be produced by Ferdie");
+	assert!(primary_best_number >= 3);
+
+	// The tip may move on between the two calls, but never backwards.
+	assert!(alice.executor.primary_best_number() >= primary_best_number);
+	assert!(ferdie.client.info().best_number >= alice.executor.primary_best_number());
+}
+
+#[substrate_test_utils::test(flavor = "multi_thread")]
+async fn executor_debug_output_should_only_contain_the_summary() {
+	let (_ferdie, alice) = setup_ferdie_and_alice(Default::default()).await;
+
+	alice.executor.set_producing(false);
+
+	let debug_output = format!("{:?}", alice.executor);
+	assert!(debug_output.starts_with("Executor {"));
+	assert!(debug_output.contains("is_authority: true"));
+	assert!(debug_output.contains("is_producing: false"));
+	assert!(
+		debug_output.contains(&format!("active_leaves: {}", alice.executor.active_leaves().len()))
+	);
+	for handle in ["client", "backend", "keystore", "transaction_pool", "spawner"] {
+		assert!(!debug_output.contains(handle), "{handle} must not be printed: {debug_output}");
+	}
+}
+
+#[substrate_test_utils::test(flavor = "multi_thread")]
+async fn evaluate_bundle_should_report_each_outcome() {
+	use crate::BundleOutcome;
+
+	let (ferdie, alice) = setup_ferdie_and_alice(Default::default()).await;
+
+	alice.wait_for_blocks(3).await;
+
+	let primary_hash = ferdie.client.info().best_hash;
+	let bundle_of = |domain_id, extrinsics| Bundle {
+		header: BundleHeader {
+			domain_id,
+			primary_hash,
+			slot_number: Default::default(),
+			extrinsics_root: Default::default(),
+		},
+		extrinsics,
+	};
+	let sign_bundle = |pair: &ExecutorPair, bundle: Bundle<_>| SignedBundle {
+		signature: pair.sign(bundle.hash().as_ref()),
+		signer: pair.public(),
+		bundle,
+	};
+
+	let alice_pair = ExecutorPair::from_string("//Alice", None).unwrap();
+	let bob_pair = ExecutorPair::from_string("//Bob", None).unwrap();
+
+	// The sender has no funds to pay the transaction fee.
+	let illegal_tx = cirrus_test_service::construct_extrinsic(
+		&alice.client,
+		pallet_balances::Call::transfer {
+			dest: cirrus_test_service::runtime::Address::Id(Charlie.public().into()),
+			value: 8,
+		},
+		One,
+		false,
+		0,
+	);
+	let signed_bundle = sign_bundle(
+		&alice_pair,
+		bundle_of(
+			DomainId::SYSTEM,
+			vec![OpaqueExtrinsic::from_bytes(&illegal_tx.encode()).unwrap()],
+		),
+	);
+	match alice.executor.evaluate_bundle(&signed_bundle).unwrap() {
+		BundleOutcome::Accepted { rebroadcast, invalid_transaction_proofs, equivocation_proof } => {
+			assert!(rebroadcast);
+			assert!(equivocation_proof.is_none());
+			assert_eq!(invalid_transaction_proofs.len(), 1);
+			assert_eq!(invalid_transaction_proofs[0].bundle_hash, signed_bundle.bundle.hash());
+			assert_eq!(invalid_transaction_proofs[0].extrinsic_index, 0);
+		},
+		outcome => panic!("Unexpected outcome of a valid bundle: {outcome:?}"),
+	}
+	// The same bundle is not rebroadcast again within the window.
+	assert!(matches!(
+		alice.executor.evaluate_bundle(&signed_bundle).unwrap(),
+		BundleOutcome::Accepted { rebroadcast: false, .. }
+	));
+
+	let mut bad_signature_bundle = sign_bundle(&alice_pair, bundle_of(DomainId::SYSTEM, vec![]));
+	bad_signature_bundle.signature = alice_pair.sign(b"not the bundle hash");
+	assert!(matches!(
+		alice.executor.evaluate_bundle(&bad_signature_bundle).unwrap(),
+		BundleOutcome::BadSignature
+	));
+
+	match alice
+		.executor
+		.evaluate_bundle(&sign_bundle(&bob_pair, bundle_of(DomainId::SYSTEM, vec![])))
+		.unwrap()
+	{
+		BundleOutcome::InvalidAuthor { got, expected } => {
+			assert_eq!(got, bob_pair.public());
+			assert_eq!(expected, alice_pair.public());
+		},
+		outcome => panic!("Unexpected outcome of a bundle of an invalid author: {outcome:?}"),
+	}
+
+	assert!(matches!(
+		alice
+			.executor
+			.evaluate_bundle(&sign_bundle(&alice_pair, bundle_of(DomainId::new(1), vec![])))
+			.unwrap(),
+		BundleOutcome::Ignored
+	));
+
+	let mut unknown_primary_block_bundle = bundle_of(DomainId::SYSTEM, vec![]);
+	unknown_primary_block_bundle.header.primary_hash = Hash::random();
+	assert!(matches!(
+		alice
+			.executor
+			.evaluate_bundle(&sign_bundle(&alice_pair, unknown_primary_block_bundle))
+			.unwrap(),
+		BundleOutcome::Ignored
+	));
+
+	// TODO: cover `BundleOutcome::Equivocation` and `BundleOutcome::AlreadyKnown` once the bundle
+	// equivocation check and the bundle pool are implemented.
+}
+
+#[substrate_test_utils::test(flavor = "multi_thread")]
+async fn submitted_dispute_should_not_be_raised_again() {
+	let (_ferdie, alice) = setup_ferdie_and_alice(Default::default()).await;
+
+	alice.wait_for_blocks(3).await;
+
+	let block_hash = alice.client.hash(2).unwrap().unwrap();
+	let mut bad_execution_receipt =
+		crate::aux_schema::load_execution_receipt(&*alice.backend, block_hash)
+			.expect("Failed to load execution receipt from the local aux_db")
+			.expect("The requested execution receipt must exist");
+	let mismatch_idx = bad_execution_receipt.trace.len() - 1;
+	bad_execution_receipt.trace[mismatch_idx] = Hash::random();
+
+	let pair = ExecutorPair::from_string("//Alice", None).unwrap();
+	let signature = pair.sign(bad_execution_receipt.hash().as_ref());
+	let mismatched_receipt = SignedExecutionReceipt {
+		execution_receipt: bad_execution_receipt,
+		signature,
+		signer: pair.public(),
+	};
+
+	assert!(matches!(
+		alice.executor.evaluate_execution_receipt(&mismatched_receipt).unwrap(),
+		crate::ExecutionReceiptDecision::WouldSubmitFraudProof(_)
+	));
+
+	// The fraud proof of this dispute was submitted before the restart, only the aux-db survives.
+	crate::aux_schema::write_submitted_dispute(&*alice.backend, block_hash, mismatch_idx as u32)
+		.unwrap();
+
+	assert!(matches!(
+		alice.executor.evaluate_execution_receipt(&mismatched_receipt).unwrap(),
+		crate::ExecutionReceiptDecision::Ignored
+	));
+	assert!(matches!(
+		alice.executor.on_execution_receipt(&mismatched_receipt).unwrap(),
+		cirrus_client_executor_gossip::Action::Empty
+	));
+
+	// A dispute over another trace root of the same block is still raised.
+	let mut other_receipt = mismatched_receipt.execution_receipt.clone();
+	other_receipt.trace[mismatch_idx - 1] = Hash::random();
+	let signature = pair.sign(other_receipt.hash().as_ref());
+	let other_receipt = SignedExecutionReceipt {
+		execution_receipt: other_receipt,
+		signature,
+		signer: pair.public(),
+	};
+	assert!(matches!(
+		alice.executor.evaluate_execution_receipt(&other_receipt).unwrap(),
+		crate::ExecutionReceiptDecision::WouldSubmitFraudProof(_)
+	));
+}
+
+#[substrate_test_utils::test(flavor = "multi_thread")]
+async fn secondary_block_built_offline_should_match_the_imported_one() {
+	use sc_client_api::BlockBackend;
+	use sp_runtime::traits::Block as BlockT;
+
+	let (ferdie, alice) = setup_ferdie_and_alice(Default::default()).await;
+
+	alice.wait_for_blocks(3).await;
+
+	let transfer = |dest: cirrus_test_service::Keyring, nonce| {
+		cirrus_test_service::construct_extrinsic(
+			&alice.client,
+			pallet_balances::Call::transfer {
+				dest: cirrus_test_service::runtime::Address::Id(dest.public().into()),
+				value: 8,
+			},
+			Alice,
+			false,
+			nonce,
+		)
+	};
+	let opaque_extrinsics = [transfer(Charlie, 0), transfer(Dave, 1)]
+		.iter()
+		.map(|xt| OpaqueExtrinsic::from_bytes(&xt.encode()).unwrap())
+		.collect();
+
+	let bundles = vec![OpaqueBundle {
+		header: BundleHeader {
+			domain_id: DomainId::SYSTEM,
+			primary_hash: ferdie.client.info().best_hash,
+			slot_number: Default::default(),
+			extrinsics_root: Default::default(),
+		},
+		opaque_extrinsics,
+	}];
+	let shuffling_seed = BlakeTwo256::hash_of(&[1u8; 64]).into();
+
+	let parent_hash = alice.client.info().best_hash;
+	let (block, trace) = crate::build_secondary_block(
+		&*alice.client,
+		&*alice.backend,
+		parent_hash,
+		bundles.clone(),
+		shuffling_seed,
+		None,
+	)
+	.unwrap();
+
+	// Building offline leaves the chain untouched.
+	assert_eq!(alice.client.info().best_hash, parent_hash);
+
+	alice
+		.executor
+		.clone()
+		.process_bundles(
+			(ferdie.client.info().best_hash, ferdie.client.info().best_number),
+			bundles,
+			shuffling_seed,
+			None,
+		)
+		.await;
+
+	let best_hash = alice.client.info().best_hash;
+	assert_eq!(block.header().hash(), best_hash);
+	assert_eq!(
+		alice.client.block_body(&BlockId::Hash(best_hash)).unwrap().unwrap(),
+		block.extrinsics().to_vec()
+	);
+
+	let execution_receipt = crate::aux_schema::load_execution_receipt::<_, _, BlockNumber, Hash>(
+		&*alice.backend,
+		best_hash,
+	)
+	.expect("Failed to load execution receipt from the local aux_db")
+	.expect("The requested execution receipt must exist");
+	assert_eq!(execution_receipt.secondary_hash, block.header().hash());
+	assert_eq!(execution_receipt.trace, trace);
+}
+
+#[substrate_test_utils::test(flavor = "multi_thread")]
+async fn receipts_should_be_ignored_while_major_syncing() {
+	use cirrus_client_executor_gossip::Action;
+	use std::sync::{
+		atomic::{AtomicBool, Ordering},
+		Arc,
+	};
+
+	let (_ferdie, alice) = setup_ferdie_and_alice(Default::default()).await;
+
+	alice.wait_for_blocks(3).await;
+
+	let execution_receipt = crate::aux_schema::load_execution_receipt(
+		&*alice.backend,
+		alice.client.hash(2).unwrap().unwrap(),
+	)
+	.expect("Failed to load execution receipt from the local aux_db")
+	.expect("The requested execution receipt must exist");
+	let pair = ExecutorPair::from_string("//Alice", None).unwrap();
+	let signed_execution_receipt = SignedExecutionReceipt {
+		signature: pair.sign(execution_receipt.hash().as_ref()),
+		signer: pair.public(),
+		execution_receipt,
+	};
+
+	let major_syncing = Arc::new(AtomicBool::new(true));
+	let mut executor = alice.executor.clone();
+	executor.is_major_syncing = {
+		let major_syncing = major_syncing.clone();
+		Arc::new(move || major_syncing.load(Ordering::SeqCst))
+	};
+
+	assert!(matches!(
+		executor.on_execution_receipt(&signed_execution_receipt).unwrap(),
+		Action::Empty
+	));
+
+	major_syncing.store(false, Ordering::SeqCst);
+	assert!(matches!(
+		executor.on_execution_receipt(&signed_execution_receipt).unwrap(),
+		Action::RebroadcastExecutionReceipt
+	));
+}
+
+#[substrate_test_utils::test(flavor = "multi_thread")]
+async fn produced_bundle_should_be_submitted_to_the_primary_chain() {
+	let (ferdie, alice) = setup_ferdie_and_alice(Default::default()).await;
+
+	alice.wait_for_blocks(1).await;
+
+	let slot_info = crate::worker::ExecutorSlotInfo {
+		slot: sp_consensus_slots::Slot::from(u64::MAX),
+		global_challenge: Default::default(),
+	};
+	let signed_bundle = alice
+		.executor
+		.bundle_producer
+		.clone()
+		.produce_bundle(ferdie.client.info().best_hash, slot_info)
+		.await
+		.unwrap()
+		.expect("Alice is an authority and must produce the bundle");
+
+	let tx_hash = alice.executor.submit_bundle(signed_bundle.clone()).await.unwrap();
+
+	let expected_tx = subspace_test_runtime::UncheckedExtrinsic::new_unsigned(
+		pallet_executor::Call::submit_transaction_bundle { signed_opaque_bundle: signed_bundle }
+			.into(),
+	);
+	assert_eq!(tx_hash, ferdie.transaction_pool.pool().hash_of(&expected_tx.into()));
+	assert!(ferdie
+		.transaction_pool
+		.pool()
+		.validated_pool()
+		.ready_by_hash(&tx_hash)
+		.is_some());
+}
+
+#[substrate_test_utils::test(flavor = "multi_thread")]
+async fn proofs_should_be_submitted_via_the_injected_submitter() {
+	use std::sync::Arc;
+
+	let (_ferdie, alice) = setup_ferdie_and_alice(Default::default()).await;
+
+	alice.wait_for_blocks(1).await;
+
+	let submitter = Arc::new(RecordingSubmitter::default());
+	let executor = alice.executor.clone().with_proof_submitter(submitter.clone());
+
+	let fraud_proof = FraudProof {
+		parent_number: 0,
+		parent_hash: Hash::random(),
+		pre_state_root: Hash::random(),
+		post_state_root: Hash::random(),
+		proof: StorageProof::empty(),
+		execution_phase: ExecutionPhase::FinalizeBlock,
+	};
+	executor.submit_fraud_proof_awaitable(fraud_proof.clone(), None).await.unwrap();
+
+	let bundle_equivocation_proof = BundleEquivocationProof::dummy_at(1);
+	executor.submit_bundle_equivocation_proof(bundle_equivocation_proof.clone(), None);
+	let invalid_transaction_proof = InvalidTransactionProof {
+		bundle_hash: Default::default(),
+		extrinsic_index: 2,
+		extrinsic: vec![1, 2, 3],
+	};
+	executor.submit_invalid_transaction_proof(invalid_transaction_proof.clone(), None);
+
+	// The equivocation and invalid transaction proofs are submitted in the background.
+	tokio::time::timeout(Duration::from_secs(10), async {
+		while submitter.bundle_equivocation_proofs.lock().is_empty() ||
+			submitter.invalid_transaction_proofs.lock().is_empty()
+		{
+			tokio::time::sleep(Duration::from_millis(10)).await;
+		}
+	})
+	.await
+	.expect("Proofs must be submitted in the background");
+
+	assert_eq!(*submitter.fraud_proofs.lock(), vec![fraud_proof]);
+	assert_eq!(*submitter.bundle_equivocation_proofs.lock(), vec![bundle_equivocation_proof]);
+	assert_eq!(*submitter.invalid_transaction_proofs.lock(), vec![invalid_transaction_proof]);
+}
+
+#[substrate_test_utils::test(flavor = "multi_thread")]
+async fn warm_up_should_load_the_recent_receipt_blocks() {
+	use parking_lot::Mutex;
+	use sc_client_api::BlockBackend;
+	use std::sync::Arc;
+
+	let (_ferdie, alice) = setup_ferdie_and_alice(crate::ExecutorConfig {
+		block_body_prefetch: Some(16),
+		warm_up_depth: Some(3),
+		..Default::default()
+	})
+	.await;
+
+	alice.wait_for_blocks(5).await;
+
+	// Start over with an empty cache as after a restart.
+	let block_body_cache = Arc::new(Mutex::new(lru::LruCache::new(16)));
+	let mut executor = alice.executor.clone();
+	executor.block_body_cache = Some(block_body_cache.clone());
+
+	let best_number = alice.client.info().best_number;
+	executor.warm_up(3).unwrap();
+
+	// More blocks may be imported meanwhile, the warm-up starts from the best block at the time.
+	let block_body_cache = block_body_cache.lock();
+	assert_eq!(block_body_cache.len(), 3);
+	for (block_hash, extrinsics) in block_body_cache.iter() {
+		let block_number = alice.client.number(*block_hash).unwrap().unwrap();
+		assert!(block_number + 3 > best_number);
+		assert_eq!(
+			*extrinsics,
+			alice.client.block_body(&BlockId::Hash(*block_hash)).unwrap().unwrap()
+		);
+	}
+}
+
+#[substrate_test_utils::test(flavor = "multi_thread")]
+async fn extrinsic_timings_should_be_recorded_for_each_extrinsic() {
+	use sc_client_api::BlockBackend;
+
+	let (ferdie, alice) = setup_ferdie_and_alice(crate::ExecutorConfig {
+		extrinsic_timings_capacity: Some(16),
+		..Default::default()
+	})
+	.await;
+
+	alice.wait_for_blocks(3).await;
+
+	let test_txs = (0..3)
+		.map(|nonce| {
+			cirrus_test_service::construct_extrinsic(
+				&alice.client,
+				pallet_balances::Call::transfer {
+					dest: cirrus_test_service::runtime::Address::Id(Charlie.public().into()),
+					value: 8,
+				},
+				Alice,
+				false,
+				nonce,
+			)
+		})
+		.collect::<Vec<_>>();
+
+	let bundles = vec![OpaqueBundle {
+		header: BundleHeader {
+			domain_id: DomainId::SYSTEM,
+			primary_hash: ferdie.client.info().best_hash,
+			slot_number: Default::default(),
+			extrinsics_root: Default::default(),
+		},
+		opaque_extrinsics: test_txs
+			.iter()
+			.map(|xt| OpaqueExtrinsic::from_bytes(&xt.encode()).unwrap())
+			.collect(),
+	}];
+
+	alice
+		.executor
+		.clone()
+		.process_bundles(
+			(ferdie.client.info().best_hash, ferdie.client.info().best_number),
+			bundles,
+			BlakeTwo256::hash_of(&[1u8; 64]).into(),
+			None,
+		)
+		.await;
+
+	let best_hash = alice.client.info().best_hash;
+	let body = alice.client.block_body(&BlockId::Hash(best_hash)).unwrap().unwrap();
+	let encoded_body = body.iter().map(Encode::encode).collect::<Vec<_>>();
+	assert!(test_txs.iter().all(|xt| encoded_body.contains(&xt.encode())));
+
+	let extrinsic_timings = alice
+		.executor
+		.extrinsic_timings(best_hash)
+		.expect("Timings of the locally built block must be recorded");
+	assert_eq!(extrinsic_timings.len(), body.len());
+
+	// No timings are kept when disabled.
+	let mut executor = alice.executor.clone();
+	executor.extrinsic_timings = None;
+	assert!(executor.extrinsic_timings(best_hash).is_none());
+}
+
+#[substrate_test_utils::test(flavor = "multi_thread")]
+async fn over_long_trace_should_be_rejected() {
+	let (_ferdie, alice) =
+		setup_ferdie_and_alice(crate::ExecutorConfig { max_trace_len: 16, ..Default::default() })
+			.await;
+
+	alice.wait_for_blocks(3).await;
+
+	let sign_execution_receipt = |execution_receipt: ExecutionReceipt<_, _, _>| {
+		let pair = ExecutorPair::from_string("//Alice", None).unwrap();
+		let signature = pair.sign(execution_receipt.hash().as_ref());
+		SignedExecutionReceipt { execution_receipt, signature, signer: pair.public() }
+	};
+
+	let block_hash = alice.client.hash(2).unwrap().unwrap();
+	let execution_receipt = crate::aux_schema::load_execution_receipt(&*alice.backend, block_hash)
+		.expect("Failed to load execution receipt from the local aux_db")
+		.expect("The requested execution receipt must exist");
+
+	// One root more than the local block can produce, the final root still matches.
+	let mut over_long_receipt = execution_receipt.clone();
+	let final_root = *over_long_receipt.trace.last().unwrap();
+	over_long_receipt.trace.push(final_root);
+	let max = execution_receipt.trace.len();
+	assert!(matches!(
+		alice.executor.on_execution_receipt(&sign_execution_receipt(over_long_receipt)),
+		Err(crate::GossipMessageError::TraceTooLong { len, max: got_max })
+			if len == max + 1 && got_max == max
+	));
+
+	// Beyond the configured maximum, rejected before the local block is even looked up.
+	let mut huge_receipt = execution_receipt;
+	huge_receipt.trace.resize(17, final_root);
+	assert!(matches!(
+		alice.executor.on_execution_receipt(&sign_execution_receipt(huge_receipt)),
+		Err(crate::GossipMessageError::TraceTooLong { len: 17, max: 16 })
+	));
+}
+
+#[substrate_test_utils::test(flavor = "multi_thread")]
+async fn invalid_transaction_proof_should_be_sent_to_the_stream() {
+	let (ferdie, alice) = setup_ferdie_and_alice(Default::default()).await;
+
+	alice.wait_for_blocks(3).await;
+
+	let mut invalid_transaction_proofs =
+		alice.executor.invalid_transaction_proof_stream().subscribe();
+
+	let legal_tx = cirrus_test_service::construct_extrinsic(
+		&alice.client,
+		pallet_balances::Call::transfer {
+			dest: cirrus_test_service::runtime::Address::Id(Charlie.public().into()),
+			value: 8,
+		},
+		Alice,
+		false,
+		0,
+	);
+	// The sender has no funds to pay the transaction fee.
+	let illegal_tx = cirrus_test_service::construct_extrinsic(
+		&alice.client,
+		pallet_balances::Call::transfer {
+			dest: cirrus_test_service::runtime::Address::Id(Charlie.public().into()),
+			value: 8,
+		},
+		One,
+		false,
+		0,
+	);
+
+	let bundle = Bundle {
+		header: BundleHeader {
+			domain_id: DomainId::SYSTEM,
+			primary_hash: ferdie.client.info().best_hash,
+			slot_number: Default::default(),
+			extrinsics_root: Default::default(),
+		},
+		extrinsics: vec![
+			OpaqueExtrinsic::from_bytes(&legal_tx.encode()).unwrap(),
+			OpaqueExtrinsic::from_bytes(&illegal_tx.encode()).unwrap(),
+		],
+	};
+	let pair = ExecutorPair::from_string("//Alice", None).unwrap();
+	let signed_bundle = SignedBundle {
+		signature: pair.sign(bundle.hash().as_ref()),
+		signer: pair.public(),
+		bundle,
+	};
+
+	alice.executor.on_bundle(&signed_bundle).unwrap();
+
+	let invalid_transaction_proof =
+		tokio::time::timeout(Duration::from_secs(10), invalid_transaction_proofs.next())
+			.await
+			.expect("Invalid transaction proof must be sent")
+			.expect("Invalid transaction proof stream must not end");
+	assert_eq!(invalid_transaction_proof.bundle_hash, signed_bundle.bundle.hash());
+	assert_eq!(invalid_transaction_proof.extrinsic_index, 1);
+	assert_eq!(invalid_transaction_proof.extrinsic, illegal_tx.encode());
+
+	// Only the illegal transaction is reported.
+	assert!(tokio::time::timeout(Duration::from_millis(500), invalid_transaction_proofs.next())
+		.await
+		.is_err());
+}
+
+#[substrate_test_utils::test(flavor = "multi_thread")]
+async fn produced_receipt_should_be_found_by_its_hash() {
+	let (_ferdie, alice) = setup_ferdie_and_alice(Default::default()).await;
+
+	alice.wait_for_blocks(3).await;
+
+	let block_hash = alice.client.hash(2).unwrap().unwrap();
+	let execution_receipt = crate::aux_schema::load_execution_receipt::<_, _, BlockNumber, Hash>(
+		&*alice.backend,
+		block_hash,
+	)
+	.expect("Failed to load execution receipt from the local aux_db")
+	.expect("The requested execution receipt must exist");
+
+	assert_eq!(
+		alice.executor.block_hash_by_receipt_hash(execution_receipt.hash()).unwrap(),
+		Some(block_hash)
+	);
+	assert_eq!(alice.executor.block_hash_by_receipt_hash(Hash::random()).unwrap(), None);
+}
+
+#[substrate_test_utils::test(flavor = "multi_thread")]
+async fn equivocation_policy_should_decide_whether_to_process_the_bundle() {
+	use crate::{BundleValidationError, EquivocationPolicy, GossipMessageError};
+	use cirrus_client_executor_gossip::Action;
+	use std::sync::Arc;
+
+	let (ferdie, alice) = setup_ferdie_and_alice(Default::default()).await;
+
+	alice.wait_for_blocks(3).await;
+
+	let equivocation_proof = BundleEquivocationProof::dummy_at(1);
+	let executor_with_policy = |equivocation_policy, submitter: Arc<RecordingSubmitter>| {
+		let mut executor = alice.executor.clone().with_proof_submitter(submitter);
+		executor.equivocation_policy = equivocation_policy;
+		executor.check_bundle_equivocation = {
+			let equivocation_proof = equivocation_proof.clone();
+			Arc::new(move |_bundle: &Bundle<_>| Some(equivocation_proof.clone()))
+		};
+		executor
+	};
+
+	// The sender has no funds to pay the transaction fee, the bundle is only processed if its
+	// invalid transaction proof is sent.
+	let illegal_tx = cirrus_test_service::construct_extrinsic(
+		&alice.client,
+		pallet_balances::Call::transfer {
+			dest: cirrus_test_service::runtime::Address::Id(Charlie.public().into()),
+			value: 8,
+		},
+		One,
+		false,
+		0,
+	);
+	let signed_bundle_at = |slot_number| {
+		let bundle = Bundle {
+			header: BundleHeader {
+				domain_id: DomainId::SYSTEM,
+				primary_hash: ferdie.client.info().best_hash,
+				slot_number,
+				extrinsics_root: Default::default(),
+			},
+			extrinsics: vec![OpaqueExtrinsic::from_bytes(&illegal_tx.encode()).unwrap()],
+		};
+		let pair = ExecutorPair::from_string("//Alice", None).unwrap();
+		SignedBundle { signature: pair.sign(bundle.hash().as_ref()), signer: pair.public(), bundle }
+	};
+
+	let wait_for_equivocation_proof = |submitter: Arc<RecordingSubmitter>| async move {
+		tokio::time::timeout(Duration::from_secs(10), async {
+			while submitter.bundle_equivocation_proofs.lock().is_empty() {
+				tokio::time::sleep(Duration::from_millis(10)).await;
+			}
+		})
+		.await
+		.expect("Equivocation proof must be submitted in the background");
+		submitter.bundle_equivocation_proofs.lock().clone()
+	};
+
+	let mut invalid_transaction_proofs =
+		alice.executor.invalid_transaction_proof_stream().subscribe();
+
+	// Processing stops at the equivocation.
+	let submitter = Arc::new(RecordingSubmitter::default());
+	let executor = executor_with_policy(EquivocationPolicy::ReportAndAbort, submitter.clone());
+	assert!(matches!(
+		executor.on_bundle(&signed_bundle_at(0)),
+		Err(GossipMessageError::BundleValidation(BundleValidationError::Equivocation(_)))
+	));
+	assert_eq!(wait_for_equivocation_proof(submitter).await, vec![equivocation_proof.clone()]);
+	assert!(tokio::time::timeout(Duration::from_millis(500), invalid_transaction_proofs.next())
+		.await
+		.is_err());
+
+	// Processing goes on after reporting the equivocation.
+	let submitter = Arc::new(RecordingSubmitter::default());
+	let executor = executor_with_policy(EquivocationPolicy::ReportAndContinue, submitter.clone());
+	assert!(matches!(executor.on_bundle(&signed_bundle_at(1)), Ok(Action::RebroadcastBundle)));
+	assert_eq!(wait_for_equivocation_proof(submitter).await, vec![equivocation_proof]);
+	let invalid_transaction_proof =
+		tokio::time::timeout(Duration::from_secs(10), invalid_transaction_proofs.next())
+			.await
+			.expect("Invalid transaction proof must be sent")
+			.expect("Invalid transaction proof stream must not end");
+	assert_eq!(invalid_transaction_proof.extrinsic, illegal_tx.encode());
+}
+
+#[substrate_test_utils::test(flavor = "multi_thread")]
+async fn light_pool_revalidation_should_recheck_the_retracted_transactions() {
+	use sc_transaction_pool_api::{
+		ChainEvent, InPoolTransaction, MaintainedTransactionPool, TransactionPool,
+	};
+
+	let mut builder = sc_cli::LoggerBuilder::new("");
+	builder.with_colors(false);
+	let _ = builder.init();
+
+	let tokio_handle = tokio::runtime::Handle::current();
+
+	// Start Ferdie
+	let (ferdie, ferdie_network_starter) =
+		run_primary_chain_validator_node(tokio_handle.clone(), Ferdie, vec![]);
+	ferdie_network_starter.start_network();
+
+	// Run Alice (a secondary chain authority node)
+	let alice = cirrus_test_service::TestNodeBuilder::new(tokio_handle.clone(), Alice)
+		.connect_to_primary_chain_node(&ferdie)
+		.transaction_pool_revalidation(cirrus_test_service::RevalidationType::Light)
+		.build(Role::Authority)
+		.await;
+
+	alice.wait_for_blocks(3).await;
+
+	let transfer = cirrus_test_service::construct_extrinsic(
+		&alice.client,
+		pallet_balances::Call::transfer {
+			dest: cirrus_test_service::runtime::Address::Id(Charlie.public().into()),
+			value: 8,
+		},
+		Alice,
+		false,
+		0,
+	);
+
+	// Include the transfer in a block without it ever entering the pool.
+	let bundles = vec![OpaqueBundle {
+		header: BundleHeader {
+			domain_id: DomainId::SYSTEM,
+			primary_hash: ferdie.client.info().best_hash,
+			slot_number: Default::default(),
+			extrinsics_root: Default::default(),
+		},
+		opaque_extrinsics: vec![OpaqueExtrinsic::from_bytes(&transfer.encode()).unwrap()],
+	}];
+	alice
+		.executor
+		.clone()
+		.process_bundles(
+			(ferdie.client.info().best_hash, ferdie.client.info().best_number),
+			bundles,
+			BlakeTwo256::hash_of(&[1u8; 64]).into(),
+			None,
+		)
+		.await;
+
+	let transaction_pool = alice.executor.transaction_pool.clone();
+	let tx_hash = transaction_pool.hash_of(&transfer.clone().into());
+	assert!(transaction_pool.ready_transaction(&tx_hash).is_none());
+
+	// Switch to the fork without the block of the transfer.
+	let block_hash = alice.client.info().best_hash;
+	let parent_hash =
+		*alice.client.header(BlockId::Hash(block_hash)).unwrap().unwrap().parent_hash();
+	let tree_route = sp_blockchain::tree_route(&*alice.client, block_hash, parent_hash).unwrap();
+	assert_eq!(tree_route.retracted().len(), 1);
+	transaction_pool
+		.maintain(ChainEvent::NewBestBlock {
+			hash: parent_hash,
+			tree_route: Some(tree_route.into()),
+		})
+		.await;
+
+	// The retracted transfer is checked against the fork and ready again.
+	let ready_transaction = transaction_pool
+		.ready_transaction(&tx_hash)
+		.expect("Retracted transaction must be revalidated and ready");
+	assert_eq!(*ready_transaction.hash(), tx_hash);
+}
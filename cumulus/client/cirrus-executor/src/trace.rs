@@ -0,0 +1,85 @@
+//! Execution trace of the secondary blocks.
+
+use cirrus_block_builder::{BlockBuilder, BuiltBlock, RecordProof};
+use cirrus_primitives::{AccountId, SecondaryApi};
+use codec::{Decode, Encode};
+use sc_client_api::{backend::StateBackendFor, BlockBackend};
+use sp_api::ProvideRuntimeApi;
+use sp_blockchain::HeaderBackend;
+use sp_runtime::{
+	generic::BlockId,
+	traits::{Block as BlockT, Header as HeaderT},
+};
+
+/// Returns the execution trace of a block and its Merkle root, given the intermediate storage
+/// `roots` noted by the runtime during the block execution and the final `state_root`.
+pub(crate) fn execution_trace<Block: BlockT>(
+	mut roots: Vec<[u8; 32]>,
+	state_root: Block::Hash,
+) -> Result<(Vec<Block::Hash>, [u8; 32]), sp_blockchain::Error> {
+	let state_root = state_root
+		.encode()
+		.try_into()
+		.expect("State root uses the same Block hash type which must fit into [u8; 32]; qed");
+
+	roots.push(state_root);
+
+	let trace_root = crate::merkle_tree::construct_trace_merkle_tree(roots.clone())?.root();
+	let trace = roots
+		.into_iter()
+		.map(|r| {
+			Block::Hash::decode(&mut r.as_slice())
+				.expect("Storage root uses the same Block hash type; qed")
+		})
+		.collect();
+
+	Ok((trace, trace_root))
+}
+
+/// Re-executes the secondary block `block_hash` on top of its parent state and returns the
+/// execution trace, i.e., the intermediate state roots followed by the final state root.
+///
+/// The block is rebuilt from its body without being imported, hence the replayed trace can be
+/// compared against the one in the stored execution receipt for the offline analysis.
+pub fn replay_block<Block, Client, Backend>(
+	client: &Client,
+	backend: &Backend,
+	block_hash: Block::Hash,
+) -> Result<Vec<Block::Hash>, sp_blockchain::Error>
+where
+	Block: BlockT,
+	Client: HeaderBackend<Block> + BlockBackend<Block> + ProvideRuntimeApi<Block>,
+	Client::Api: SecondaryApi<Block, AccountId>
+		+ sp_block_builder::BlockBuilder<Block>
+		+ sp_api::ApiExt<Block, StateBackend = StateBackendFor<Backend, Block>>,
+	Backend: sc_client_api::Backend<Block>,
+{
+	let header = client.header(BlockId::Hash(block_hash))?.ok_or_else(|| {
+		sp_blockchain::Error::UnknownBlock(format!("Header for {block_hash:?} not found"))
+	})?;
+	let parent_hash = *header.parent_hash();
+	let parent_number =
+		client.header(BlockId::Hash(parent_hash))?.map(|h| *h.number()).ok_or_else(|| {
+			sp_blockchain::Error::UnknownBlock(format!("Header for {parent_hash:?} not found"))
+		})?;
+	let extrinsics = client.block_body(&BlockId::Hash(block_hash))?.ok_or_else(|| {
+		sp_blockchain::Error::UnknownBlock(format!("Block body for {block_hash:?} not found"))
+	})?;
+
+	let block_builder = BlockBuilder::new(
+		client,
+		parent_hash,
+		parent_number,
+		RecordProof::No,
+		Default::default(),
+		backend,
+		extrinsics,
+	)?;
+
+	let (BuiltBlock { block, .. }, roots) = block_builder
+		.build_and_inspect(|api, at| api.intermediate_roots(at).map_err(Into::into))?;
+
+	let (trace, _trace_root) = execution_trace::<Block>(roots, *block.header().state_root())?;
+
+	Ok(trace)
+}
@@ -0,0 +1,184 @@
+//! Cache of the transaction validity results of the bundle extrinsics.
+
+use lru::LruCache;
+use parking_lot::Mutex;
+use sp_core::H256;
+use sp_runtime::transaction_validity::TransactionValidity;
+use std::hash::Hash;
+
+struct Inner<BlockHash: Hash + Eq> {
+	/// Best block the cached results were produced under.
+	best_hash: Option<BlockHash>,
+	results: LruCache<(BlockHash, H256), TransactionValidity>,
+}
+
+/// Bounded cache of the recent [`TransactionValidity`] results keyed by the block the extrinsic
+/// was validated at and the extrinsic hash.
+///
+/// The same extrinsic is often validated repeatedly against the same block, e.g., when it's
+/// included in the bundles of several executors. All the entries are dropped once the best block
+/// changes, the results against the old blocks are unlikely to be looked up again.
+pub(crate) struct ValidationCache<BlockHash: Hash + Eq> {
+	inner: Option<Mutex<Inner<BlockHash>>>,
+}
+
+impl<BlockHash: Hash + Eq + Copy> ValidationCache<BlockHash> {
+	/// Creates a cache holding up to `capacity` results, `0` disables the caching.
+	pub(crate) fn new(capacity: usize) -> Self {
+		Self {
+			inner: (capacity > 0)
+				.then(|| Mutex::new(Inner { best_hash: None, results: LruCache::new(capacity) })),
+		}
+	}
+
+	/// Returns the cached validity of `extrinsic_hash` at `at`, or runs `validate` and caches its
+	/// result on a miss.
+	///
+	/// `validate` runs without holding the lock, so the concurrent misses of the same extrinsic may
+	/// validate it more than once.
+	pub(crate) fn get_or_validate<Error>(
+		&self,
+		best_hash: BlockHash,
+		at: BlockHash,
+		extrinsic_hash: H256,
+		validate: impl FnOnce() -> Result<TransactionValidity, Error>,
+	) -> Result<TransactionValidity, Error> {
+		let inner = match &self.inner {
+			Some(inner) => inner,
+			None => return validate(),
+		};
+
+		{
+			let mut inner = inner.lock();
+			if inner.best_hash != Some(best_hash) {
+				inner.results.clear();
+				inner.best_hash = Some(best_hash);
+			}
+			if let Some(validity) = inner.results.get(&(at, extrinsic_hash)) {
+				return Ok(validity.clone())
+			}
+		}
+
+		let validity = validate()?;
+
+		let mut inner = inner.lock();
+		// Don't cache the result produced under a best block that has been replaced meanwhile.
+		if inner.best_hash == Some(best_hash) {
+			inner.results.put((at, extrinsic_hash), validity.clone());
+		}
+
+		Ok(validity)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use sp_runtime::transaction_validity::{
+		InvalidTransaction, TransactionValidityError, ValidTransaction,
+	};
+	use std::cell::Cell;
+
+	fn validate_counted(
+		validations: &Cell<usize>,
+		validity: TransactionValidity,
+	) -> impl FnOnce() -> Result<TransactionValidity, ()> + '_ {
+		move || {
+			validations.set(validations.get() + 1);
+			Ok(validity)
+		}
+	}
+
+	#[test]
+	fn repeated_validation_at_the_same_block_should_hit_the_cache() {
+		let cache = ValidationCache::new(16);
+		let validations = Cell::new(0);
+		let best = H256::repeat_byte(1);
+		let xt = H256::repeat_byte(0xaa);
+		let valid: TransactionValidity = Ok(ValidTransaction::default());
+
+		let first =
+			cache.get_or_validate(best, best, xt, validate_counted(&validations, valid.clone()));
+		let second =
+			cache.get_or_validate(best, best, xt, validate_counted(&validations, valid.clone()));
+		assert_eq!(first, Ok(valid.clone()));
+		assert_eq!(second, Ok(valid.clone()));
+		assert_eq!(validations.get(), 1);
+
+		// The same extrinsic at another block misses.
+		let other_block = H256::repeat_byte(2);
+		let invalid: TransactionValidity =
+			Err(TransactionValidityError::Invalid(InvalidTransaction::Stale));
+		assert_eq!(
+			cache.get_or_validate(
+				best,
+				other_block,
+				xt,
+				validate_counted(&validations, invalid.clone())
+			),
+			Ok(invalid.clone())
+		);
+		assert_eq!(validations.get(), 2);
+		assert_eq!(
+			cache.get_or_validate(
+				best,
+				other_block,
+				xt,
+				validate_counted(&validations, valid.clone())
+			),
+			Ok(invalid)
+		);
+		assert_eq!(validations.get(), 2);
+	}
+
+	#[test]
+	fn best_block_change_should_invalidate_the_cache() {
+		let cache = ValidationCache::new(16);
+		let validations = Cell::new(0);
+		let at = H256::repeat_byte(1);
+		let xt = H256::repeat_byte(0xaa);
+		let valid: TransactionValidity = Ok(ValidTransaction::default());
+
+		cache
+			.get_or_validate(at, at, xt, validate_counted(&validations, valid.clone()))
+			.unwrap();
+		cache
+			.get_or_validate(H256::repeat_byte(2), at, xt, validate_counted(&validations, valid))
+			.unwrap();
+		assert_eq!(validations.get(), 2);
+	}
+
+	#[test]
+	fn cache_should_be_bounded() {
+		let cache = ValidationCache::new(2);
+		let validations = Cell::new(0);
+		let best = H256::repeat_byte(1);
+		let valid: TransactionValidity = Ok(ValidTransaction::default());
+
+		for byte in [1, 2, 3, 1] {
+			cache
+				.get_or_validate(
+					best,
+					best,
+					H256::repeat_byte(byte),
+					validate_counted(&validations, valid.clone()),
+				)
+				.unwrap();
+		}
+		// The first extrinsic has been evicted by the third one.
+		assert_eq!(validations.get(), 4);
+
+		let disabled = ValidationCache::new(0);
+		for _ in 0..2 {
+			disabled
+				.get_or_validate(
+					best,
+					best,
+					H256::zero(),
+					validate_counted(&validations, valid.clone()),
+				)
+				.unwrap();
+		}
+		assert_eq!(validations.get(), 6);
+	}
+}
@@ -15,10 +15,12 @@
 // along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
 
 use crate::{BundleProcessor, BundleProducer};
-use cirrus_primitives::{AccountId, SecondaryApi};
+use cirrus_primitives::{AccountId, Balance, SecondaryApi};
 use codec::{Decode, Encode};
 use futures::{future, FutureExt, Stream, StreamExt, TryFutureExt};
-use sc_client_api::{AuxStore, BlockBackend};
+use pallet_transaction_payment_rpc_runtime_api::TransactionPaymentApi;
+use parking_lot::Mutex;
+use sc_client_api::{AuxStore, BlockBackend, BlockchainEvents};
 use sc_consensus::BlockImport;
 use sp_api::{ApiError, BlockT, ProvideRuntimeApi, TransactionFor};
 use sp_block_builder::BlockBuilder;
@@ -35,8 +37,11 @@ use std::{
 	collections::{hash_map::Entry, HashMap},
 	fmt::Debug,
 	future::Future,
+	panic::AssertUnwindSafe,
 	pin::Pin,
 	sync::Arc,
+	task::{Context, Poll},
+	time::Duration,
 };
 use subspace_core_primitives::{Randomness, Sha256Hash};
 use subspace_runtime_primitives::Hash as PHash;
@@ -44,6 +49,13 @@ use tracing::Instrument;
 
 const LOG_TARGET: &str = "executor-worker";
 
+/// Delay before the first re-subscription of an ended notification stream.
+pub(super) const RESUBSCRIPTION_BASE_DELAY: Duration = Duration::from_secs(1);
+/// Maximum delay between the re-subscriptions of an ended notification stream.
+pub(super) const RESUBSCRIPTION_MAX_DELAY: Duration = Duration::from_secs(30);
+/// Number of the consecutive re-subscriptions yielding nothing before giving up on the stream.
+pub(super) const MAX_RESUBSCRIPTION_ATTEMPTS: u32 = 10;
+
 /// Data required to produce bundles on executor node.
 #[derive(PartialEq, Clone, Debug)]
 pub(super) struct ExecutorSlotInfo {
@@ -53,6 +65,130 @@ pub(super) struct ExecutorSlotInfo {
 	pub(super) global_challenge: Sha256Hash,
 }
 
+/// What to do when the executor worker panics.
+#[derive(Debug, Clone, Copy)]
+pub enum WorkerRestartPolicy {
+	/// The worker is spawned as an essential task, a panic of it brings down the node.
+	Terminate,
+	/// The worker is spawned as a non-essential task and restarted after a panic.
+	///
+	/// The delay before the first restart is `base_delay`, doubled on each subsequent restart up
+	/// to `max_delay`.
+	Restart { base_delay: Duration, max_delay: Duration },
+}
+
+impl Default for WorkerRestartPolicy {
+	fn default() -> Self {
+		Self::Terminate
+	}
+}
+
+/// Stream shared across the worker restarts, a restarted worker resumes from the notifications
+/// the panicked one left off.
+pub(super) struct SharedStream<S>(Arc<Mutex<Pin<Box<S>>>>);
+
+impl<S> SharedStream<S> {
+	pub(super) fn new(stream: S) -> Self {
+		Self(Arc::new(Mutex::new(Box::pin(stream))))
+	}
+}
+
+impl<S> Clone for SharedStream<S> {
+	fn clone(&self) -> Self {
+		Self(self.0.clone())
+	}
+}
+
+impl<S: Stream> Stream for SharedStream<S> {
+	type Item = S::Item;
+
+	fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+		self.0.lock().as_mut().poll_next(cx)
+	}
+}
+
+/// Runs the worker returned by `start` until it exits, the worker is started again with the
+/// exponential backoff from `base_delay` to `max_delay` each time it panics.
+pub(super) async fn supervise<Start, Worker>(
+	base_delay: Duration,
+	max_delay: Duration,
+	mut start: Start,
+) where
+	Start: FnMut() -> Worker,
+	Worker: Future<Output = ()>,
+{
+	let mut restarts = 0u32;
+	while AssertUnwindSafe(start()).catch_unwind().await.is_err() {
+		let delay = backoff_delay(base_delay, max_delay, restarts);
+		tracing::error!(
+			target: LOG_TARGET,
+			restarts,
+			?delay,
+			"Executor worker panicked, restarting",
+		);
+		futures_timer::Delay::new(delay).await;
+		restarts = restarts.saturating_add(1);
+	}
+}
+
+/// Returns the delay before the retry following `retries` retries, doubled from `base_delay` on
+/// each retry up to `max_delay`.
+fn backoff_delay(base_delay: Duration, max_delay: Duration, retries: u32) -> Duration {
+	base_delay.saturating_mul(1 << retries.min(16)).min(max_delay)
+}
+
+/// Yields the items of the stream returned by `subscribe`, subscribing again each time the stream
+/// ends, which is only expected on an upstream drop for the endless streams like the slot
+/// notifications.
+///
+/// The re-subscriptions are backed off from `base_delay` to `max_delay` while the re-subscribed
+/// streams end without yielding anything, the stream ends after `max_attempts` such consecutive
+/// re-subscriptions.
+pub(super) fn resubscribing<S, Subscribe>(
+	stream_name: &'static str,
+	mut subscribe: Subscribe,
+	(base_delay, max_delay): (Duration, Duration),
+	max_attempts: u32,
+) -> impl Stream<Item = S::Item>
+where
+	S: Stream,
+	Subscribe: FnMut() -> S,
+{
+	let stream = Box::pin(subscribe());
+	futures::stream::unfold(
+		(stream, subscribe, 0u32),
+		move |(mut stream, mut subscribe, mut attempts)| async move {
+			loop {
+				if let Some(item) = stream.next().await {
+					return Some((item, (stream, subscribe, 0)))
+				}
+
+				if attempts >= max_attempts {
+					tracing::error!(
+						target: LOG_TARGET,
+						stream_name,
+						attempts,
+						"Notification stream ended and couldn't be recovered, giving up",
+					);
+					return None
+				}
+
+				let delay = backoff_delay(base_delay, max_delay, attempts);
+				tracing::warn!(
+					target: LOG_TARGET,
+					stream_name,
+					attempts,
+					?delay,
+					"Notification stream ended unexpectedly, re-subscribing",
+				);
+				futures_timer::Delay::new(delay).await;
+				stream = Box::pin(subscribe());
+				attempts += 1;
+			}
+		},
+	)
+}
+
 /// An event telling the `Overseer` on the particular block
 /// that has been imported or finalized.
 ///
@@ -91,10 +227,15 @@ pub(super) async fn start_worker<
 ) where
 	Block: BlockT,
 	PBlock: BlockT,
-	Client:
-		HeaderBackend<Block> + BlockBackend<Block> + AuxStore + ProvideRuntimeApi<Block> + 'static,
+	Client: HeaderBackend<Block>
+		+ BlockBackend<Block>
+		+ AuxStore
+		+ ProvideRuntimeApi<Block>
+		+ BlockchainEvents<Block>
+		+ 'static,
 	Client::Api: SecondaryApi<Block, AccountId>
 		+ BlockBuilder<Block>
+		+ TransactionPaymentApi<Block, Balance>
 		+ sp_api::ApiExt<
 			Block,
 			StateBackend = sc_client_api::backend::StateBackendFor<Backend, Block>,
@@ -143,6 +284,19 @@ pub(super) async fn start_worker<
 	let handle_slot_notifications_fut = handle_slot_notifications(
 		primary_chain_client.as_ref(),
 		move |primary_hash, slot_info| {
+			if !bundle_producer.is_producing() {
+				tracing::debug!(
+					target: LOG_TARGET,
+					"Skipping the bundle production as it's halted by the operator",
+				);
+				return future::ready(None).boxed()
+			}
+
+			// Return early on the slots the node can't act on, e.g., on a non-authority node.
+			if !bundle_producer.should_produce_at(slot_info.slot) {
+				return future::ready(None).boxed()
+			}
+
 			bundle_producer
 				.clone()
 				.produce_bundle(primary_hash, slot_info)
@@ -158,10 +312,10 @@ pub(super) async fn start_worker<
 				})
 				.boxed()
 		},
-		Box::pin(
+		Box::pin(skip_stale_slots(
 			new_slot_notification_stream
 				.map(|(slot, global_challenge)| ExecutorSlotInfo { slot, global_challenge }),
-		),
+		)),
 	);
 
 	let _ = future::select(
@@ -171,6 +325,25 @@ pub(super) async fn start_worker<
 	.await;
 }
 
+/// Drops the slots not newer than the last slot seen, e.g., the slots emitted again on an upstream
+/// resubscription, a duplicated slot would otherwise produce another bundle for the same slot,
+/// which looks like an equivocation of the executor itself.
+pub(super) fn skip_stale_slots(
+	slots: impl Stream<Item = ExecutorSlotInfo>,
+) -> impl Stream<Item = ExecutorSlotInfo> {
+	let mut last_slot = None;
+	slots.filter(move |executor_slot_info| {
+		let slot = executor_slot_info.slot;
+		let is_new = last_slot.map_or(true, |last_slot| slot > last_slot);
+		if is_new {
+			last_slot = Some(slot);
+		} else {
+			tracing::warn!(target: LOG_TARGET, ?slot, ?last_slot, "Ignoring the non-increasing slot");
+		}
+		future::ready(is_new)
+	})
+}
+
 async fn handle_slot_notifications<PBlock, PClient, BundlerFn, SecondaryHash>(
 	primary_chain_client: &PClient,
 	bundler: BundlerFn,
@@ -187,7 +360,17 @@ async fn handle_slot_notifications<PBlock, PClient, BundlerFn, SecondaryHash>(
 		+ Sync,
 	SecondaryHash: Encode + Decode,
 {
-	while let Some(executor_slot_info) = slots.next().await {
+	loop {
+		let executor_slot_info = match slots.next().await {
+			Some(executor_slot_info) => executor_slot_info,
+			None => {
+				tracing::error!(
+					target: LOG_TARGET,
+					"Slot notification stream ended, the bundles are no longer produced",
+				);
+				break
+			},
+		};
 		if let Err(error) = on_new_slot(primary_chain_client, &bundler, executor_slot_info).await {
 			tracing::error!(
 				target: LOG_TARGET,
@@ -230,7 +413,17 @@ async fn handle_block_import_notifications<PBlock, PClient, ProcessorFn, Seconda
 		}
 	}
 
-	while let Some(block_number) = block_imports.next().await {
+	loop {
+		let block_number = match block_imports.next().await {
+			Some(block_number) => block_number,
+			None => {
+				tracing::error!(
+					target: LOG_TARGET,
+					"Imported block notification stream ended, the bundles are no longer processed",
+				);
+				break
+			},
+		};
 		let header = primary_chain_client
 			.header(BlockId::Number(block_number))
 			.expect("Header of imported block must exist; qed")
@@ -333,6 +526,41 @@ where
 	Ok(())
 }
 
+/// Extracts the bundles from the body of the primary block `primary_hash`, which are exactly the
+/// bundles an executor processes for that primary block.
+pub fn extract_bundles<PBlock, PClient, SecondaryHash>(
+	primary_chain_client: &PClient,
+	primary_hash: PBlock::Hash,
+	primary_block_body: Vec<PBlock::Extrinsic>,
+) -> Result<Vec<OpaqueBundle>, ApiError>
+where
+	PBlock: BlockT,
+	PClient: ProvideRuntimeApi<PBlock>,
+	PClient::Api: ExecutorApi<PBlock, SecondaryHash>,
+	SecondaryHash: Encode + Decode,
+{
+	primary_chain_client.runtime_api().extract_bundles(
+		&BlockId::Hash(primary_hash),
+		to_opaque_extrinsics::<PBlock>(primary_block_body, "extract_bundles")?,
+	)
+}
+
+/// Converts the body of a primary block into the opaque extrinsics taken by the runtime API
+/// `function`.
+pub(crate) fn to_opaque_extrinsics<PBlock: BlockT>(
+	primary_block_body: Vec<PBlock::Extrinsic>,
+	function: &'static str,
+) -> Result<Vec<OpaqueExtrinsic>, ApiError> {
+	primary_block_body
+		.into_iter()
+		.map(|xt| {
+			OpaqueExtrinsic::from_bytes(&xt.encode()).map_err(|error| {
+				ApiError::FailedToConvertParameter { function, parameter: "extrinsics", error }
+			})
+		})
+		.collect()
+}
+
 /// Apply the transaction bundles for given primary block as follows:
 ///
 /// 1. Extract the transaction bundles from the block.
@@ -373,15 +601,8 @@ where
 		Ok(Some(body)) => body,
 	};
 
-	let bundles = primary_chain_client.runtime_api().extract_bundles(
-		&block_id,
-		extrinsics
-			.into_iter()
-			.map(|xt| {
-				OpaqueExtrinsic::from_bytes(&xt.encode()).expect("Certainly a correct extrinsic")
-			})
-			.collect(),
-	)?;
+	let bundles =
+		extract_bundles::<PBlock, _, SecondaryHash>(primary_chain_client, block_hash, extrinsics)?;
 
 	let header = match primary_chain_client.header(block_id) {
 		Err(err) => {
@@ -395,6 +616,28 @@ where
 		Ok(Some(header)) => header,
 	};
 
+	let (shuffling_seed, maybe_new_runtime) =
+		secondary_block_inputs::<PBlock, _, SecondaryHash>(primary_chain_client, header)?;
+
+	processor((block_hash, block_number), bundles, shuffling_seed, maybe_new_runtime).await;
+
+	Ok(())
+}
+
+/// Returns the seed the extrinsics are shuffled with and the runtime code to upgrade to, if any,
+/// of the secondary block built for the primary block `header`.
+pub(crate) fn secondary_block_inputs<PBlock, PClient, SecondaryHash>(
+	primary_chain_client: &PClient,
+	header: PBlock::Header,
+) -> Result<(Randomness, Option<Cow<'static, [u8]>>), ApiError>
+where
+	PBlock: BlockT,
+	PClient: ProvideRuntimeApi<PBlock>,
+	PClient::Api: ExecutorApi<PBlock, SecondaryHash>,
+	SecondaryHash: Encode + Decode,
+{
+	let block_id = BlockId::Hash(header.hash());
+
 	let maybe_new_runtime = if header
 		.digest()
 		.logs
@@ -410,7 +653,5 @@ where
 		.runtime_api()
 		.extrinsics_shuffling_seed(&block_id, header)?;
 
-	processor((block_hash, block_number), bundles, shuffling_seed, maybe_new_runtime).await;
-
-	Ok(())
+	Ok((shuffling_seed, maybe_new_runtime))
 }
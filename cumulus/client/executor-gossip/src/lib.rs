@@ -3,12 +3,12 @@ mod worker;
 use self::worker::GossipWorker;
 use parity_scale_codec::{Decode, Encode};
 use parking_lot::{Mutex, RwLock};
-use sc_network::{ObservedRole, PeerId};
+use sc_network::{ObservedRole, PeerId, ReputationChange};
 use sc_network_gossip::{
 	GossipEngine, MessageIntent, Network as GossipNetwork, ValidationResult, Validator,
 	ValidatorContext,
 };
-use sc_utils::mpsc::TracingUnboundedReceiver;
+use sc_utils::mpsc::{tracing_unbounded, TracingUnboundedReceiver, TracingUnboundedSender};
 use sp_core::hashing::twox_64;
 use sp_executor::{SignedBundle, SignedExecutionReceipt};
 use sp_runtime::traits::{Block as BlockT, Hash as HashT, Header as HeaderT, NumberFor};
@@ -22,7 +22,9 @@ use std::{
 
 const LOG_TARGET: &str = "gossip::executor";
 
-const EXECUTOR_PROTOCOL_NAME: &str = "/subspace/executor/1";
+/// Version 2 carries the domain in the header of the gossiped bundles, the peers on version 1
+/// can't decode them.
+const EXECUTOR_PROTOCOL_NAME: &str = "/subspace/executor/2";
 
 // TODO: proper timeout
 /// Timeout for rebroadcasting messages.
@@ -31,6 +33,19 @@ const REBROADCAST_AFTER: Duration = Duration::from_secs(6);
 
 type MessageHash = [u8; 8];
 
+/// Reputation costs of the peers sending the invalid gossip messages.
+pub mod cost {
+	use sc_network::ReputationChange as Rep;
+
+	/// The message can not be decoded.
+	pub const UNDECODABLE_MESSAGE: Rep = Rep::new(-(1 << 10), "Executor: Undecodable message");
+	/// The signature of the message is invalid.
+	pub const BAD_SIGNATURE: Rep = Rep::new(-(1 << 12), "Executor: Bad signature");
+}
+
+/// Reputation change of a peer reported by the gossip validator.
+pub type PeerReport = (PeerId, ReputationChange);
+
 /// Returns the configuration value to put in [`sc_network::config::NetworkConfiguration::extra_sets`].
 pub fn executor_gossip_peers_set_config() -> sc_network::config::NonDefaultSetConfig {
 	let mut cfg =
@@ -93,6 +108,13 @@ impl Action {
 	}
 }
 
+/// Error of handling a gossip message which may be attributed to the peer sending it.
+pub trait PeerReputation {
+	/// Returns the reputation change of the peer that sent the message causing this error, `None`
+	/// if the peer is not to blame.
+	fn reputation_change(&self) -> Option<ReputationChange>;
+}
+
 /// Handler for the messages received from the executor gossip network.
 pub trait GossipMessageHandler<PBlock, Block>
 where
@@ -100,7 +122,7 @@ where
 	Block: BlockT,
 {
 	/// Error type.
-	type Error: Debug;
+	type Error: Debug + PeerReputation;
 
 	/// Validates and applies when a transaction bundle was received.
 	fn on_bundle(&self, bundle: &SignedBundle<Block::Extrinsic>) -> Result<Action, Self::Error>;
@@ -123,6 +145,7 @@ where
 	executor: Executor,
 	next_rebroadcast: Mutex<Instant>,
 	known_rebroadcasted: RwLock<HashSet<MessageHash>>,
+	report_sender: TracingUnboundedSender<PeerReport>,
 	_phantom_data: PhantomData<PBlock>,
 }
 
@@ -132,14 +155,22 @@ where
 	Block: BlockT,
 	Executor: GossipMessageHandler<PBlock, Block>,
 {
-	pub fn new(executor: Executor) -> Self {
-		Self {
+	/// Creates a new validator along with the stream of the peer reputation changes it reports.
+	pub fn new(executor: Executor) -> (Self, TracingUnboundedReceiver<PeerReport>) {
+		let (report_sender, report_receiver) = tracing_unbounded("mpsc_executor_gossip_reports");
+		let validator = Self {
 			topic: topic::<Block>(),
 			executor,
 			next_rebroadcast: Mutex::new(Instant::now() + REBROADCAST_AFTER),
 			known_rebroadcasted: RwLock::new(HashSet::new()),
+			report_sender,
 			_phantom_data: PhantomData::default(),
-		}
+		};
+		(validator, report_receiver)
+	}
+
+	fn report(&self, who: &PeerId, reputation_change: ReputationChange) {
+		let _ = self.report_sender.unbounded_send((*who, reputation_change));
 	}
 
 	pub(crate) fn note_rebroadcasted(&self, encoded_message: &[u8]) {
@@ -147,7 +178,11 @@ where
 		known_rebroadcasted.insert(twox_64(encoded_message));
 	}
 
-	fn validate_message(&self, msg: GossipMessage<PBlock, Block>) -> ValidationResult<Block::Hash> {
+	fn validate_message(
+		&self,
+		sender: &PeerId,
+		msg: GossipMessage<PBlock, Block>,
+	) -> ValidationResult<Block::Hash> {
 		match msg {
 			GossipMessage::Bundle(bundle) => {
 				let outcome = self.executor.on_bundle(&bundle);
@@ -160,6 +195,9 @@ where
 							?err,
 							"Invalid GossipMessage::Bundle discarded"
 						);
+						if let Some(reputation_change) = err.reputation_change() {
+							self.report(sender, reputation_change);
+						}
 						ValidationResult::Discard
 					},
 					_ => ValidationResult::ProcessAndDiscard(self.topic),
@@ -176,6 +214,9 @@ where
 							?err,
 							"Invalid GossipMessage::ExecutionReceipt discarded"
 						);
+						if let Some(reputation_change) = err.reputation_change() {
+							self.report(sender, reputation_change);
+						}
 						ValidationResult::Discard
 					},
 					_ => ValidationResult::ProcessAndDiscard(self.topic),
@@ -204,13 +245,13 @@ where
 	fn validate(
 		&self,
 		_context: &mut dyn ValidatorContext<Block>,
-		_sender: &PeerId,
+		sender: &PeerId,
 		mut data: &[u8],
 	) -> ValidationResult<Block::Hash> {
 		match GossipMessage::<PBlock, Block>::decode(&mut data) {
 			Ok(msg) => {
 				tracing::debug!(target: LOG_TARGET, ?msg, "Validating incoming message");
-				self.validate_message(msg)
+				self.validate_message(sender, msg)
 			},
 			Err(err) => {
 				tracing::debug!(
@@ -219,6 +260,7 @@ where
 					?data,
 					"Message discarded due to the decoding error"
 				);
+				self.report(sender, cost::UNDECODABLE_MESSAGE);
 				ValidationResult::Discard
 			},
 		}
@@ -301,7 +343,8 @@ pub async fn start_gossip_worker<PBlock, Block, Network, Executor>(
 	let ExecutorGossipParams { network, executor, bundle_receiver, execution_receipt_receiver } =
 		gossip_params;
 
-	let gossip_validator = Arc::new(GossipValidator::new(executor));
+	let (gossip_validator, report_receiver) = GossipValidator::new(executor);
+	let gossip_validator = Arc::new(gossip_validator);
 	let gossip_engine =
 		GossipEngine::new(network, EXECUTOR_PROTOCOL_NAME, gossip_validator.clone(), None);
 
@@ -310,6 +353,7 @@ pub async fn start_gossip_worker<PBlock, Block, Network, Executor>(
 		Arc::new(Mutex::new(gossip_engine)),
 		bundle_receiver,
 		execution_receipt_receiver,
+		report_receiver,
 	);
 
 	gossip_worker.run().await
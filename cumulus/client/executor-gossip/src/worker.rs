@@ -1,4 +1,4 @@
-use crate::{topic, GossipMessage, GossipMessageHandler, GossipValidator, LOG_TARGET};
+use crate::{topic, GossipMessage, GossipMessageHandler, GossipValidator, PeerReport, LOG_TARGET};
 use futures::{future, FutureExt, StreamExt};
 use parity_scale_codec::{Decode, Encode};
 use parking_lot::Mutex;
@@ -21,6 +21,7 @@ where
 	execution_receipt_receiver: TracingUnboundedReceiver<
 		SignedExecutionReceipt<NumberFor<PBlock>, PBlock::Hash, Block::Hash>,
 	>,
+	report_receiver: TracingUnboundedReceiver<PeerReport>,
 }
 
 impl<PBlock, Block, Executor> GossipWorker<PBlock, Block, Executor>
@@ -36,8 +37,15 @@ where
 		execution_receipt_receiver: TracingUnboundedReceiver<
 			SignedExecutionReceipt<NumberFor<PBlock>, PBlock::Hash, Block::Hash>,
 		>,
+		report_receiver: TracingUnboundedReceiver<PeerReport>,
 	) -> Self {
-		Self { gossip_validator, gossip_engine, bundle_receiver, execution_receipt_receiver }
+		Self {
+			gossip_validator,
+			gossip_engine,
+			bundle_receiver,
+			execution_receipt_receiver,
+			report_receiver,
+		}
 	}
 
 	fn gossip_bundle(&self, bundle: SignedBundle<Block::Extrinsic>) {
@@ -95,6 +103,11 @@ where
 						self.gossip_execution_receipt(execution_receipt);
 					}
 				}
+				report = self.report_receiver.next().fuse() => {
+					if let Some((who, reputation_change)) = report {
+						self.gossip_engine.lock().report(who, reputation_change);
+					}
+				}
 				_ = gossip_engine.fuse() => {
 					tracing::error!(target: LOG_TARGET, "Gossip engine has terminated.");
 					return;
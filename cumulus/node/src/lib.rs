@@ -2,3 +2,4 @@
 
 mod rpc;
 pub mod service;
+mod transaction_pool_metrics;
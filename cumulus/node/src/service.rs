@@ -1,12 +1,14 @@
 //! Service and ServiceFactory implementation. Specialized wrapper over substrate service.
 
-use cirrus_client_executor::Executor;
+use cirrus_client_executor::{DomainRegistry, Executor, ExecutorConfig};
 use cirrus_client_executor_gossip::ExecutorGossipParams;
 use cirrus_primitives::SecondaryApi;
 use cirrus_runtime::{opaque::Block, AccountId, Balance, Hash};
 use futures::Stream;
 use pallet_transaction_payment_rpc::TransactionPaymentRuntimeApi;
-use sc_client_api::{BlockBackend, StateBackendFor};
+use sc_client_api::{
+	BlockBackend, BlockchainEvents, ExecutorProvider, StateBackendFor, UsageProvider,
+};
 use sc_executor::{NativeElseWasmExecutor, NativeExecutionDispatch};
 use sc_network::NetworkService;
 use sc_service::{
@@ -14,6 +16,7 @@ use sc_service::{
 	TFullBackend, TFullClient, TaskManager,
 };
 use sc_telemetry::{Telemetry, TelemetryWorker, TelemetryWorkerHandle};
+pub use sc_transaction_pool::RevalidationType;
 use sc_utils::mpsc::tracing_unbounded;
 use sp_api::{ApiExt, BlockT, ConstructRuntimeApi, Metadata, NumberFor, ProvideRuntimeApi};
 use sp_block_builder::BlockBuilder;
@@ -56,6 +59,26 @@ pub type FullPool<RuntimeApi, ExecutorDispatch> = sc_transaction_pool::BasicPool
 	Block,
 >;
 
+/// Secondary chain specific service configuration.
+pub struct SecondaryChainConfiguration {
+	/// Base configuration.
+	pub base: Configuration,
+	/// Revalidation strategy of the transaction pool on each imported block.
+	pub transaction_pool_revalidation: RevalidationType,
+	/// Tunables of the executor.
+	pub executor_config: ExecutorConfig,
+}
+
+impl From<Configuration> for SecondaryChainConfiguration {
+	fn from(base: Configuration) -> Self {
+		Self {
+			base,
+			transaction_pool_revalidation: RevalidationType::Full,
+			executor_config: ExecutorConfig::default(),
+		}
+	}
+}
+
 /// Starts a `ServiceBuilder` for a full service.
 ///
 /// Use this macro if you don't actually need the full service, but just the builder in order to
@@ -63,6 +86,7 @@ pub type FullPool<RuntimeApi, ExecutorDispatch> = sc_transaction_pool::BasicPool
 #[allow(clippy::type_complexity)]
 fn new_partial<RuntimeApi, Executor>(
 	config: &Configuration,
+	transaction_pool_revalidation: RevalidationType,
 ) -> Result<
 	PartialComponents<
 		FullClient<RuntimeApi, Executor>,
@@ -113,13 +137,27 @@ where
 		telemetry
 	});
 
-	let transaction_pool = sc_transaction_pool::BasicPool::new_full(
-		config.transaction_pool.clone(),
-		config.role.is_authority().into(),
-		config.prometheus_registry(),
-		task_manager.spawn_essential_handle(),
-		client.clone(),
-	);
+	let transaction_pool = {
+		let pool_api = Arc::new(sc_transaction_pool::FullChainApi::new(
+			client.clone(),
+			config.prometheus_registry(),
+			&task_manager.spawn_essential_handle(),
+		));
+		let transaction_pool = Arc::new(sc_transaction_pool::BasicPool::with_revalidation_type(
+			config.transaction_pool.clone(),
+			config.role.is_authority().into(),
+			pool_api,
+			config.prometheus_registry(),
+			transaction_pool_revalidation,
+			task_manager.spawn_essential_handle(),
+			client.usage_info().chain.best_number,
+		));
+
+		// Make transaction pool available for off-chain runtime calls.
+		client.execution_extensions().register_transaction_pool(&transaction_pool);
+
+		transaction_pool
+	};
 
 	let import_queue = cumulus_client_consensus_relay_chain::import_queue(
 		client.clone(),
@@ -191,13 +229,25 @@ where
 /// Start a node with the given parachain `Configuration` and relay chain `Configuration`.
 ///
 /// This is the actual implementation that is abstract over the executor and the runtime api.
-pub async fn new_full<PBlock, PClient, SC, IBNS, NSNS, RuntimeApi, ExecutorDispatch>(
-	mut secondary_chain_config: Configuration,
+///
+/// `subscribe_new_slot_notifications` subscribes to the slot notifications of the primary node,
+/// it's called again if the subscribed stream ends.
+pub async fn new_full<
+	PBlock,
+	PClient,
+	SC,
+	IBNS,
+	NSNS,
+	SubscribeNSNS,
+	RuntimeApi,
+	ExecutorDispatch,
+>(
+	secondary_chain_config: SecondaryChainConfiguration,
 	primary_chain_client: Arc<PClient>,
 	primary_network: Arc<NetworkService<PBlock, PBlock::Hash>>,
 	select_chain: &SC,
 	imported_block_notification_stream: IBNS,
-	new_slot_notification_stream: NSNS,
+	subscribe_new_slot_notifications: SubscribeNSNS,
 ) -> sc_service::error::Result<
 	NewFull<
 		Arc<FullClient<RuntimeApi, ExecutorDispatch>>,
@@ -212,6 +262,7 @@ where
 	PBlock: BlockT,
 	PClient: HeaderBackend<PBlock>
 		+ BlockBackend<PBlock>
+		+ BlockchainEvents<PBlock>
 		+ ProvideRuntimeApi<PBlock>
 		+ Send
 		+ Sync
@@ -220,6 +271,7 @@ where
 	SC: SelectChain<PBlock>,
 	IBNS: Stream<Item = NumberFor<PBlock>> + Send + 'static,
 	NSNS: Stream<Item = (Slot, Sha256Hash)> + Send + 'static,
+	SubscribeNSNS: FnMut() -> NSNS + Send + 'static,
 	RuntimeApi: ConstructRuntimeApi<Block, FullClient<RuntimeApi, ExecutorDispatch>>
 		+ Send
 		+ Sync
@@ -235,6 +287,12 @@ where
 		+ TransactionPaymentRuntimeApi<Block, Balance>,
 	ExecutorDispatch: NativeExecutionDispatch + 'static,
 {
+	let SecondaryChainConfiguration {
+		base: mut secondary_chain_config,
+		transaction_pool_revalidation,
+		executor_config,
+	} = secondary_chain_config;
+
 	if matches!(secondary_chain_config.role, Role::Light) {
 		return Err("Light client not supported!".into())
 	}
@@ -247,7 +305,7 @@ where
 		.extra_sets
 		.push(cirrus_client_executor_gossip::executor_gossip_peers_set_config());
 
-	let params = new_partial(&secondary_chain_config)?;
+	let params = new_partial(&secondary_chain_config, transaction_pool_revalidation)?;
 
 	let (mut telemetry, _telemetry_worker_handle, code_executor) = params.other;
 
@@ -284,6 +342,8 @@ where
 		})
 	};
 
+	let prometheus_registry = secondary_chain_config.prometheus_registry().cloned();
+
 	let rpc_handlers = sc_service::spawn_tasks(SpawnTasksParams {
 		rpc_builder,
 		client: client.clone(),
@@ -297,12 +357,19 @@ where
 		telemetry: telemetry.as_mut(),
 	})?;
 
+	if let Some(registry) = prometheus_registry.as_ref() {
+		let metrics = crate::transaction_pool_metrics::TransactionPoolMetrics::register(registry)?;
+		task_manager.spawn_handle().spawn(
+			"cirrus-txpool-metrics",
+			None,
+			crate::transaction_pool_metrics::run(client.clone(), transaction_pool.clone(), metrics),
+		);
+	}
+
 	let code_executor = Arc::new(code_executor);
 
 	let spawn_essential = task_manager.spawn_essential_handle();
 	let (bundle_sender, bundle_receiver) = tracing_unbounded("transaction_bundle_stream");
-	let (execution_receipt_sender, execution_receipt_receiver) =
-		tracing_unbounded("execution_receipt_stream");
 
 	let executor = Executor::new(
 		primary_chain_client,
@@ -310,25 +377,30 @@ where
 		&spawn_essential,
 		select_chain,
 		imported_block_notification_stream,
-		new_slot_notification_stream,
+		subscribe_new_slot_notifications,
 		client.clone(),
 		Box::new(task_manager.spawn_handle()),
 		transaction_pool,
 		Arc::new(bundle_sender),
-		Arc::new(execution_receipt_sender),
 		backend.clone(),
 		code_executor.clone(),
 		validator,
 		params.keystore_container.sync_keystore(),
+		prometheus_registry.as_ref(),
+		executor_config,
 	)
 	.await?;
 
+	// The node runs a single domain for now, the bundles of the other domains are ignored.
+	let mut domain_registry = DomainRegistry::new();
+	domain_registry.register(executor.domain_id(), executor.clone());
+
 	let executor_gossip =
 		cirrus_client_executor_gossip::start_gossip_worker(ExecutorGossipParams {
 			network: network.clone(),
-			executor: executor.clone(),
+			executor: domain_registry,
 			bundle_receiver,
-			execution_receipt_receiver,
+			execution_receipt_receiver: executor.execution_receipt_stream().subscribe(),
 		});
 	spawn_essential.spawn_essential_blocking("cirrus-gossip", None, Box::pin(executor_gossip));
 
@@ -0,0 +1,71 @@
+//! Prometheus metrics of the secondary chain transaction pool.
+
+use futures::StreamExt;
+use sc_client_api::BlockchainEvents;
+use sc_transaction_pool_api::PoolStatus;
+use sp_runtime::traits::Block as BlockT;
+use std::sync::Arc;
+use substrate_prometheus_endpoint::{register, Gauge, PrometheusError, Registry, U64};
+
+/// Gauge tracking the status of the transaction pool.
+///
+/// The ready transactions are already reported by substrate as
+/// `substrate_ready_transactions_number`, only the backlog of the future transactions is added.
+#[derive(Clone)]
+pub(crate) struct TransactionPoolMetrics {
+	future: Gauge<U64>,
+}
+
+impl TransactionPoolMetrics {
+	pub(crate) fn register(registry: &Registry) -> Result<Self, PrometheusError> {
+		Ok(Self {
+			future: register(
+				Gauge::new(
+					"cirrus_txpool_future_transactions",
+					"Number of future transactions in the secondary chain transaction pool",
+				)?,
+				registry,
+			)?,
+		})
+	}
+
+	pub(crate) fn report(&self, status: PoolStatus) {
+		self.future.set(status.future as u64);
+	}
+}
+
+/// Reports the status of `transaction_pool` on each imported block, which is when the pool
+/// gets maintained.
+pub(crate) async fn run<Block, Client, TransactionPool>(
+	client: Arc<Client>,
+	transaction_pool: Arc<TransactionPool>,
+	metrics: TransactionPoolMetrics,
+) where
+	Block: BlockT,
+	Client: BlockchainEvents<Block>,
+	TransactionPool: sc_transaction_pool_api::TransactionPool<Block = Block>,
+{
+	metrics.report(transaction_pool.status());
+
+	let mut import_notification_stream = client.import_notification_stream();
+	while import_notification_stream.next().await.is_some() {
+		metrics.report(transaction_pool.status());
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn report_should_update_the_gauges() {
+		let registry = Registry::new();
+		let metrics = TransactionPoolMetrics::register(&registry).unwrap();
+
+		metrics.report(PoolStatus { ready: 3, ready_bytes: 300, future: 1, future_bytes: 100 });
+		assert_eq!(metrics.future.get(), 1);
+
+		metrics.report(PoolStatus { ready: 0, ready_bytes: 0, future: 2, future_bytes: 200 });
+		assert_eq!(metrics.future.get(), 2);
+	}
+}
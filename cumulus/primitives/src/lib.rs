@@ -71,6 +71,7 @@ where
 
 sp_api::decl_runtime_apis! {
 	/// API necessary for secondary node.
+	#[api_version(2)]
 	pub trait SecondaryApi<AccountId: Encode + Decode> {
 		/// Extracts the optional signer per extrinsic.
 		fn extract_signer(
@@ -88,5 +89,16 @@ sp_api::decl_runtime_apis! {
 
 		/// Returns an encoded extrinsic aiming to upgrade the runtime using given code.
 		fn construct_set_code_extrinsic(code: Vec<u8>) -> Vec<u8>;
+
+		/// Returns the minimum a transaction has to offer, i.e., its fee plus tip, to be included
+		/// in a bundle.
+		///
+		/// Introduced in version 2.
+		fn minimum_bundle_tx_fee() -> Balance;
+
+		/// Returns the tip offered by the extrinsic on top of its fee, `0` for an unsigned one.
+		///
+		/// Introduced in version 2.
+		fn extrinsic_tip(extrinsic: <Block as BlockT>::Extrinsic) -> Balance;
 	}
 }
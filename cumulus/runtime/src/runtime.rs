@@ -95,7 +95,7 @@ pub const VERSION: RuntimeVersion = RuntimeVersion {
 	spec_name: create_runtime_str!("subspace-executor"),
 	impl_name: create_runtime_str!("subspace-executor"),
 	authoring_version: 0,
-	spec_version: 0,
+	spec_version: 1,
 	impl_version: 0,
 	apis: RUNTIME_API_VERSIONS,
 	transaction_version: 0,
@@ -227,6 +227,11 @@ impl pallet_balances::Config for Runtime {
 parameter_types! {
 	pub const TransactionByteFee: Balance = 1;
 	pub const OperationalFeeMultiplier: u8 = 5;
+	/// Minimum a transaction has to offer, i.e., its fee plus tip, to be included in a bundle.
+	///
+	/// It's above the base fee of an extrinsic, the lightest transactions have to top up their fee
+	/// with a tip.
+	pub const MinimumBundleTxFee: Balance = 100_000_000 * SHANNON;
 }
 
 impl pallet_transaction_payment::Config for Runtime {
@@ -385,6 +390,19 @@ impl_runtime_apis! {
 				}.into()
 			).encode()
 		}
+
+		fn minimum_bundle_tx_fee() -> Balance {
+			MinimumBundleTxFee::get()
+		}
+
+		fn extrinsic_tip(extrinsic: <Block as BlockT>::Extrinsic) -> Balance {
+			use codec::{Compact, Decode, Encode};
+			// `ChargeTransactionPayment` doesn't expose its tip, which it encodes as compact.
+			extrinsic.signature.map_or(0, |(_, _, extra)| {
+				Compact::<Balance>::decode(&mut extra.7.encode().as_slice())
+					.map_or(0, |tip| tip.0)
+			})
+		}
 	}
 
 	#[cfg(feature = "runtime-benchmarks")]
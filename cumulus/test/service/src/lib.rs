@@ -20,6 +20,7 @@
 
 pub mod chain_spec;
 
+use cirrus_client_executor::ExecutorConfig;
 use cirrus_test_runtime::{opaque::Block, Hash};
 use futures::StreamExt;
 use sc_client_api::execution_extensions::ExecutionStrategies;
@@ -43,6 +44,7 @@ use substrate_test_client::{
 	BlockchainEventsExt, RpcHandlersExt, RpcTransactionError, RpcTransactionOutput,
 };
 
+pub use cirrus_node::service::RevalidationType;
 pub use cirrus_test_runtime as runtime;
 pub use sp_keyring::Sr25519Keyring as Keyring;
 
@@ -93,6 +95,8 @@ pub type Client =
 async fn run_executor(
 	secondary_chain_config: Configuration,
 	primary_chain_config: Configuration,
+	executor_config: ExecutorConfig,
+	transaction_pool_revalidation: RevalidationType,
 ) -> sc_service::error::Result<(
 	TaskManager,
 	Arc<Client>,
@@ -134,24 +138,33 @@ async fn run_executor(
 		_,
 		_,
 		_,
+		_,
 		cirrus_test_runtime::RuntimeApi,
 		RuntimeExecutor,
 	>(
-		secondary_chain_config,
+		cirrus_node::service::SecondaryChainConfiguration {
+			executor_config,
+			transaction_pool_revalidation,
+			..secondary_chain_config.into()
+		},
 		primary_chain_full_node.client.clone(),
 		primary_chain_full_node.network.clone(),
 		&primary_chain_full_node.select_chain,
 		primary_chain_full_node.imported_block_notification_stream.subscribe().then(
 			|imported_block_notification| async move { imported_block_notification.block_number },
 		),
-		primary_chain_full_node.new_slot_notification_stream.subscribe().then(
-			|slot_notification| async move {
-				(
-					slot_notification.new_slot_info.slot,
-					slot_notification.new_slot_info.global_challenge,
-				)
-			},
-		),
+		{
+			let new_slot_notification_stream =
+				primary_chain_full_node.new_slot_notification_stream.clone();
+			move || {
+				new_slot_notification_stream.subscribe().then(|slot_notification| async move {
+					(
+						slot_notification.new_slot_info.slot,
+						slot_notification.new_slot_info.global_challenge,
+					)
+				})
+			}
+		},
 	)
 	.await?;
 
@@ -203,6 +216,8 @@ pub struct TestNodeBuilder {
 	secondary_nodes: Vec<MultiaddrWithPeerId>,
 	secondary_nodes_exclusive: bool,
 	primary_nodes: Vec<MultiaddrWithPeerId>,
+	executor_config: ExecutorConfig,
+	transaction_pool_revalidation: RevalidationType,
 }
 
 impl TestNodeBuilder {
@@ -218,6 +233,8 @@ impl TestNodeBuilder {
 			secondary_nodes: Vec::new(),
 			secondary_nodes_exclusive: false,
 			primary_nodes: Vec::new(),
+			executor_config: ExecutorConfig::default(),
+			transaction_pool_revalidation: RevalidationType::Full,
 		}
 	}
 
@@ -275,6 +292,21 @@ impl TestNodeBuilder {
 		self
 	}
 
+	/// Run the executor with the given tunables instead of the defaults.
+	pub fn executor_config(mut self, executor_config: ExecutorConfig) -> Self {
+		self.executor_config = executor_config;
+		self
+	}
+
+	/// Revalidate the transaction pool with the given strategy instead of the full revalidation.
+	pub fn transaction_pool_revalidation(
+		mut self,
+		transaction_pool_revalidation: RevalidationType,
+	) -> Self {
+		self.transaction_pool_revalidation = transaction_pool_revalidation;
+		self
+	}
+
 	/// Build the [`TestNode`].
 	pub async fn build(self, role: Role) -> TestNode {
 		let secondary_chain_config = node_config(
@@ -298,9 +330,14 @@ impl TestNodeBuilder {
 
 		let multiaddr = secondary_chain_config.network.listen_addresses[0].clone();
 		let (task_manager, client, backend, code_executor, network, rpc_handlers, executor) =
-			run_executor(secondary_chain_config, primary_chain_config)
-				.await
-				.expect("could not start secondary chain node");
+			run_executor(
+				secondary_chain_config,
+				primary_chain_config,
+				self.executor_config,
+				self.transaction_pool_revalidation,
+			)
+			.await
+			.expect("could not start secondary chain node");
 
 		let peer_id = *network.local_peer_id();
 		let addr = MultiaddrWithPeerId { multiaddr, peer_id };
@@ -450,6 +487,19 @@ pub fn construct_extrinsic(
 	caller: Sr25519Keyring,
 	immortal: bool,
 	nonce: u32,
+) -> runtime::UncheckedExtrinsic {
+	construct_extrinsic_with_tip(client, function, caller, immortal, nonce, 0)
+}
+
+/// Construct an extrinsic offering `tip` on top of its fee that can be applied to the test
+/// runtime.
+pub fn construct_extrinsic_with_tip(
+	client: &Client,
+	function: impl Into<runtime::Call>,
+	caller: Sr25519Keyring,
+	immortal: bool,
+	nonce: u32,
+	tip: runtime::Balance,
 ) -> runtime::UncheckedExtrinsic {
 	let function = function.into();
 	let current_block_hash = client.info().best_hash;
@@ -459,7 +509,6 @@ pub fn construct_extrinsic(
 		.checked_next_power_of_two()
 		.map(|c| c / 2)
 		.unwrap_or(2) as u64;
-	let tip = 0;
 	let extra: runtime::SignedExtra = (
 		frame_system::CheckNonZeroSender::<runtime::Runtime>::new(),
 		frame_system::CheckSpecVersion::<runtime::Runtime>::new(),